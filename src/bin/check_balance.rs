@@ -1,12 +1,21 @@
 // src/bin/check_balance.rs
 
 use blockdag::blockdag::BlockDAG;
+use blockdag::config::NodeConfig;
+use blockdag::storage::{LogStore, Storage};
 use tokio::io::{self, AsyncBufReadExt, BufReader};
 
 #[tokio::main]
 async fn main() {
-    // Load BlockDAG from file
-    let dag = BlockDAG::load_from_file("blockdag.json").expect("Failed to load BlockDAG from file");
+    let config = NodeConfig::load().expect("Invalid configuration");
+
+    // Load BlockDAG from the node database, or from a JSON export if there is none
+    let store = LogStore::open_read_only(config.db_path()).expect("Failed to open database");
+    let dag = if store.is_empty().expect("Failed to read database") {
+        BlockDAG::load_from_file(config.dag_path()).expect("Failed to load BlockDAG from file")
+    } else {
+        BlockDAG::load_from_storage(&store).expect("Failed to load BlockDAG from database")
+    };
 
     // Read address from stdin
     let mut reader = BufReader::new(io::stdin());
@@ -16,6 +25,7 @@ async fn main() {
     let address = address.trim();
 
     // Get balance
-    let balance = dag.get_balance(address);
-    println!("Balance for address {}: {}", address, balance);
+    let balance = dag.balance(address);
+    println!("Balance for address {}: {}", address, balance.total);
+    println!("Spendable: {}, immature: {}", balance.spendable, balance.immature);
 }
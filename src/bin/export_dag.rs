@@ -0,0 +1,25 @@
+// src/bin/export_dag.rs
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::config::NodeConfig;
+use blockdag::storage::LogStore;
+use std::io::{self, BufRead};
+
+fn main() {
+    let config = NodeConfig::load().expect("Invalid configuration");
+
+    // Read destination from stdin, defaulting to the data directory's JSON file
+    println!("Enter export file path (default {}):", config.dag_path().display());
+    let mut path = String::new();
+    io::stdin().lock().read_line(&mut path).expect("Failed to read path");
+    let path = match path.trim() {
+        "" => config.dag_path(),
+        other => other.into(),
+    };
+
+    let store = LogStore::open_read_only(config.db_path()).expect("Failed to open database");
+    let dag = BlockDAG::load_from_storage(&store).expect("Failed to load BlockDAG from database");
+    dag.save_to_file(&path).expect("Failed to export BlockDAG");
+
+    println!("Exported {} blocks to {}", dag.blocks.len(), path.display());
+}
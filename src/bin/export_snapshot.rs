@@ -0,0 +1,35 @@
+// src/bin/export_snapshot.rs
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::config::NodeConfig;
+use blockdag::storage::LogStore;
+use std::io::{self, BufRead};
+
+fn main() {
+    let config = NodeConfig::load().expect("Invalid configuration");
+    let store = LogStore::open_read_only(config.db_path()).expect("Failed to open database");
+    let mut dag = BlockDAG::load_from_storage(&store).expect("Failed to load BlockDAG from database");
+    dag.fill_ghostdag();
+
+    // Read the block and destination from stdin, defaulting to the pruning point and the data directory
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    println!("Enter snapshot block hash (default pruning point {}):", dag.pruning_point);
+    let hash = lines.next().expect("Failed to read block hash").expect("Failed to read block hash");
+    let hash = match hash.trim() {
+        "" => dag.pruning_point.clone(),
+        other => other.to_string(),
+    };
+    println!("Enter snapshot file path (default {}):", config.snapshot_path().display());
+    let path = lines.next().expect("Failed to read path").expect("Failed to read path");
+    let path = match path.trim() {
+        "" => config.snapshot_path(),
+        other => other.into(),
+    };
+
+    let snapshot = dag.export_snapshot(&hash).expect("Failed to build snapshot");
+    snapshot.save_to_file(&path).expect("Failed to write snapshot");
+
+    println!("Exported ledger snapshot at block {} to {}", hash, path.display());
+    println!("Commitment: {}", snapshot.commitment);
+}
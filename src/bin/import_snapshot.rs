@@ -0,0 +1,33 @@
+// src/bin/import_snapshot.rs
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::config::NodeConfig;
+use blockdag::snapshot::LedgerSnapshot;
+use blockdag::storage::LogStore;
+use std::io::{self, BufRead};
+
+fn main() {
+    let config = NodeConfig::load().expect("Invalid configuration");
+    config.ensure_data_dir().expect("Failed to create data directory");
+    let params = config.params().expect("Invalid configuration");
+
+    // Read the snapshot file and the commitment it must match from stdin
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    println!("Enter snapshot file path (default {}):", config.snapshot_path().display());
+    let path = lines.next().expect("Failed to read path").expect("Failed to read path");
+    let path = match path.trim() {
+        "" => config.snapshot_path(),
+        other => other.into(),
+    };
+    println!("Enter the expected snapshot commitment:");
+    let commitment = lines.next().expect("Failed to read commitment").expect("Failed to read commitment");
+
+    let snapshot = LedgerSnapshot::load_from_file(&path).expect("Failed to read snapshot");
+    let store = LogStore::open(config.db_path()).expect("Failed to open database");
+    let mut dag = BlockDAG::open(Box::new(store), params, None).expect("Failed to open BlockDAG");
+    let hash = snapshot.block.hash.clone();
+    dag.import_snapshot(snapshot, &commitment).expect("Failed to import snapshot");
+
+    println!("Imported ledger snapshot at block {}; the node will sync from there", hash);
+}
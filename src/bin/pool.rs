@@ -0,0 +1,29 @@
+// src/bin/pool.rs
+
+use log::info;
+use tokio::net::TcpListener;
+use blockdag::config::NodeConfig;
+use blockdag::logging;
+use blockdag::pool::PoolServer;
+use blockdag::rpc::RpcClient;
+use blockdag::shutdown::{listen_for_signals, Shutdown};
+
+/// Runs a mining pool on `--pool-listen` that takes its work from the node at `--rpc-connect`
+/// and pays block rewards to `--mining-address`.
+#[tokio::main]
+async fn main() {
+    let config = NodeConfig::load().expect("Invalid configuration");
+    logging::init(&config).expect("Failed to set up logging");
+    let payout_address = config.mining_address.clone().expect("A payout address is required (--mining-address or BLOCKDAG_MINING_ADDRESS)");
+    let listener = TcpListener::bind(&config.pool_listen).await.expect("Failed to bind pool server");
+
+    let shutdown = Shutdown::new();
+    tokio::spawn(listen_for_signals(shutdown.clone()));
+    let pool = PoolServer::new(RpcClient::new(config.rpc_url()), payout_address, config.share_difficulty);
+    pool.clone().run(listener, shutdown).await;
+
+    for (worker, stats) in pool.stats() {
+        info!(target: "mining", "Worker {}: {} accepted and {} rejected shares, {} blocks", worker, stats.accepted_shares, stats.rejected_shares, stats.blocks_found);
+    }
+    info!(target: "mining", "Stopped cleanly");
+}
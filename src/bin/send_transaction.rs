@@ -1,13 +1,17 @@
 // src/bin/send_transaction.rs
 
 use blockdag::transaction::Transaction;
-use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer};
-use std::io::{self, BufRead, Write};
-use std::net::TcpStream;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use std::io::{self, BufRead};
 use blockdag::messages::Message;
-use hex;
+use blockdag::config::NodeConfig;
+use blockdag::rpc::RpcClient;
+use blockdag::transport::{Connection, NodeIdentity};
+use serde_json::{json, Value};
 
-fn main() {
+#[tokio::main]
+async fn main() {
+    let config = NodeConfig::load().expect("Invalid configuration");
     let mut sender_address = String::new();
     let mut receiver_address = String::new();
     let mut amount = String::new();
@@ -50,8 +54,12 @@ fn main() {
         panic!("Provided sender address does not match derived public key from the private key");
     }
 
+    // The node knows which nonce comes next, counting what is already in its mempool
+    let balance = RpcClient::new(config.rpc_url()).call("getBalance", json!([sender_address])).await.expect("Could not fetch the sender's nonce");
+    let nonce = balance.get("nextNonce").and_then(Value::as_u64).expect("Node did not report a nonce");
+
     // Create and sign transaction
-    let transaction = Transaction::new(sender_address.clone(), receiver_address, amount, fee, "".to_string());
+    let transaction = Transaction::new(sender_address.clone(), receiver_address, amount, fee, "".to_string()).with_nonce(nonce);
     let message = transaction.calculate_hash();
     let signature = Keypair { public: public_key, secret: private_key }.sign(message.as_bytes());
     let signed_transaction = Transaction {
@@ -60,10 +68,9 @@ fn main() {
     };
 
     // Connect to server and send transaction
-    let mut stream = TcpStream::connect("127.0.0.1:8080").expect("Could not connect to server");
+    let mut conn = Connection::connect(&config.node_address(), config.params().unwrap().magic, config.transport, &NodeIdentity::generate()).await.expect("Could not connect to server");
     let message = Message::NewTransaction(signed_transaction);
-    let serialized_message = serde_json::to_string(&message).unwrap();
-    stream.write_all(serialized_message.as_bytes()).expect("Failed to send transaction");
+    conn.send(&message).await.expect("Failed to send transaction");
 
     println!("Transaction added successfully!");
 }
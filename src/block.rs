@@ -1,8 +1,9 @@
 // src/block.rs
 
-use sha2::{Sha256, Digest};
+use std::io::{Error, ErrorKind};
 use serde::{Serialize, Deserialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::ledger::{hash_leaf, merkle_root};
+use crate::pow::{self, PowHash};
 use crate::transaction::Transaction;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -17,35 +18,160 @@ pub struct Block {
     pub reward: u64,
     pub difficulty: u64,
     pub message: Option<String>,
+    /// Root of the ledger state of this block's past; empty on blocks predating state commitments.
+    #[serde(default)]
+    pub state_commitment: String,
+    /// Merkle root of the transaction ids; empty on blocks whose hash covers the transactions
+    /// themselves.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub transactions_root: String,
+}
+
+/// Everything about a block except its transactions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: u128,
+    pub previous_hashes: Vec<String>,
+    pub hash: String,
+    pub nonce: u64,
+    pub difficulty: u64,
+    pub transaction_count: usize,
+    #[serde(default)]
+    pub state_commitment: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub transactions_root: String,
+}
+
+/// A block waiting for a nonce: what a miner needs to search for proof of work.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockTemplate {
+    pub index: u64,
+    pub timestamp: u128,
+    pub previous_hashes: Vec<String>,
+    pub transactions: Vec<Transaction>,
+    pub reward: u64,
+    pub difficulty: u64,
+    pub state_commitment: String,
+    /// Name of the network's proof-of-work function.
+    #[serde(default = "default_pow")]
+    pub pow: String,
+}
+
+fn default_pow() -> String {
+    pow::SHA256.name().to_string()
+}
+
+impl BlockTemplate {
+    /// The proof-of-work function the template must be mined with.
+    pub fn pow(&self) -> Result<&'static dyn PowHash, Error> {
+        pow::by_name(&self.pow).ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Unsupported proof-of-work function: {}", self.pow)))
+    }
+
+    pub fn hash_with_nonce(&self, pow: &dyn PowHash, nonce: u64) -> String {
+        self.hash_with_root(pow, nonce, &Block::transactions_root(&self.transactions))
+    }
+
+    /// Like [`hash_with_nonce`](Self::hash_with_nonce), with the transactions root computed once
+    /// by the caller.
+    pub fn hash_with_root(&self, pow: &dyn PowHash, nonce: u64, transactions_root: &str) -> String {
+        Block::calculate_header_hash(pow, self.index, self.timestamp, &self.previous_hashes, nonce, self.difficulty, transactions_root, &self.state_commitment)
+    }
+
+    /// The same template with `extra_nonce` written into its coinbase, giving a fresh nonce space.
+    pub fn with_extra_nonce(&self, extra_nonce: u64) -> BlockTemplate {
+        let mut template = self.clone();
+        if let Some(coinbase) = template.transactions.iter_mut().rev().find(|tx| tx.sender == "0") {
+            // Extra nonce 0 leaves the coinbase as it was
+            coinbase.signature = if extra_nonce == 0 { String::new() } else { format!("{:016x}", extra_nonce) };
+        }
+        template
+    }
+
+    /// Completes the template with a nonce that satisfies its difficulty.
+    pub fn into_block(self, pow: &dyn PowHash, nonce: u64) -> Block {
+        let transactions_root = Block::transactions_root(&self.transactions);
+        Block {
+            hash: self.hash_with_root(pow, nonce, &transactions_root),
+            index: self.index,
+            timestamp: self.timestamp,
+            previous_hashes: self.previous_hashes,
+            nonce,
+            transactions: self.transactions,
+            weight: 0,
+            reward: self.reward,
+            difficulty: self.difficulty,
+            message: None,
+            state_commitment: self.state_commitment,
+            transactions_root,
+        }
+    }
 }
 
 impl Block {
-    pub fn new(index: u64, previous_hashes: Vec<String>, transactions: Vec<Transaction>, reward: u64, message: Option<String>, difficulty: u64) -> Block {
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-        let (nonce, hash, mining_time) = Block::mine_block(index, timestamp, &previous_hashes, &transactions, difficulty);
-        println!("Block mined in {} ms with difficulty {}", mining_time, difficulty);
-        Block { index, timestamp, previous_hashes, hash, nonce, transactions, weight: 0, reward, difficulty, message }
-    }
-
-    pub fn mine_block(index: u64, timestamp: u128, previous_hashes: &Vec<String>, transactions: &Vec<Transaction>, difficulty: u64) -> (u64, String, u128) {
-        let start_time = SystemTime::now();
-        let mut nonce = 0;
-        let target_prefix = "0".repeat(difficulty as usize); // Adjust target prefix based on difficulty
-        loop {
-            let hash = Block::calculate_hash(index, timestamp, previous_hashes, nonce, transactions);
-            if &hash[..difficulty as usize] == target_prefix {
-                let end_time = SystemTime::now();
-                let mining_time = end_time.duration_since(start_time).unwrap().as_millis();
-                return (nonce, hash, mining_time);
-            }
-            nonce += 1;
+    /// Whether a hex hash has at least `difficulty` leading zeros.
+    pub fn meets_difficulty(hash: &str, difficulty: u64) -> bool {
+        let difficulty = difficulty as usize;
+        hash.len() >= difficulty && hash.bytes().take(difficulty).all(|byte| byte == b'0')
+    }
+
+    pub fn calculate_hash(pow: &dyn PowHash, index: u64, timestamp: u128, previous_hashes: &Vec<String>, nonce: u64, transactions: &Vec<Transaction>, state_commitment: &str) -> String {
+        let mut preimage = format!("{}{}{:?}{}{:?}", index, timestamp, previous_hashes, nonce, transactions);
+        // Empty on blocks from before state commitments, which so keep their hashes
+        preimage.push_str(state_commitment);
+        hex::encode(pow.hash(preimage.as_bytes()))
+    }
+
+    /// Hash of a block that commits to its transactions through their root, so that it can be
+    /// recomputed from the header alone. Unlike the legacy hash it also covers the difficulty.
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_header_hash(pow: &dyn PowHash, index: u64, timestamp: u128, previous_hashes: &[String], nonce: u64, difficulty: u64, transactions_root: &str, state_commitment: &str) -> String {
+        let preimage = format!("v2:{}:{}:{:?}:{}:{}:{}:{}", index, timestamp, previous_hashes, nonce, difficulty, transactions_root, state_commitment);
+        hex::encode(pow.hash(preimage.as_bytes()))
+    }
+
+    /// Merkle root over the ids of `transactions`, in block order.
+    pub fn transactions_root(transactions: &[Transaction]) -> String {
+        hex::encode(merkle_root(transactions.iter().map(|tx| hash_leaf(&tx.txid())).collect()))
+    }
+
+    /// Hash the block's contents imply, by whichever rule it was mined under.
+    pub fn compute_hash(&self, pow: &dyn PowHash) -> String {
+        if self.transactions_root.is_empty() {
+            Block::calculate_hash(pow, self.index, self.timestamp, &self.previous_hashes, self.nonce, &self.transactions, &self.state_commitment)
+        } else {
+            Block::calculate_header_hash(pow, self.index, self.timestamp, &self.previous_hashes, self.nonce, self.difficulty, &self.transactions_root, &self.state_commitment)
         }
     }
 
-    pub fn calculate_hash(index: u64, timestamp: u128, previous_hashes: &Vec<String>, nonce: u64, transactions: &Vec<Transaction>) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(format!("{}{}{:?}{}{:?}", index, timestamp, previous_hashes, nonce, transactions));
-        let result = hasher.finalize();
-        hex::encode(result)
+    /// Whether the block's hash and transactions root match what it carries.
+    pub fn matches_contents(&self, pow: &dyn PowHash) -> bool {
+        let root_matches = self.transactions_root.is_empty() || self.transactions_root == Block::transactions_root(&self.transactions);
+        root_matches && self.compute_hash(pow) == self.hash
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp,
+            previous_hashes: self.previous_hashes.clone(),
+            hash: self.hash.clone(),
+            nonce: self.nonce,
+            difficulty: self.difficulty,
+            transaction_count: self.transactions.len(),
+            state_commitment: self.state_commitment.clone(),
+            transactions_root: self.transactions_root.clone(),
+        }
+    }
+}
+
+impl BlockHeader {
+    /// Hash the header's fields imply; `None` for legacy blocks, whose hash covers transactions
+    /// the header doesn't carry.
+    pub fn compute_hash(&self, pow: &dyn PowHash) -> Option<String> {
+        if self.transactions_root.is_empty() {
+            return None;
+        }
+        Some(Block::calculate_header_hash(pow, self.index, self.timestamp, &self.previous_hashes, self.nonce, self.difficulty, &self.transactions_root, &self.state_commitment))
     }
 }
@@ -2,15 +2,32 @@
 
 use std::collections::{HashMap, HashSet};
 use serde::{Serialize, Deserialize};
-use crate::block::Block;
+use serde_json::{Map, Value};
+use log::{error, info, warn};
+use crate::block::{Block, BlockHeader, BlockTemplate};
+use crate::events::{EventBus, NodeEvent};
+use crate::ghostdag::GhostdagData;
+use crate::indexer::TransactionIndex;
+use crate::ledger::LedgerState;
+use crate::metrics::Metrics;
+use crate::reachability::ReachabilityIndex;
 use crate::transaction::Transaction;
 use crate::wallet::Wallet;
-use crate::constants::{INITIAL_BLOCK_REWARD, HALVING_INTERVAL, TARGET_BLOCK_TIME, DIFFICULTY_ADJUSTMENT_INTERVAL, TOTAL_SUPPLY};
+use crate::params::{NetworkParams, MAINNET};
+use crate::pruning::ProofRequests;
+use crate::schema::{self, CURRENT_SCHEMA_VERSION, SCHEMA_VERSION_KEY};
+use crate::storage::{write_atomic, Keyspace, Storage, WriteBatch};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CHAIN_STATE_KEY: &str = "chain";
 
 #[derive(Serialize, Deserialize)]
 pub struct BlockDAG {
+    pub network: String,
     pub blocks: HashMap<String, Block>,
     pub tips: Vec<String>,
     pub pending_transactions: Vec<Transaction>,
@@ -19,148 +36,660 @@ pub struct BlockDAG {
     pub block_times: Vec<u128>, // Track block mining times
     pub block_count: u64,       // Track the number of blocks mined
     pub current_block_reward: u64, // Track the current block reward
+    pub ghostdag: HashMap<String, GhostdagData>,
+    pub pruning_point: String,
+    pub pruning_snapshot: LedgerState, // Ledger state of the pruning point's past
+    pub pruned_headers: HashMap<String, BlockHeader>,
+    pub pruned_block_count: u64,
+    /// Also drop the headers of pruned blocks, keeping only their GHOSTDAG data.
+    #[serde(skip)]
+    pub prune_headers: bool,
+    #[serde(skip)]
+    pub(crate) newly_pruned: Vec<String>,
+    /// Accepted blocks whose write failed, written again with the next block or flush.
+    #[serde(skip)]
+    unpersisted: Vec<String>,
+    #[serde(skip)]
+    pub(crate) reachability: ReachabilityIndex,
+    #[serde(skip)]
+    pub(crate) events: EventBus,
+    #[serde(skip)]
+    pub(crate) tx_index: Option<TransactionIndex>,
+    #[serde(skip)]
+    pub(crate) proof_requests: ProofRequests,
+    #[serde(skip)]
+    pub(crate) metrics: Arc<Metrics>,
+    #[serde(skip)]
+    store: Option<Box<dyn Storage>>,
+}
+
+/// Everything in `BlockDAG` besides blocks and tips, persisted under the state keyspace.
+#[derive(Serialize, Deserialize)]
+struct ChainState {
+    network: String,
+    pending_transactions: Vec<Transaction>,
+    current_supply: u64,
+    difficulty: u64,
+    block_times: Vec<u128>,
+    block_count: u64,
+    current_block_reward: u64,
+    pruning_point: String,
+    pruning_snapshot: LedgerState,
+    pruned_block_count: u64,
+}
+
+impl Default for BlockDAG {
+    fn default() -> Self {
+        BlockDAG::new(&MAINNET)
+    }
 }
 
 impl BlockDAG {
-    pub fn new() -> BlockDAG {
-        let genesis_message = Some("Genesis Block - Welcome to BlockDAG!".to_string());
-        let initial_difficulty = 4;
-        let genesis_block = Block::new(0, vec!["0".to_string()], vec![], 0, genesis_message, initial_difficulty);
+    pub fn new(params: &'static NetworkParams) -> BlockDAG {
+        let genesis_block = params.genesis_block();
         let genesis_hash = genesis_block.hash.clone();
         let mut blocks = HashMap::new();
         blocks.insert(genesis_hash.clone(), genesis_block);
+        let mut ghostdag = HashMap::new();
+        ghostdag.insert(genesis_hash.clone(), GhostdagData::default());
+        let mut reachability = ReachabilityIndex::default();
+        reachability.add_block(&genesis_hash, None, &[]);
         BlockDAG {
+            network: params.name.to_string(),
             blocks,
-            tips: vec![genesis_hash],
+            tips: vec![genesis_hash.clone()],
             pending_transactions: vec![],
             current_supply: 0,
-            difficulty: initial_difficulty,
+            difficulty: params.genesis.difficulty,
             block_times: vec![],
             block_count: 1, // Start with the genesis block
-            current_block_reward: INITIAL_BLOCK_REWARD,
+            current_block_reward: params.initial_block_reward,
+            ghostdag,
+            pruning_point: genesis_hash,
+            pruning_snapshot: LedgerState::default(),
+            pruned_headers: HashMap::new(),
+            pruned_block_count: 0,
+            prune_headers: false,
+            newly_pruned: vec![],
+            unpersisted: vec![],
+            reachability,
+            events: EventBus::default(),
+            tx_index: None,
+            proof_requests: ProofRequests::default(),
+            metrics: Arc::default(),
+            store: None,
+        }
+    }
+
+    /// Opens the DAG kept in `store`. An empty store is seeded from the JSON export at
+    /// `import`, if one exists, or else from the network's genesis block.
+    pub fn open(store: Box<dyn Storage>, params: &'static NetworkParams, import: Option<&Path>) -> Result<BlockDAG, Error> {
+        let dag = if store.is_empty()? {
+            let mut dag = match import {
+                Some(path) if path.exists() => BlockDAG::load_from_file(path)?,
+                _ => BlockDAG::new(params),
+            };
+            dag.store = Some(store);
+            dag.persist_all()?;
+            dag
+        } else {
+            let stored_version = BlockDAG::stored_schema_version(store.as_ref())?;
+            let mut dag = BlockDAG::load_from_storage(store.as_ref())?;
+            let filled = dag.fill_ghostdag();
+            dag.store = Some(store);
+            if stored_version < CURRENT_SCHEMA_VERSION {
+                // Older versions counted the rewards of blocks since coloured red into the supply
+                dag.current_supply = dag.virtual_ledger().supply;
+                dag.rewrite_store()?;
+            } else if !filled.is_empty() {
+                let mut batch = WriteBatch::new();
+                for hash in &filled {
+                    batch.put(Keyspace::Ghostdag, hash, serde_json::to_vec(&dag.ghostdag[hash])?);
+                }
+                dag.write(batch)?;
+            }
+            dag
+        };
+        if dag.network != params.name {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Database belongs to {}, not {}", dag.network, params.name)));
+        }
+        dag.verify_integrity()?;
+        Ok(dag)
+    }
+
+    /// Checks that the loaded DAG is internally consistent, so a damaged database is reported
+    /// instead of being silently replaced or extended.
+    pub fn verify_integrity(&self) -> Result<(), Error> {
+        let fail = |reason: String| Err(Error::new(ErrorKind::InvalidData, format!("Integrity check failed: {}", reason)));
+        let genesis_hash = self.params().genesis.hash;
+        if self.pruning_point == genesis_hash && !self.blocks.contains_key(genesis_hash) {
+            return fail(format!("genesis block of {} is missing", self.network));
+        }
+        if !self.blocks.contains_key(&self.pruning_point) {
+            return fail(format!("pruning point {} is missing", self.pruning_point));
+        }
+        for (hash, block) in &self.blocks {
+            if hash != &block.hash {
+                return fail(format!("block {} is stored under key {}", block.hash, hash));
+            }
+            if !block.matches_contents(self.params().pow) {
+                return fail(format!("block {} does not match its contents", hash));
+            }
+            // Parents of a pruning point a node bootstrapped from are legitimately unknown
+            for parent in &block.previous_hashes {
+                if parent != "0" && !self.has_block(parent) && hash != &self.pruning_point {
+                    return fail(format!("parent {} of block {} is missing", parent, hash));
+                }
+            }
+            if !self.ghostdag.contains_key(hash) {
+                return fail(format!("GHOSTDAG data of block {} is missing", hash));
+            }
+        }
+        if self.tips.is_empty() {
+            return fail("no tips".to_string());
+        }
+        for tip in &self.tips {
+            if !self.blocks.contains_key(tip) {
+                return fail(format!("tip {} is missing", tip));
+            }
+        }
+        Ok(())
+    }
+
+    /// Schema version of a store; stores predating the version header are v1.
+    fn stored_schema_version(store: &dyn Storage) -> Result<u64, Error> {
+        match store.get(Keyspace::State, SCHEMA_VERSION_KEY)? {
+            Some(bytes) => String::from_utf8_lossy(&bytes).parse()
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid schema version in store")),
+            None => Ok(1),
+        }
+    }
+
+    /// Rebuilds a detached DAG from the contents of a store, migrating older schemas in memory.
+    pub fn load_from_storage(store: &dyn Storage) -> Result<BlockDAG, Error> {
+        let state = store.get(Keyspace::State, CHAIN_STATE_KEY)?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Store holds no chain state"))?;
+        let mut doc: Value = serde_json::from_slice(&state)?;
+        let mut blocks = Map::new();
+        for (hash, bytes) in store.scan(Keyspace::Blocks)? {
+            blocks.insert(hash, serde_json::from_slice(&bytes)?);
+        }
+        let mut pruned_headers = Map::new();
+        for (hash, bytes) in store.scan(Keyspace::Headers)? {
+            if !blocks.contains_key(&hash) {
+                pruned_headers.insert(hash, serde_json::from_slice(&bytes)?);
+            }
+        }
+        let mut ghostdag = Map::new();
+        for (hash, bytes) in store.scan(Keyspace::Ghostdag)? {
+            ghostdag.insert(hash, serde_json::from_slice(&bytes)?);
+        }
+        let tips = store.scan(Keyspace::Tips)?.into_iter().map(|(hash, _)| Value::from(hash)).collect();
+        let fields = doc.as_object_mut().ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid chain state in store"))?;
+        fields.insert("blocks".to_string(), Value::Object(blocks));
+        fields.insert("pruned_headers".to_string(), Value::Object(pruned_headers));
+        fields.insert("ghostdag".to_string(), Value::Object(ghostdag));
+        fields.insert("tips".to_string(), Value::Array(tips));
+        fields.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(BlockDAG::stored_schema_version(store)?));
+        schema::migrate(&mut doc)?;
+        let dag: BlockDAG = serde_json::from_value(doc)?;
+        dag.check_network()?;
+        Ok(dag)
+    }
+
+    /// Replaces the attached store's contents with the in-memory DAG in one batch.
+    pub(crate) fn rewrite_store(&mut self) -> Result<(), Error> {
+        let store = match self.store.as_ref() {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        let mut batch = WriteBatch::new();
+        for keyspace in Keyspace::ALL {
+            for (key, _) in store.scan(keyspace)? {
+                batch.delete(keyspace, &key);
+            }
+        }
+        self.put_all(&mut batch)?;
+        self.write(batch)
+    }
+
+    fn chain_state(&self) -> Result<Vec<u8>, Error> {
+        let state = ChainState {
+            network: self.network.clone(),
+            pending_transactions: self.pending_transactions.clone(),
+            current_supply: self.current_supply,
+            difficulty: self.difficulty,
+            block_times: self.block_times.clone(),
+            block_count: self.block_count,
+            current_block_reward: self.current_block_reward,
+            pruning_point: self.pruning_point.clone(),
+            pruning_snapshot: self.pruning_snapshot.clone(),
+            pruned_block_count: self.pruned_block_count,
+        };
+        Ok(serde_json::to_vec(&state)?)
+    }
+
+    fn put_block(batch: &mut WriteBatch, block: &Block) -> Result<(), Error> {
+        batch.put(Keyspace::Blocks, &block.hash, serde_json::to_vec(block)?);
+        batch.put(Keyspace::Headers, &block.hash, serde_json::to_vec(&block.header())?);
+        batch.put(Keyspace::Index, &format!("{:020}:{}", block.index, block.hash), block.hash.as_bytes().to_vec());
+        Ok(())
+    }
+
+    fn write(&mut self, batch: WriteBatch) -> Result<(), Error> {
+        match self.store.as_mut() {
+            Some(store) => store.write_batch(batch),
+            None => Ok(()),
+        }
+    }
+
+    fn put_all(&self, batch: &mut WriteBatch) -> Result<(), Error> {
+        for block in self.blocks.values() {
+            BlockDAG::put_block(batch, block)?;
+        }
+        for (hash, header) in &self.pruned_headers {
+            batch.put(Keyspace::Headers, hash, serde_json::to_vec(header)?);
+        }
+        for (hash, data) in &self.ghostdag {
+            batch.put(Keyspace::Ghostdag, hash, serde_json::to_vec(data)?);
+        }
+        for tip in &self.tips {
+            batch.put(Keyspace::Tips, tip, vec![]);
+        }
+        batch.put(Keyspace::State, CHAIN_STATE_KEY, self.chain_state()?);
+        batch.put(Keyspace::State, SCHEMA_VERSION_KEY, CURRENT_SCHEMA_VERSION.to_string().into_bytes());
+        Ok(())
+    }
+
+    /// Writes the chain state, including the mempool, which only reaches the store with blocks
+    /// otherwise, and any block an earlier write failed to store. Called on shutdown.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some(hash) = self.unpersisted.pop() {
+            return self.persist_block(&hash);
+        }
+        let mut batch = WriteBatch::new();
+        batch.put(Keyspace::State, CHAIN_STATE_KEY, self.chain_state()?);
+        self.write(batch)
+    }
+
+    /// Writes the whole DAG to the attached store in a single batch.
+    fn persist_all(&mut self) -> Result<(), Error> {
+        let mut batch = WriteBatch::new();
+        self.put_all(&mut batch)?;
+        self.write(batch)
+    }
+
+    /// Commits an accepted block together with the tips, chain state and pruning it caused. If the
+    /// write fails, the block is written again with the next one, so the store catches up once
+    /// it works again.
+    pub fn persist_block(&mut self, hash: &str) -> Result<(), Error> {
+        let mut hashes = std::mem::take(&mut self.unpersisted);
+        hashes.push(hash.to_string());
+        let newly_pruned = std::mem::take(&mut self.newly_pruned);
+        let result = match self.persist_batch(&hashes, &newly_pruned) {
+            Ok(Some(batch)) => self.write(batch),
+            Ok(None) => return Ok(()),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.unpersisted = hashes;
+            self.newly_pruned = newly_pruned;
         }
+        result
     }
 
-    pub fn load_from_file(filename: &str) -> Result<BlockDAG, std::io::Error> {
+    fn persist_batch(&self, hashes: &[String], newly_pruned: &[String]) -> Result<Option<WriteBatch>, Error> {
+        let store = match self.store.as_ref() {
+            Some(store) => store,
+            None => return Ok(None),
+        };
+        let mut batch = WriteBatch::new();
+        for hash in hashes {
+            if let Some(block) = self.blocks.get(hash) {
+                BlockDAG::put_block(&mut batch, block)?;
+            }
+            if let Some(data) = self.ghostdag.get(hash) {
+                batch.put(Keyspace::Ghostdag, hash, serde_json::to_vec(data)?);
+            }
+        }
+        for pruned in newly_pruned {
+            batch.delete(Keyspace::Blocks, pruned);
+            if self.prune_headers {
+                batch.delete(Keyspace::Headers, pruned);
+            }
+            if let Some(data) = self.ghostdag.get(pruned) {
+                batch.put(Keyspace::Ghostdag, pruned, serde_json::to_vec(data)?);
+            }
+        }
+        for (stale, _) in store.scan(Keyspace::Tips)? {
+            if !self.tips.contains(&stale) {
+                batch.delete(Keyspace::Tips, &stale);
+            }
+        }
+        for tip in &self.tips {
+            batch.put(Keyspace::Tips, tip, vec![]);
+        }
+        batch.put(Keyspace::State, CHAIN_STATE_KEY, self.chain_state()?);
+        Ok(Some(batch))
+    }
+
+    /// Consensus parameters of the DAG's network, which is checked whenever a DAG is loaded.
+    pub fn params(&self) -> &'static NetworkParams {
+        NetworkParams::by_name(&self.network).unwrap_or_else(|| panic!("DAG belongs to unknown network {}", self.network))
+    }
+
+    /// Refuses DAGs of networks this build doesn't know instead of running them on other rules.
+    fn check_network(&self) -> Result<(), Error> {
+        match NetworkParams::by_name(&self.network) {
+            Some(_) => Ok(()),
+            None => Err(Error::new(ErrorKind::InvalidData, format!("Unknown network {}", self.network))),
+        }
+    }
+
+    /// Imports a DAG from a JSON export of any schema version.
+    pub fn load_from_file<P: AsRef<Path>>(filename: P) -> Result<BlockDAG, std::io::Error> {
         let mut file = File::open(filename)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        let blockdag: BlockDAG = serde_json::from_str(&contents)?;
+        let mut doc: Value = serde_json::from_str(&contents)?;
+        let version = schema::detect_version(&doc);
+        schema::migrate(&mut doc)?;
+        let mut blockdag: BlockDAG = serde_json::from_value(doc)?;
+        blockdag.check_network()?;
+        blockdag.fill_ghostdag();
+        if version < CURRENT_SCHEMA_VERSION {
+            // As when opening an older store
+            blockdag.current_supply = blockdag.virtual_ledger().supply;
+        }
         Ok(blockdag)
     }
 
-    pub fn save_to_file(&self, filename: &str) -> Result<(), std::io::Error> {
-        let contents = serde_json::to_string_pretty(self)?;
-        let mut file = File::create(filename)?;
-        file.write_all(contents.as_bytes())?;
-        Ok(())
+    /// Exports the DAG as JSON.
+    pub fn save_to_file<P: AsRef<Path>>(&self, filename: P) -> Result<(), std::io::Error> {
+        let mut doc = serde_json::to_value(self)?;
+        if let Some(fields) = doc.as_object_mut() {
+            fields.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(CURRENT_SCHEMA_VERSION));
+        }
+        let contents = serde_json::to_string_pretty(&doc)?;
+        write_atomic(filename.as_ref(), contents.as_bytes())
     }
 
-    pub fn add_transaction(&mut self, transaction: Transaction) {
+    /// Adds a transaction to the mempool if its sender can pay for it with mature funds.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        self.check_mempool_transaction(&transaction)?;
+        self.events.publish(NodeEvent::TransactionAccepted { txid: transaction.txid(), transaction: transaction.clone() });
         self.pending_transactions.push(transaction);
+        Ok(())
     }
 
-    pub fn create_block(&mut self, miner_address: &str) -> Option<Block> {
-        if self.current_supply >= TOTAL_SUPPLY {
-            println!("Total supply reached. No more blocks can be created.");
-            return None;
+    /// Builds the next block to mine on the current tips, paying the reward to `miner_address`.
+    pub fn block_template(&self, miner_address: &str) -> Result<BlockTemplate, Error> {
+        let params = self.params();
+        if self.current_supply >= params.total_supply {
+            return Err(Error::other("Total supply reached. No more blocks can be created."));
         }
 
-        let previous_hashes = self.tips.clone();
-        let index = self.blocks.len() as u64;
-        let transactions = self.pending_transactions.clone();
+        // Tips left behind in the pruning point's anticone can't be built on
+        let previous_hashes: Vec<String> = self.tips.iter().filter(|tip| self.is_dag_ancestor_of(&self.pruning_point, tip)).cloned().collect();
+        let ledger = self.ledger_for_parents(&previous_hashes)?;
+        let state_commitment = ledger.commitment();
+        let index = self.known_block_count();
+        // Only what senders can pay for with mature funds
+        let mut transactions = self.spendable_transactions(&previous_hashes, &ledger);
 
         // Add the mining reward transaction
-        let reward = std::cmp::min(self.current_block_reward, TOTAL_SUPPLY - self.current_supply);
-        let reward_transaction = Transaction::new("0".to_string(), miner_address.to_string(), reward, 0, "".to_string());
-        let mut block_transactions = transactions;
-        block_transactions.push(reward_transaction);
-
-        let new_block = Block::new(index, previous_hashes.clone(), block_transactions, reward, None, self.difficulty);
-        let new_hash = new_block.hash.clone();
-
-        // Validate the new block
-        if self.validate_block(&new_block) {
-            // Update the DAG
-            self.blocks.insert(new_hash.clone(), new_block.clone());
-            self.update_tips(new_hash);
-            self.current_supply += reward;
-            self.block_times.push(new_block.timestamp); // Track block timestamp
-            self.block_count += 1; // Increment block count
-
-            // Adjust difficulty if needed
-            if self.blocks.len() % DIFFICULTY_ADJUSTMENT_INTERVAL == 0 {
-                self.adjust_difficulty();
-            }
+        let reward = self.expected_reward();
+        transactions.push(Transaction::new("0".to_string(), miner_address.to_string(), reward, 0, "".to_string()));
+
+        Ok(BlockTemplate {
+            index,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis(),
+            difficulty: self.required_difficulty(&previous_hashes),
+            previous_hashes,
+            transactions,
+            reward,
+            state_commitment,
+            pow: params.pow.name().to_string(),
+        })
+    }
 
-            // Halve the block reward if necessary
-            if self.block_count % HALVING_INTERVAL == 0 {
-                self.current_block_reward /= 2;
-                println!("Block reward halved to {}", self.current_block_reward);
-            }
+    /// Reward the next block may claim.
+    pub fn expected_reward(&self) -> u64 {
+        let params = self.params();
+        std::cmp::min(self.current_block_reward, params.total_supply.saturating_sub(self.current_supply))
+    }
+
+    /// Accepts a block from a miner or a peer: validates it, adds it to the DAG and updates
+    /// supply, difficulty, reward schedule and mempool. The caller persists it.
+    pub fn submit_block(&mut self, block: Block) -> Result<(), Error> {
+        if self.has_block(&block.hash) {
+            self.metrics.block_rejected("duplicate");
+            return Err(Error::new(ErrorKind::AlreadyExists, format!("Block {} is already known", block.hash)));
+        }
+        if !self.validate_block(&block) {
+            self.drop_invalid_pending(&block.transactions);
+            return Err(Error::new(ErrorKind::InvalidData, format!("Block {} failed validation", block.hash)));
+        }
+        let coinbase: u64 = block.transactions.iter().filter(|tx| tx.sender == "0").map(|tx| tx.amount).sum();
+        if coinbase != block.reward || block.reward > self.expected_reward() {
+            self.metrics.block_rejected("invalid_reward");
+            return Err(Error::new(ErrorKind::InvalidData, format!("Block {} claims an invalid reward", block.hash)));
+        }
+
+        let params = self.params();
+        let included: HashSet<String> = block.transactions.iter().map(Transaction::txid).collect();
+        self.block_times.push(block.timestamp); // Track block timestamp
+        self.block_count += 1; // Increment block count
+        self.insert_block(block);
+        // Rewards of red blocks never reach the ledger, so they aren't issued either
+        let ledger = self.virtual_ledger();
+        self.current_supply = ledger.supply;
+
+        // What the next block on the tips must carry
+        let difficulty = self.required_difficulty(&self.tips);
+        if difficulty != self.difficulty {
+            info!(target: "consensus", "Difficulty retargeted from {} to {}", self.difficulty, difficulty);
+            self.difficulty = difficulty;
+        }
+
+        // Halve the block reward if necessary
+        if self.block_count.is_multiple_of(params.halving_interval) {
+            self.current_block_reward /= 2;
+            info!(target: "consensus", "Block reward halved to {}", self.current_block_reward);
+        }
+
+        // Drop the transactions the block included from the mempool, and those whose nonce a
+        // transaction in another block used up
+        self.pending_transactions.retain(|tx| !included.contains(&tx.txid()) && tx.nonce > ledger.nonce(&tx.sender));
+        Ok(())
+    }
+
+    /// Number of blocks ever added, including pruned ones.
+    pub fn known_block_count(&self) -> u64 {
+        self.blocks.len() as u64 + self.pruned_block_count
+    }
+
+    /// Whether a block is known, with or without its body.
+    pub fn has_block(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash) || self.ghostdag.contains_key(hash)
+    }
+
+    /// Adds a validated block to the DAG, deriving its GHOSTDAG data and advancing the pruning point.
+    pub fn insert_block(&mut self, block: Block) {
+        let hash = block.hash.clone();
+        if self.has_block(&hash) {
+            return;
+        }
+        let old_tips = self.tips.clone();
+        let old_virtual_selected_parent = self.virtual_selected_parent();
+        let data = self.compute_ghostdag(&block.previous_hashes);
+        self.reachability.add_block(&hash, data.selected_parent.as_deref(), &data.mergeset);
+        if let Some(index) = self.tx_index.as_mut() {
+            index.add_block(&block);
+        }
+        self.blocks.insert(hash.clone(), block);
+        self.ghostdag.insert(hash.clone(), data);
+        self.update_tips(hash.clone());
+        self.metrics.block_accepted();
+        self.publish_insert_events(&hash, &old_tips, old_virtual_selected_parent);
+        self.update_pruning_point();
+    }
 
-            // Clear pending transactions
-            self.pending_transactions.clear();
-            return Some(new_block);
+    /// Checks that a block's hash matches its contents and meets its difficulty target.
+    pub fn has_valid_pow(&self, block: &Block) -> bool {
+        // Validate the block's hash
+        if !block.matches_contents(self.params().pow) {
+            return false;
         }
 
-        None
+        // Ensure the hash meets the difficulty target
+        Block::meets_difficulty(&block.hash, block.difficulty)
     }
 
     pub fn validate_block(&self, block: &Block) -> bool {
         // Check if all previous hashes exist in the DAG
         for hash in &block.previous_hashes {
-            if hash != "0" && !self.blocks.contains_key(hash) {
+            if hash != "0" && !self.has_block(hash) {
+                self.metrics.block_rejected("unknown_parent");
                 return false;
             }
         }
 
-        // Validate the block's hash
-        let calculated_hash = Block::calculate_hash(block.index, block.timestamp, &block.previous_hashes, block.nonce, &block.transactions);
-        if calculated_hash != block.hash {
+        if !self.has_valid_pow(block) {
+            self.metrics.block_rejected("invalid_pow");
             return false;
         }
 
-        // Ensure the hash meets the difficulty target
-        let target_prefix = "0".repeat(block.difficulty as usize);
-        if &block.hash[..block.difficulty as usize] != target_prefix {
+        // New blocks must be checkable from their header alone, as pruning point proofs carry only headers
+        if block.transactions_root.is_empty() {
+            warn!(target: "consensus", "Block {} does not commit to a transactions root", block.hash);
+            self.metrics.block_rejected("invalid_pow");
+            return false;
+        }
+
+        // Exactly what the block's own past requires, however our tips have moved on since
+        let required = self.required_difficulty(&block.previous_hashes);
+        if block.difficulty != required || !self.params().allows_difficulty(block.difficulty) {
+            warn!(target: "consensus", "Block {} has difficulty {}, expected {}", block.hash, block.difficulty, required);
+            self.metrics.block_rejected("difficulty");
+            return false;
+        }
+
+        // The header must commit to the ledger state its parents imply
+        let ledger = match self.ledger_for_parents(&block.previous_hashes) {
+            Ok(ledger) => ledger,
+            Err(e) => {
+                warn!(target: "consensus", "Cannot check state commitment of block {}: {}", block.hash, e);
+                self.metrics.block_rejected("state_commitment");
+                return false;
+            }
+        };
+        let expected = ledger.commitment();
+        if expected != block.state_commitment {
+            warn!(target: "consensus", "State commitment mismatch in block {}: expected {}, got {}", block.hash, expected, block.state_commitment);
+            self.metrics.block_rejected("state_commitment");
+            return false;
+        }
+
+        // Reject alternative histories that reorder blocks below the finality point
+        if self.violates_finality(block) {
+            self.metrics.block_rejected("finality");
             return false;
         }
 
         // Validate transactions (simplified for this example)
         for tx in &block.transactions {
-            if tx.sender != "0" {
-                let sender_pub_key = hex::decode(&tx.sender).expect("Invalid sender hex");
-                let public_key = ed25519_dalek::PublicKey::from_bytes(&sender_pub_key).expect("Invalid public key bytes");
-                if !Wallet::verify(&public_key, &tx.calculate_hash(), &tx.signature) {
-                    return false;
-                }
+            if tx.sender != "0" && !BlockDAG::verify_transaction(tx) {
+                self.metrics.block_rejected("invalid_signature");
+                return false;
             }
         }
 
+        // Senders may only spend balance whose mined rewards have matured
+        if let Err(e) = self.check_spendable(&block.previous_hashes, &ledger, &block.transactions) {
+            warn!(target: "consensus", "Block {} spends immature or missing funds: {}", block.hash, e);
+            self.metrics.block_rejected("insufficient_funds");
+            return false;
+        }
+
+        // Each sender's transactions must continue its nonce sequence, so none can be replayed
+        if let Err(e) = ledger.check_nonces(&block.transactions) {
+            warn!(target: "consensus", "Block {} carries a replayed or out of order transaction: {}", block.hash, e);
+            self.metrics.block_rejected("invalid_nonce");
+            return false;
+        }
+
         true
     }
 
-    pub fn adjust_difficulty(&mut self) {
-        let len = self.block_times.len();
-        if len < DIFFICULTY_ADJUSTMENT_INTERVAL {
-            return; // Not enough blocks to adjust difficulty yet
+    /// Drops pending transactions that a rejected block carried and that no block could include:
+    /// forged ones, e.g. admitted by an older build, and those whose nonce is used up. Otherwise
+    /// every template would carry them into another invalid block.
+    fn drop_invalid_pending(&mut self, transactions: &[Transaction]) {
+        let carried: HashSet<String> = transactions.iter().map(Transaction::txid).collect();
+        let ledger = self.virtual_ledger();
+        self.pending_transactions.retain(|tx| {
+            let keep = !carried.contains(&tx.txid()) || (BlockDAG::verify_transaction(tx) && tx.nonce > ledger.nonce(&tx.sender));
+            if !keep {
+                warn!(target: "consensus", "Dropping invalid transaction {} from the mempool", tx.txid());
+            }
+            keep
+        });
+    }
+
+    /// Checks a transaction's signature against its sender's public key.
+    pub fn verify_transaction(tx: &Transaction) -> bool {
+        let sender_pub_key = match hex::decode(&tx.sender) {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        match ed25519_dalek::PublicKey::from_bytes(&sender_pub_key) {
+            Ok(public_key) => Wallet::verify(&public_key, &tx.calculate_hash(), &tx.signature),
+            Err(_) => false,
         }
+    }
 
-        let start_time = self.block_times[len - DIFFICULTY_ADJUSTMENT_INTERVAL];
-        let end_time = self.block_times[len - 1];
-        let actual_time = end_time - start_time;
-        let expected_time = TARGET_BLOCK_TIME * DIFFICULTY_ADJUSTMENT_INTERVAL as u128;
+    /// Difficulty a block on `parents` must carry, from its own past alone: that of its selected
+    /// parent, retargeted at every multiple of `difficulty_window` blue score from the timestamps
+    /// of the `difficulty_window` selected chain blocks ending at that parent.
+    pub fn required_difficulty(&self, parents: &[String]) -> u64 {
+        let params = self.params();
+        let data = self.compute_ghostdag(parents);
+        let (timestamp, current) = match data.selected_parent.as_deref().and_then(|hash| self.timestamp_and_difficulty(hash)) {
+            Some(found) => found,
+            None => return params.genesis.difficulty,
+        };
+        let window = params.difficulty_window;
+        if params.no_retargeting || !data.blue_score.is_multiple_of(window as u64) {
+            return current;
+        }
 
-        if actual_time < expected_time / 2 {
-            self.difficulty += 1;
-            println!("Difficulty increased to {}", self.difficulty);
-        } else if actual_time > expected_time * 2 {
-            if self.difficulty > 1 {
-                self.difficulty -= 1;
-                println!("Difficulty decreased to {}", self.difficulty);
+        let mut start_time = timestamp;
+        let mut hash = data.selected_parent;
+        for _ in 1..window {
+            hash = hash.and_then(|hash| self.ghostdag.get(&hash)?.selected_parent.clone());
+            match hash.as_deref().and_then(|hash| self.timestamp_and_difficulty(hash)) {
+                Some((timestamp, _)) => start_time = timestamp,
+                None => return current, // Not enough blocks to adjust difficulty yet
             }
         }
+        let actual_time = timestamp.saturating_sub(start_time);
+        let expected_time = params.target_block_time * window as u128;
+        if actual_time < expected_time / 2 {
+            current + 1
+        } else if actual_time > expected_time * 2 && current > params.min_difficulty {
+            current - 1
+        } else {
+            current
+        }
+    }
+
+    fn timestamp_and_difficulty(&self, hash: &str) -> Option<(u128, u64)> {
+        match (self.blocks.get(hash), self.pruned_headers.get(hash)) {
+            (Some(block), _) => Some((block.timestamp, block.difficulty)),
+            (None, Some(header)) => Some((header.timestamp, header.difficulty)),
+            (None, None) => None,
+        }
     }
 
     pub fn update_tips(&mut self, new_hash: String) {
@@ -176,89 +705,21 @@ impl BlockDAG {
             new_tips.push(new_hash);
             self.tips = new_tips;
         } else {
-            println!("Error: Block not found in DAG during tip update");
+            error!(target: "consensus", "Error: Block not found in DAG during tip update");
         }
     }
 
     pub fn ghostdag(&mut self) {
-        // Implement the GHOSTDAG algorithm to determine the heaviest sub-tree
-        let mut visited = HashSet::new();
-        let mut weights = HashMap::new();
-
-        // Initialize weights
-        for (hash, block) in &self.blocks {
-            weights.insert(hash.clone(), block.weight);
-        }
-
-        // Compute weights recursively
-        for tip in &self.tips {
-            self.compute_weights(tip, &mut visited, &mut weights);
-        }
-
-        // Update block weights
-        for (hash, weight) in weights {
-            if let Some(block) = self.blocks.get_mut(&hash) {
-                block.weight = weight;
+        // Block weights follow the blue scores maintained on insertion
+        for (hash, block) in self.blocks.iter_mut() {
+            if let Some(data) = self.ghostdag.get(hash) {
+                block.weight = data.blue_score;
             }
         }
     }
 
-    fn compute_weights(&self, hash: &String, visited: &mut HashSet<String>, weights: &mut HashMap<String, u64>) -> u64 {
-        if visited.contains(hash) {
-            return *weights.get(hash).unwrap_or(&0);
-        }
-
-        visited.insert(hash.clone());
-
-        let block = match self.blocks.get(hash) {
-            Some(block) => block,
-            None => {
-                if hash == "0" {
-                    return 0; // Genesis block parent hash is "0", so return 0 weight
-                }
-                println!("Error: Block not found in DAG with hash {}", hash);
-                return 0;
-            }
-        };
-
-        let mut weight = block.transactions.len() as u64 + 1; // Including the block itself
-
-        for parent_hash in &block.previous_hashes {
-            weight += self.compute_weights(parent_hash, visited, weights);
-        }
-
-        weights.insert(hash.clone(), weight);
-        weight
-    }
-
+    /// Balance of `address` in the ledger the virtual block sees, with conflicting spends resolved.
     pub fn get_balance(&self, address: &str) -> u64 {
-        let mut balance = 0;
-        for block in self.blocks.values() {
-            for transaction in &block.transactions {
-                if transaction.receiver == address {
-                    balance += transaction.amount;
-                }
-                if transaction.sender == address {
-                    balance -= transaction.amount;
-                }
-            }
-        }
-        balance
-    }
-
-    pub fn display(&self) {
-        for (hash, block) in &self.blocks {
-            println!("Block Hash: {}", hash);
-            println!("Block Data: {:?}", block);
-            println!("Block Weight: {}", block.weight);
-            println!("Block Reward: {}", block.reward);
-            println!("Block Difficulty: {}", block.difficulty);
-            if let Some(ref message) = block.message {
-                println!("Block Message: {}", message);
-            }
-        }
-        println!("Current Supply: {}", self.current_supply);
-        println!("Current Block Reward: {}", self.current_block_reward);
-        println!("Block Count: {}", self.block_count);
+        self.virtual_ledger().balance(address)
     }
 }
@@ -0,0 +1,464 @@
+// src/config.rs
+
+use serde::{Serialize, Deserialize};
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread;
+use crate::logging::{LogFilter, LogFormat};
+use crate::params::NetworkParams;
+use crate::transport::TransportMode;
+
+const CONFIG_FILE_NAME: &str = "blockdag.toml";
+const DAG_FILE_NAME: &str = "blockdag.json";
+const DB_FILE_NAME: &str = "blockdag.db";
+const SNAPSHOT_FILE_NAME: &str = "snapshot.json";
+const NODE_KEY_FILE_NAME: &str = "node_key.json";
+
+/// Node settings shared by every binary.
+///
+/// Values are layered: built-in defaults, then the TOML file (`--config`, `BLOCKDAG_CONFIG`
+/// or `<data_dir>/blockdag.toml`), then `BLOCKDAG_*` environment variables, then command-line flags.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NodeConfig {
+    pub listen_address: String,
+    pub port: Option<u16>,
+    /// Peers to dial, as `host:port`, or `id@host:port` to insist on that node's key.
+    pub peers: Vec<String>,
+    pub data_dir: PathBuf,
+    pub mining_address: Option<String>,
+    /// Proof-of-work worker threads; all available cores when unset.
+    pub mining_threads: Option<usize>,
+    pub network: String,
+    pub transport: TransportMode,
+    /// Drop headers of pruned blocks too, keeping only their GHOSTDAG data.
+    pub prune_headers: bool,
+    /// JSON-RPC listens on localhost unless configured otherwise.
+    pub rpc_address: String,
+    pub rpc_port: Option<u16>,
+    pub disable_rpc: bool,
+    /// JSON-RPC URL of the node clients such as the standalone miner talk to; this node's own
+    /// RPC server when unset.
+    pub rpc_connect: Option<String>,
+    /// Address the pool server accepts workers on.
+    pub pool_listen: String,
+    /// Pool (`host:port`) the miner works for instead of mining against a node directly.
+    pub pool_connect: Option<String>,
+    /// Leading zeros a pool share needs; one below the block difficulty when unset.
+    pub share_difficulty: Option<u64>,
+    /// Maintain txid and address lookups for the explorer API.
+    pub tx_index: bool,
+    /// Log filter such as `info,network=debug`; adjustable at runtime over RPC.
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Log to this file, relative to the data directory, instead of stdout.
+    pub log_file: Option<PathBuf>,
+    /// Size at which the log file is rotated, and how many rotated files are kept.
+    pub log_max_size: u64,
+    pub log_max_files: usize,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            listen_address: "127.0.0.1".to_string(),
+            port: None,
+            peers: vec![],
+            data_dir: PathBuf::from("."),
+            mining_address: None,
+            mining_threads: None,
+            network: "mainnet".to_string(),
+            transport: TransportMode::Plain,
+            prune_headers: false,
+            rpc_address: "127.0.0.1".to_string(),
+            rpc_port: None,
+            disable_rpc: false,
+            rpc_connect: None,
+            pool_listen: "127.0.0.1:3333".to_string(),
+            pool_connect: None,
+            share_difficulty: None,
+            tx_index: false,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            log_file: None,
+            log_max_size: 10 * 1024 * 1024,
+            log_max_files: 5,
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Builds the configuration from the process environment and command line.
+    pub fn load() -> Result<NodeConfig, Error> {
+        NodeConfig::from_args(env::args().skip(1).collect())
+    }
+
+    pub fn from_args(args: Vec<String>) -> Result<NodeConfig, Error> {
+        let overrides = CliOverrides::parse(args)?;
+
+        let config_path = overrides.config.clone()
+            .or_else(|| env::var("BLOCKDAG_CONFIG").ok().map(PathBuf::from))
+            .or_else(|| {
+                let data_dir = overrides.data_dir.clone()
+                    .or_else(|| env::var("BLOCKDAG_DATA_DIR").ok().map(PathBuf::from))
+                    .unwrap_or_else(|| PathBuf::from("."));
+                let candidate = data_dir.join(CONFIG_FILE_NAME);
+                if candidate.exists() { Some(candidate) } else { None }
+            });
+
+        let mut config = match config_path {
+            Some(path) => NodeConfig::from_file(&path)?,
+            None => NodeConfig::default(),
+        };
+        config.apply_env()?;
+        overrides.apply(&mut config);
+        config.params()?;
+        LogFilter::parse(&config.log_level)?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<NodeConfig, Error> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| invalid(format!("Invalid config file {}: {}", path.display(), e)))
+    }
+
+    fn apply_env(&mut self) -> Result<(), Error> {
+        if let Ok(value) = env::var("BLOCKDAG_LISTEN") {
+            self.listen_address = value;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_PORT") {
+            self.port = Some(parse_port(&value)?);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_PEERS") {
+            self.peers = value.split(',').map(|peer| peer.trim().to_string()).filter(|peer| !peer.is_empty()).collect();
+        }
+        if let Ok(value) = env::var("BLOCKDAG_DATA_DIR") {
+            self.data_dir = PathBuf::from(value);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_MINING_ADDRESS") {
+            self.mining_address = Some(value);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_MINING_THREADS") {
+            self.mining_threads = Some(parse_threads(&value)?);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_NETWORK") {
+            self.network = value;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_TRANSPORT") {
+            self.transport = parse_transport(&value)?;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_PRUNE_HEADERS") {
+            self.prune_headers = parse_bool(&value)?;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_RPC_LISTEN") {
+            self.rpc_address = value;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_RPC_PORT") {
+            self.rpc_port = Some(parse_port(&value)?);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_DISABLE_RPC") {
+            self.disable_rpc = parse_bool(&value)?;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_RPC_CONNECT") {
+            self.rpc_connect = Some(value);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_POOL_LISTEN") {
+            self.pool_listen = value;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_POOL_CONNECT") {
+            self.pool_connect = Some(value);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_SHARE_DIFFICULTY") {
+            self.share_difficulty = Some(parse_difficulty(&value)?);
+        }
+        if let Ok(value) = env::var("BLOCKDAG_TXINDEX") {
+            self.tx_index = parse_bool(&value)?;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_LOG_LEVEL") {
+            self.log_level = value;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_LOG_FORMAT") {
+            self.log_format = parse_log_format(&value)?;
+        }
+        if let Ok(value) = env::var("BLOCKDAG_LOG_FILE") {
+            self.log_file = Some(PathBuf::from(value));
+        }
+        Ok(())
+    }
+
+    pub fn params(&self) -> Result<&'static NetworkParams, Error> {
+        NetworkParams::by_name(&self.network).ok_or_else(|| invalid(format!("Unknown network: {}", self.network)))
+    }
+
+    /// Configured port, falling back to the selected network's default.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or_else(|| self.params().map(|params| params.default_port).unwrap_or(8080))
+    }
+
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port.unwrap_or_else(|| self.params().map(|params| params.default_rpc_port).unwrap_or(8081))
+    }
+
+    /// Worker threads for the proof-of-work search.
+    pub fn mining_threads(&self) -> usize {
+        self.mining_threads.unwrap_or_else(|| thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1))
+    }
+
+    /// Address the JSON-RPC server binds to.
+    pub fn rpc_socket(&self) -> String {
+        socket_address(&self.rpc_address, self.rpc_port())
+    }
+
+    /// URL clients use to reach the JSON-RPC server.
+    pub fn rpc_url(&self) -> String {
+        if let Some(url) = &self.rpc_connect {
+            return url.clone();
+        }
+        format!("http://{}/", socket_address(loopback(&self.rpc_address), self.rpc_port()))
+    }
+
+    /// Address the P2P server binds to.
+    pub fn listen_socket(&self) -> String {
+        socket_address(&self.listen_address, self.port())
+    }
+
+    /// Address local clients (wallet tools, self-connection) use to reach this node.
+    pub fn node_address(&self) -> String {
+        socket_address(loopback(&self.listen_address), self.port())
+    }
+
+    /// JSON export of the DAG, imported automatically into an empty database.
+    pub fn dag_path(&self) -> PathBuf {
+        self.data_dir.join(DAG_FILE_NAME)
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.data_dir.join(DB_FILE_NAME)
+    }
+
+    /// Default location of ledger snapshots written by `export_snapshot`.
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.data_dir.join(SNAPSHOT_FILE_NAME)
+    }
+
+    /// The node's P2P identity key, created on first start.
+    pub fn node_key_path(&self) -> PathBuf {
+        self.data_dir.join(NODE_KEY_FILE_NAME)
+    }
+
+    /// Log file location, if logging to a file.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.log_file.as_ref().map(|file| self.data_dir.join(file))
+    }
+
+    /// Creates the data directory if it does not exist yet.
+    pub fn ensure_data_dir(&self) -> Result<(), Error> {
+        fs::create_dir_all(&self.data_dir)
+    }
+}
+
+#[derive(Default)]
+struct CliOverrides {
+    config: Option<PathBuf>,
+    listen_address: Option<String>,
+    port: Option<u16>,
+    peers: Vec<String>,
+    data_dir: Option<PathBuf>,
+    mining_address: Option<String>,
+    mining_threads: Option<usize>,
+    network: Option<String>,
+    transport: Option<TransportMode>,
+    prune_headers: bool,
+    rpc_address: Option<String>,
+    rpc_port: Option<u16>,
+    disable_rpc: bool,
+    rpc_connect: Option<String>,
+    pool_listen: Option<String>,
+    pool_connect: Option<String>,
+    share_difficulty: Option<u64>,
+    tx_index: bool,
+    log_level: Option<String>,
+    log_format: Option<LogFormat>,
+    log_file: Option<PathBuf>,
+}
+
+impl CliOverrides {
+    fn parse(args: Vec<String>) -> Result<CliOverrides, Error> {
+        let mut overrides = CliOverrides::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            if flag == "--encrypted" {
+                overrides.transport = Some(TransportMode::Encrypted);
+                continue;
+            }
+            if flag == "--prune-headers" {
+                overrides.prune_headers = true;
+                continue;
+            }
+            if flag == "--no-rpc" {
+                overrides.disable_rpc = true;
+                continue;
+            }
+            if flag == "--txindex" {
+                overrides.tx_index = true;
+                continue;
+            }
+            let value = match inline_value {
+                Some(value) => value,
+                None => args.next().ok_or_else(|| invalid(format!("Missing value for {}", flag)))?,
+            };
+            match flag.as_str() {
+                "--config" => overrides.config = Some(PathBuf::from(value)),
+                "--listen" => overrides.listen_address = Some(value),
+                "--port" => overrides.port = Some(parse_port(&value)?),
+                "--peer" => overrides.peers.push(value),
+                "--data-dir" => overrides.data_dir = Some(PathBuf::from(value)),
+                "--mining-address" => overrides.mining_address = Some(value),
+                "--mining-threads" => overrides.mining_threads = Some(parse_threads(&value)?),
+                "--network" => overrides.network = Some(value),
+                "--transport" => overrides.transport = Some(parse_transport(&value)?),
+                "--rpc-listen" => overrides.rpc_address = Some(value),
+                "--rpc-port" => overrides.rpc_port = Some(parse_port(&value)?),
+                "--rpc-connect" => overrides.rpc_connect = Some(value),
+                "--pool-listen" => overrides.pool_listen = Some(value),
+                "--pool-connect" => overrides.pool_connect = Some(value),
+                "--share-difficulty" => overrides.share_difficulty = Some(parse_difficulty(&value)?),
+                "--log-level" => overrides.log_level = Some(value),
+                "--log-format" => overrides.log_format = Some(parse_log_format(&value)?),
+                "--log-file" => overrides.log_file = Some(PathBuf::from(value)),
+                _ => return Err(invalid(format!("Unknown option {}", flag))),
+            }
+        }
+        Ok(overrides)
+    }
+
+    fn apply(self, config: &mut NodeConfig) {
+        if let Some(listen_address) = self.listen_address {
+            config.listen_address = listen_address;
+        }
+        if let Some(port) = self.port {
+            config.port = Some(port);
+        }
+        if !self.peers.is_empty() {
+            config.peers = self.peers;
+        }
+        if let Some(data_dir) = self.data_dir {
+            config.data_dir = data_dir;
+        }
+        if let Some(mining_address) = self.mining_address {
+            config.mining_address = Some(mining_address);
+        }
+        if let Some(mining_threads) = self.mining_threads {
+            config.mining_threads = Some(mining_threads);
+        }
+        if let Some(network) = self.network {
+            config.network = network;
+        }
+        if let Some(transport) = self.transport {
+            config.transport = transport;
+        }
+        if self.prune_headers {
+            config.prune_headers = true;
+        }
+        if let Some(rpc_address) = self.rpc_address {
+            config.rpc_address = rpc_address;
+        }
+        if let Some(rpc_port) = self.rpc_port {
+            config.rpc_port = Some(rpc_port);
+        }
+        if self.disable_rpc {
+            config.disable_rpc = true;
+        }
+        if let Some(rpc_connect) = self.rpc_connect {
+            config.rpc_connect = Some(rpc_connect);
+        }
+        if let Some(pool_listen) = self.pool_listen {
+            config.pool_listen = pool_listen;
+        }
+        if let Some(pool_connect) = self.pool_connect {
+            config.pool_connect = Some(pool_connect);
+        }
+        if let Some(share_difficulty) = self.share_difficulty {
+            config.share_difficulty = Some(share_difficulty);
+        }
+        if self.tx_index {
+            config.tx_index = true;
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        if let Some(log_file) = self.log_file {
+            config.log_file = Some(log_file);
+        }
+    }
+}
+
+/// Local address to reach a server bound to `host`, which may be a wildcard address.
+fn loopback(host: &str) -> &str {
+    match host {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        other => other,
+    }
+}
+
+/// `host:port`, with IPv6 hosts in brackets so the result parses as a socket address.
+fn socket_address(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, Error> {
+    match value.trim() {
+        "1" | "true" | "yes" => Ok(true),
+        "0" | "false" | "no" => Ok(false),
+        _ => Err(invalid(format!("Invalid boolean: {}", value))),
+    }
+}
+
+fn parse_port(value: &str) -> Result<u16, Error> {
+    value.trim().parse().map_err(|_| invalid(format!("Invalid port: {}", value)))
+}
+
+fn parse_threads(value: &str) -> Result<usize, Error> {
+    match value.trim().parse() {
+        Ok(threads) if threads > 0 => Ok(threads),
+        _ => Err(invalid(format!("Invalid thread count: {}", value))),
+    }
+}
+
+fn parse_difficulty(value: &str) -> Result<u64, Error> {
+    value.trim().parse().map_err(|_| invalid(format!("Invalid difficulty: {}", value)))
+}
+
+fn parse_transport(value: &str) -> Result<TransportMode, Error> {
+    match value.trim().to_lowercase().as_str() {
+        "plain" => Ok(TransportMode::Plain),
+        "encrypted" => Ok(TransportMode::Encrypted),
+        _ => Err(invalid(format!("Invalid transport: {} (expected plain or encrypted)", value))),
+    }
+}
+
+fn parse_log_format(value: &str) -> Result<LogFormat, Error> {
+    match value.trim().to_lowercase().as_str() {
+        "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(invalid(format!("Invalid log format: {} (expected text or json)", value))),
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
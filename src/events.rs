@@ -0,0 +1,119 @@
+// src/events.rs
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use log::info;
+use crate::blockdag::BlockDAG;
+use crate::transaction::Transaction;
+
+/// Events buffered per subscriber; slow subscribers skip ahead instead of blocking the node.
+const EVENT_BUFFER: usize = 1024;
+
+/// Something that changed in the node's view of the DAG.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum NodeEvent {
+    BlockAdded { hash: String, blue_score: u64, selected_parent: Option<String> },
+    TipsChanged { tips: Vec<String> },
+    VirtualSelectedParentChanged { hash: String, blue_score: u64 },
+    TransactionAccepted { txid: String, transaction: Transaction },
+    /// The selected chain switched branches: `removed` left it and `added` joined it, tips first.
+    Reorg { removed: Vec<String>, added: Vec<String> },
+    FinalityConflict { hash: String, finality_point: String },
+}
+
+impl NodeEvent {
+    /// Name clients subscribe to, matching the serialized `event` tag.
+    pub fn topic(&self) -> &'static str {
+        match self {
+            NodeEvent::BlockAdded { .. } => "blockAdded",
+            NodeEvent::TipsChanged { .. } => "tipsChanged",
+            NodeEvent::VirtualSelectedParentChanged { .. } => "virtualSelectedParentChanged",
+            NodeEvent::TransactionAccepted { .. } => "transactionAccepted",
+            NodeEvent::Reorg { .. } => "reorg",
+            NodeEvent::FinalityConflict { .. } => "finalityConflict",
+        }
+    }
+
+    pub const TOPICS: [&'static str; 6] = [
+        "blockAdded", "tipsChanged", "virtualSelectedParentChanged", "transactionAccepted", "reorg", "finalityConflict",
+    ];
+}
+
+/// Fan-out of node events to any number of subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NodeEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus { sender: broadcast::channel(EVENT_BUFFER).0 }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: NodeEvent) {
+        // Nobody listening is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl BlockDAG {
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Publishes what changed when `hash` was inserted, given the tips and virtual selected parent before it.
+    pub(crate) fn publish_insert_events(&self, hash: &str, old_tips: &[String], old_virtual_selected_parent: Option<String>) {
+        let data = self.ghostdag.get(hash).cloned().unwrap_or_default();
+        self.events.publish(NodeEvent::BlockAdded {
+            hash: hash.to_string(),
+            blue_score: data.blue_score,
+            selected_parent: data.selected_parent,
+        });
+        if self.tips != old_tips {
+            self.events.publish(NodeEvent::TipsChanged { tips: self.tips.clone() });
+        }
+
+        let new_virtual_selected_parent = match self.virtual_selected_parent() {
+            Some(hash) if Some(&hash) != old_virtual_selected_parent.as_ref() => hash,
+            _ => return,
+        };
+        self.events.publish(NodeEvent::VirtualSelectedParentChanged {
+            blue_score: self.blue_score(&new_virtual_selected_parent),
+            hash: new_virtual_selected_parent.clone(),
+        });
+
+        // Walk the old chain down to where it meets the new one
+        let mut removed = vec![];
+        let mut current = old_virtual_selected_parent;
+        while let Some(hash) = current {
+            if self.reachability.is_tree_ancestor_of(&hash, &new_virtual_selected_parent) {
+                current = Some(hash);
+                break;
+            }
+            current = self.ghostdag.get(&hash).and_then(|data| data.selected_parent.clone());
+            removed.push(hash);
+        }
+        if removed.is_empty() {
+            return;
+        }
+        let mut added = vec![];
+        let mut hash = new_virtual_selected_parent;
+        while Some(&hash) != current.as_ref() {
+            let selected_parent = self.ghostdag.get(&hash).and_then(|data| data.selected_parent.clone());
+            added.push(hash);
+            hash = match selected_parent {
+                Some(selected_parent) => selected_parent,
+                None => break,
+            };
+        }
+        info!(target: "consensus", "Selected chain reorganized: {} blocks removed, {} added", removed.len(), added.len());
+        self.events.publish(NodeEvent::Reorg { removed, added });
+    }
+}
@@ -0,0 +1,138 @@
+// src/explorer.rs
+
+use serde::Serialize;
+use crate::blockdag::BlockDAG;
+use crate::indexer::{AcceptanceStatus, TransactionLocation};
+use crate::transaction::Transaction;
+
+/// A transaction and where it sits: in a stored block, or still in the mempool.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionEntry {
+    pub txid: String,
+    pub block_hash: Option<String>,
+    pub position: Option<usize>,
+    pub blue_score: Option<u64>,
+    pub status: AcceptanceStatus,
+    pub transaction: Transaction,
+}
+
+/// Difficulty of one selected chain block.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyPoint {
+    pub hash: String,
+    pub blue_score: u64,
+    pub timestamp: u128,
+    pub difficulty: u64,
+}
+
+impl BlockDAG {
+    /// Known blocks that list `hash` as a parent, oldest first.
+    pub fn children_of(&self, hash: &str) -> Vec<String> {
+        let mut children: Vec<String> = self.blocks.keys().chain(self.pruned_headers.keys())
+            .filter(|child| self.parents_of(child).iter().any(|parent| parent == hash))
+            .cloned()
+            .collect();
+        children.sort_by_key(|child| (self.blue_score(child), child.clone()));
+        children
+    }
+
+    /// Whether `hash` is on the virtual selected parent's selected chain.
+    pub fn is_chain_block(&self, hash: &str) -> bool {
+        match self.virtual_selected_parent() {
+            Some(tip) => self.reachability.is_tree_ancestor_of(hash, &tip),
+            None => false,
+        }
+    }
+
+    /// Finds a transaction by id in the mempool or the stored blocks, preferring its earliest
+    /// inclusion when parallel blocks carry it.
+    pub fn find_transaction(&self, txid: &str) -> Option<TransactionEntry> {
+        let mut entries = match self.tx_index() {
+            Some(index) => self.entries_at(index.transaction(txid), |transaction| transaction.txid() == txid),
+            None => self.transaction_entries(|transaction| transaction.txid() == txid),
+        };
+        entries.pop()
+    }
+
+    /// Transactions sending to or from `address`, newest first.
+    pub fn address_history(&self, address: &str) -> Vec<TransactionEntry> {
+        let involves = |transaction: &Transaction| transaction.sender == address || transaction.receiver == address;
+        match self.tx_index() {
+            Some(index) => self.entries_at(index.address(address), involves),
+            None => self.transaction_entries(involves),
+        }
+    }
+
+    /// Difficulty along the selected chain, newest first, for blocks whose header is still known.
+    pub fn difficulty_history(&self) -> Vec<DifficultyPoint> {
+        self.selected_chain().into_iter().filter_map(|hash| {
+            let (timestamp, difficulty) = match (self.blocks.get(&hash), self.pruned_headers.get(&hash)) {
+                (Some(block), _) => (block.timestamp, block.difficulty),
+                (None, Some(header)) => (header.timestamp, header.difficulty),
+                (None, None) => return None,
+            };
+            Some(DifficultyPoint { blue_score: self.blue_score(&hash), hash, timestamp, difficulty })
+        }).collect()
+    }
+
+    /// Matching mempool transactions followed by the indexed ones at `locations`, newest first.
+    fn entries_at<F: Fn(&Transaction) -> bool>(&self, locations: &[TransactionLocation], matches: F) -> Vec<TransactionEntry> {
+        let mut entries = self.pending_entries(matches);
+        let mut confirmed: Vec<TransactionEntry> = locations.iter()
+            .filter_map(|location| {
+                let transaction = self.blocks.get(&location.block_hash)?.transactions.get(location.position)?;
+                Some(self.confirmed_entry(&location.block_hash, location.position, transaction))
+            })
+            .collect();
+        sort_newest_first(&mut confirmed);
+        entries.extend(confirmed);
+        entries
+    }
+
+    /// Matching mempool transactions followed by matching stored ones, newest first.
+    fn transaction_entries<F: Fn(&Transaction) -> bool>(&self, matches: F) -> Vec<TransactionEntry> {
+        let mut entries = self.pending_entries(&matches);
+        let mut confirmed = vec![];
+        for (hash, block) in &self.blocks {
+            for (position, transaction) in block.transactions.iter().enumerate() {
+                if matches(transaction) {
+                    confirmed.push(self.confirmed_entry(hash, position, transaction));
+                }
+            }
+        }
+        sort_newest_first(&mut confirmed);
+        entries.extend(confirmed);
+        entries
+    }
+
+    fn pending_entries<F: Fn(&Transaction) -> bool>(&self, matches: F) -> Vec<TransactionEntry> {
+        self.pending_transactions.iter()
+            .filter(|transaction| matches(transaction))
+            .map(|transaction| TransactionEntry {
+                txid: transaction.txid(),
+                block_hash: None,
+                position: None,
+                blue_score: None,
+                status: AcceptanceStatus::Pending,
+                transaction: transaction.clone(),
+            })
+            .collect()
+    }
+
+    fn confirmed_entry(&self, hash: &str, position: usize, transaction: &Transaction) -> TransactionEntry {
+        TransactionEntry {
+            txid: transaction.txid(),
+            block_hash: Some(hash.to_string()),
+            position: Some(position),
+            blue_score: Some(self.blue_score(hash)),
+            status: self.acceptance_status(hash),
+            transaction: transaction.clone(),
+        }
+    }
+}
+
+fn sort_newest_first(entries: &mut [TransactionEntry]) {
+    entries.sort_by(|a, b| (b.blue_score, &b.block_hash, b.position).cmp(&(a.blue_score, &a.block_hash, a.position)));
+}
@@ -0,0 +1,55 @@
+// src/finality.rs
+
+use log::warn;
+use crate::block::Block;
+use crate::blockdag::BlockDAG;
+use crate::events::NodeEvent;
+
+impl BlockDAG {
+    /// Selected-chain block buried `finality_depth` blue score below the virtual selected parent.
+    /// Nothing below it may be reorganized.
+    pub fn finality_point(&self) -> Option<String> {
+        let depth = self.params().finality_depth;
+        let mut current = self.virtual_selected_parent()?;
+        let tip_score = self.blue_score(&current);
+        if tip_score < depth {
+            return None;
+        }
+        loop {
+            if self.blue_score(&current) <= tip_score - depth || current == self.pruning_point {
+                return Some(current);
+            }
+            current = self.ghostdag.get(&current)?.selected_parent.clone()?;
+        }
+    }
+
+    /// Whether accepting `block` would make the virtual selected chain skip the finality point,
+    /// i.e. reorganize history deeper than the finality window.
+    pub fn violates_finality(&self, block: &Block) -> bool {
+        let (finality_point, virtual_selected_parent) = match (self.finality_point(), self.virtual_selected_parent()) {
+            (Some(finality_point), Some(virtual_selected_parent)) => (finality_point, virtual_selected_parent),
+            _ => return false,
+        };
+        let data = self.compute_ghostdag(&block.previous_hashes);
+        let current_score = self.blue_score(&virtual_selected_parent);
+        // Blocks that don't take over the selected chain are merged later and reorganize nothing
+        let takes_over = data.blue_score > current_score || (data.blue_score == current_score && block.hash < virtual_selected_parent);
+        if !takes_over {
+            return false;
+        }
+        let selected_parent = match data.selected_parent {
+            Some(selected_parent) => selected_parent,
+            None => return true,
+        };
+        if selected_parent == finality_point || self.is_dag_ancestor_of(&finality_point, &selected_parent) {
+            return false;
+        }
+        warn!(
+            target: "consensus",
+            "Finality conflict: block {} (blue score {}) would reorganize past finality point {} (blue score {})",
+            block.hash, data.blue_score, finality_point, self.blue_score(&finality_point),
+        );
+        self.events().publish(NodeEvent::FinalityConflict { hash: block.hash.clone(), finality_point });
+        true
+    }
+}
@@ -0,0 +1,220 @@
+// src/ghostdag.rs
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use serde::{Serialize, Deserialize};
+use crate::blockdag::BlockDAG;
+use crate::reachability::ReachabilityIndex;
+
+/// Consensus data derived for every block when it is added to the DAG.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GhostdagData {
+    pub selected_parent: Option<String>,
+    pub blue_score: u64,
+    /// Blocks this block merges besides its selected parent, in consensus order.
+    pub mergeset: Vec<String>,
+    /// Merged blocks coloured red for having more than `k` blue blocks in their anticone.
+    #[serde(default)]
+    pub mergeset_reds: Vec<String>,
+    /// Size of the blue anticone, as seen from this block, of its selected parent, its blue
+    /// merged blocks and the earlier blues whose anticone those grew.
+    #[serde(default)]
+    pub blues_anticone_sizes: BTreeMap<String, u64>,
+}
+
+impl GhostdagData {
+    /// Selected parent followed by the merged blocks coloured blue, in consensus order.
+    pub fn mergeset_blues(&self) -> impl Iterator<Item = &String> {
+        self.selected_parent.iter().chain(self.mergeset.iter().filter(|hash| !self.mergeset_reds.contains(hash)))
+    }
+}
+
+impl BlockDAG {
+    /// Parents of a known block, whether its body is still stored or has been pruned.
+    pub fn parents_of(&self, hash: &str) -> Vec<String> {
+        let previous_hashes = match self.blocks.get(hash) {
+            Some(block) => &block.previous_hashes,
+            None => match self.pruned_headers.get(hash) {
+                Some(header) => &header.previous_hashes,
+                None => return vec![],
+            },
+        };
+        previous_hashes.iter().filter(|parent| parent.as_str() != "0").cloned().collect()
+    }
+
+    pub fn blue_score(&self, hash: &str) -> u64 {
+        self.ghostdag.get(hash).map(|data| data.blue_score).unwrap_or(0)
+    }
+
+    /// Picks the block with the highest blue score, breaking ties by the smaller hash.
+    fn select<'a, I: IntoIterator<Item = &'a String>>(&self, candidates: I) -> Option<String> {
+        candidates.into_iter().max_by_key(|hash| (self.blue_score(hash), Reverse(hash.as_str()))).cloned()
+    }
+
+    /// Whether `ancestor` is `descendant` itself or in its past.
+    pub fn is_dag_ancestor_of(&self, ancestor: &str, descendant: &str) -> bool {
+        // Pruned blocks lie in the pruning point's past, and so in the past of its whole future
+        let pruned = !self.blocks.contains_key(ancestor) && self.ghostdag.contains_key(ancestor);
+        if pruned && self.reachability.is_dag_ancestor_of(&self.pruning_point, descendant) {
+            return true;
+        }
+        self.reachability.is_dag_ancestor_of(ancestor, descendant)
+    }
+
+    /// Runs GHOSTDAG for a block with the given parents: picks the selected parent and colours
+    /// the rest of the mergeset so that the blue set stays a `k`-cluster, i.e. no blue block has
+    /// more than `ghostdag_k` blue blocks in its anticone.
+    pub fn compute_ghostdag(&self, parents: &[String]) -> GhostdagData {
+        let parents: Vec<String> = parents.iter().filter(|parent| parent.as_str() != "0").cloned().collect();
+        let selected_parent = match self.select(&parents) {
+            Some(selected_parent) => selected_parent,
+            None => return GhostdagData::default(),
+        };
+
+        let mut mergeset = HashSet::new();
+        let mut queue: VecDeque<String> = parents.iter().filter(|parent| **parent != selected_parent).cloned().collect();
+        while let Some(hash) = queue.pop_front() {
+            if mergeset.contains(&hash) || !self.ghostdag.contains_key(&hash) || self.is_dag_ancestor_of(&hash, &selected_parent) {
+                continue;
+            }
+            queue.extend(self.parents_of(&hash));
+            mergeset.insert(hash);
+        }
+        let mut mergeset: Vec<String> = mergeset.into_iter().collect();
+        mergeset.sort_by_key(|hash| (self.blue_score(hash), hash.clone()));
+
+        let k = self.params().ghostdag_k;
+        let mut data = GhostdagData {
+            blue_score: 0,
+            blues_anticone_sizes: BTreeMap::from([(selected_parent.clone(), 0)]),
+            selected_parent: Some(selected_parent.clone()),
+            mergeset: vec![],
+            mergeset_reds: vec![],
+        };
+        // Candidates come in topological order, so each is coloured knowing the blues before it
+        for candidate in mergeset {
+            match self.blue_anticone(&data, &candidate, k) {
+                Some(anticone) => {
+                    data.blues_anticone_sizes.insert(candidate.clone(), anticone.len() as u64);
+                    for (blue, size) in anticone {
+                        data.blues_anticone_sizes.insert(blue, size + 1);
+                    }
+                }
+                None => data.mergeset_reds.push(candidate.clone()),
+            }
+            data.mergeset.push(candidate);
+        }
+        data.blue_score = self.blue_score(&selected_parent) + 1 + (data.mergeset.len() - data.mergeset_reds.len()) as u64;
+        data
+    }
+
+    /// Blue blocks in the anticone of `candidate`, with their blue anticone sizes, from the point
+    /// of view of a new block whose colouring so far is `data`; `None` if colouring `candidate`
+    /// blue would give it or any of those blues more than `k` blues in their anticone.
+    fn blue_anticone(&self, data: &GhostdagData, candidate: &str, k: u64) -> Option<Vec<(String, u64)>> {
+        if data.mergeset_blues().count() as u64 > k {
+            return None;
+        }
+        let mut anticone = vec![];
+        // Blues merged by the new block, then by each block down its selected chain, until a
+        // chain block in the candidate's past: all blues below it are in the candidate's past too
+        let mut chain_block: Option<&String> = None;
+        let mut chain_data = data;
+        loop {
+            if chain_block.is_some_and(|hash| self.is_dag_ancestor_of(hash, candidate)) {
+                return Some(anticone);
+            }
+            for blue in chain_data.mergeset_blues() {
+                if self.is_dag_ancestor_of(blue, candidate) {
+                    continue;
+                }
+                let size = self.blue_anticone_size(data, blue)?;
+                if size == k || anticone.len() as u64 == k {
+                    return None;
+                }
+                anticone.push((blue.clone(), size));
+            }
+            // The chain ends at genesis or at the oldest block we still know
+            chain_block = chain_data.selected_parent.as_ref();
+            chain_data = match chain_block.and_then(|hash| self.ghostdag.get(hash)) {
+                Some(chain_data) => chain_data,
+                None => return Some(anticone),
+            };
+        }
+    }
+
+    /// Blue anticone size of the blue block `blue` as seen from the block whose data is `data`:
+    /// the value recorded nearest to it on its selected chain.
+    fn blue_anticone_size(&self, data: &GhostdagData, blue: &str) -> Option<u64> {
+        let mut current = data;
+        loop {
+            if let Some(size) = current.blues_anticone_sizes.get(blue) {
+                return Some(*size);
+            }
+            current = self.ghostdag.get(current.selected_parent.as_ref()?)?;
+        }
+    }
+
+    /// The tip the virtual block would pick as its selected parent.
+    pub fn virtual_selected_parent(&self) -> Option<String> {
+        self.select(&self.tips)
+    }
+
+    /// Selected chain from the virtual selected parent down to the oldest known chain block.
+    pub fn selected_chain(&self) -> Vec<String> {
+        let mut chain = vec![];
+        let mut current = self.virtual_selected_parent();
+        while let Some(hash) = current {
+            current = self.ghostdag.get(&hash).and_then(|data| data.selected_parent.clone());
+            chain.push(hash);
+        }
+        chain
+    }
+
+    /// Known blocks ordered so that every block comes after its parents.
+    pub fn topological_order(&self) -> Vec<String> {
+        let known: Vec<&String> = self.blocks.keys().chain(self.pruned_headers.keys()).collect();
+        let mut pending: HashMap<&str, usize> = HashMap::new();
+        let mut children: HashMap<String, Vec<&str>> = HashMap::new();
+        for hash in &known {
+            let parents: Vec<String> = self.parents_of(hash).into_iter()
+                .filter(|parent| self.blocks.contains_key(parent) || self.pruned_headers.contains_key(parent))
+                .collect();
+            pending.insert(hash.as_str(), parents.len());
+            for parent in parents {
+                children.entry(parent).or_default().push(hash.as_str());
+            }
+        }
+        let mut ready: Vec<&str> = pending.iter().filter(|(_, count)| **count == 0).map(|(hash, _)| *hash).collect();
+        ready.sort();
+        let mut order = vec![];
+        while let Some(hash) = ready.pop() {
+            order.push(hash.to_string());
+            for child in children.get(hash).cloned().unwrap_or_default() {
+                let count = pending.get_mut(child).expect("child is known");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(child);
+                }
+            }
+        }
+        order
+    }
+
+    /// Computes GHOSTDAG data for every known block that lacks it and rebuilds the reachability
+    /// index, returning the blocks whose GHOSTDAG data was filled in.
+    pub fn fill_ghostdag(&mut self) -> Vec<String> {
+        let mut filled = vec![];
+        self.reachability = ReachabilityIndex::default();
+        for hash in self.topological_order() {
+            if !self.ghostdag.contains_key(&hash) {
+                let data = self.compute_ghostdag(&self.parents_of(&hash));
+                self.ghostdag.insert(hash.clone(), data);
+                filled.push(hash.clone());
+            }
+            let data = &self.ghostdag[&hash];
+            self.reachability.add_block(&hash, data.selected_parent.as_deref(), &data.mergeset);
+        }
+        filled
+    }
+}
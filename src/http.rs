@@ -0,0 +1,245 @@
+// src/http.rs
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::pin::Pin;
+use std::sync::Arc;
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use log::{debug, error, info, warn};
+
+const MAX_HEADER_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Takes over a connection whose request asks for a protocol upgrade, e.g. to WebSocket.
+pub type UpgradeHandler = Arc<dyn Fn(HttpRequest, TcpStream) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A parsed HTTP/1.1 request. Header names are lowercased.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    /// First value of a query string parameter. Values are not percent-decoded.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.split('&')
+            .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    fn keep_alive(&self) -> bool {
+        !matches!(self.header("connection"), Some(value) if value.eq_ignore_ascii_case("close"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn json(status: u16, value: &Value) -> HttpResponse {
+        HttpResponse { status, content_type: "application/json", body: value.to_string().into_bytes() }
+    }
+
+    pub fn text(status: u16, body: &str) -> HttpResponse {
+        HttpResponse { status, content_type: "text/plain; charset=utf-8", body: body.as_bytes().to_vec() }
+    }
+
+    pub fn no_content() -> HttpResponse {
+        HttpResponse { status: 204, content_type: "text/plain; charset=utf-8", body: vec![] }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            101 => "Switching Protocols",
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// Reads one request, or `None` once the client closes an idle connection.
+pub async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<HttpRequest>, Error> {
+    let request_line = match read_line(reader).await? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => (method.to_string(), target),
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("Malformed request line: {}", request_line))),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (target.to_string(), String::new()),
+    };
+
+    let headers = read_headers(reader).await?;
+    let body = read_body(reader, &headers).await?;
+    Ok(Some(HttpRequest { method, path, query, headers, body }))
+}
+
+async fn read_headers<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<HashMap<String, String>, Error> {
+    let mut headers = HashMap::new();
+    loop {
+        let line = read_line(reader).await?.ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "Connection closed in headers"))?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(Error::new(ErrorKind::InvalidData, "Too many headers"));
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| Error::new(ErrorKind::InvalidData, "Malformed header"))?;
+        headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+}
+
+async fn read_body<R: AsyncBufRead + Unpin>(reader: &mut R, headers: &HashMap<String, String>) -> Result<Vec<u8>, Error> {
+    let length = match headers.get("content-length") {
+        Some(length) => length.parse::<usize>().map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(Error::new(ErrorKind::InvalidData, "Body too large"));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<String>, Error> {
+    let mut line = Vec::new();
+    let read = (&mut *reader).take(MAX_HEADER_LINE as u64 + 2).read_until(b'\n', &mut line).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n") {
+        return Err(Error::new(ErrorKind::InvalidData, "Header line too long"));
+    }
+    let line = String::from_utf8(line).map_err(|_| Error::new(ErrorKind::InvalidData, "Header is not UTF-8"))?;
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+pub async fn write_response<W: AsyncWrite + Unpin>(writer: &mut W, response: &HttpResponse, keep_alive: bool) -> Result<(), Error> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+        response.status, response.reason(), response.content_type, response.body.len(),
+        if keep_alive { "keep-alive" } else { "close" },
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(&response.body).await?;
+    writer.flush().await
+}
+
+/// POSTs a JSON body to an `http://host:port/path` URL and returns the response status and body.
+/// Bodies that are empty, e.g. on 204, come back as `null`.
+pub async fn post_json(url: &str, body: &Value) -> Result<(u16, Value), Error> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unsupported URL: {}", url)))?;
+    let (host, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    let body = body.to_string();
+    let mut stream = BufReader::new(TcpStream::connect(host).await?);
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path, host, body.len(),
+    );
+    stream.get_mut().write_all(head.as_bytes()).await?;
+    stream.get_mut().write_all(body.as_bytes()).await?;
+
+    let status_line = read_line(&mut stream).await?.ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "Connection closed before response"))?;
+    let status = match status_line.split_whitespace().nth(1).map(str::parse::<u16>) {
+        Some(Ok(status)) if status_line.starts_with("HTTP/1.") => status,
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("Malformed status line: {}", status_line))),
+    };
+    let headers = read_headers(&mut stream).await?;
+    let body = read_body(&mut stream, &headers).await?;
+    if body.is_empty() {
+        return Ok((status, Value::Null));
+    }
+    Ok((status, serde_json::from_slice(&body)?))
+}
+
+/// Serves HTTP on `listen`, answering every request with `handler` and handing upgrade
+/// requests to `upgrade`, if given.
+pub async fn serve<F, Fut>(listen: String, name: &'static str, handler: F, upgrade: Option<UpgradeHandler>)
+where
+    F: Fn(HttpRequest) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HttpResponse> + Send,
+{
+    match TcpListener::bind(&listen).await {
+        Ok(listener) => serve_listener(listener, name, handler, upgrade).await,
+        Err(e) => error!(target: "rpc", "Failed to start {} server on {}: {}", name, listen, e),
+    }
+}
+
+/// Like [`serve`], on a socket the caller has already bound.
+pub async fn serve_listener<F, Fut>(listener: TcpListener, name: &'static str, handler: F, upgrade: Option<UpgradeHandler>)
+where
+    F: Fn(HttpRequest) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HttpResponse> + Send,
+{
+    if let Ok(address) = listener.local_addr() {
+        info!(target: "rpc", "{} server running on http://{}", name, address);
+    }
+
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(target: "rpc", "Failed to accept {} connection: {}", name, e);
+                continue;
+            }
+        };
+        let handler = handler.clone();
+        let upgrade = upgrade.clone();
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(socket);
+            loop {
+                let request = match read_request(&mut stream).await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let response = HttpResponse::text(400, &e.to_string());
+                        let _ = write_response(stream.get_mut(), &response, false).await;
+                        debug!(target: "rpc", "Bad {} request from {}: {}", name, addr, e);
+                        break;
+                    }
+                };
+                if let (Some(upgrade), Some(_)) = (&upgrade, request.header("upgrade")) {
+                    upgrade(request, stream.into_inner()).await;
+                    break;
+                }
+                let keep_alive = request.keep_alive();
+                let response = handler(request).await;
+                if write_response(stream.get_mut(), &response, keep_alive).await.is_err() || !keep_alive {
+                    break;
+                }
+            }
+        });
+    }
+}
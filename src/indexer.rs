@@ -0,0 +1,109 @@
+// src/indexer.rs
+
+use std::collections::HashMap;
+use serde::Serialize;
+use log::info;
+use crate::block::Block;
+use crate::blockdag::BlockDAG;
+
+/// Where a transaction was included: a stored block and its index in that block.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionLocation {
+    pub block_hash: String,
+    pub position: usize,
+}
+
+/// Whether a transaction counts towards the ledger of the current virtual block.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AcceptanceStatus {
+    /// In the mempool.
+    Pending,
+    /// In a block on, or merged by, the virtual selected parent's chain.
+    Accepted,
+    /// In a tip the virtual selected parent does not have in its past yet.
+    Unmerged,
+}
+
+/// Txid and address lookups over stored block bodies.
+///
+/// Kept in memory and rebuilt from the stored blocks when enabled, so it never has to be
+/// migrated; blocks leave it when their bodies are pruned.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionIndex {
+    transactions: HashMap<String, Vec<TransactionLocation>>,
+    addresses: HashMap<String, Vec<TransactionLocation>>,
+}
+
+impl TransactionIndex {
+    pub fn add_block(&mut self, block: &Block) {
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let location = TransactionLocation { block_hash: block.hash.clone(), position };
+            self.transactions.entry(transaction.txid()).or_default().push(location.clone());
+            self.addresses.entry(transaction.receiver.clone()).or_default().push(location.clone());
+            if transaction.sender != transaction.receiver {
+                self.addresses.entry(transaction.sender.clone()).or_default().push(location);
+            }
+        }
+    }
+
+    pub fn remove_block(&mut self, block: &Block) {
+        for transaction in &block.transactions {
+            for key in [&transaction.sender, &transaction.receiver] {
+                remove_locations(&mut self.addresses, key, &block.hash);
+            }
+            remove_locations(&mut self.transactions, &transaction.txid(), &block.hash);
+        }
+    }
+
+    /// Every stored block that includes `txid`; the same transaction can land in parallel blocks.
+    pub fn transaction(&self, txid: &str) -> &[TransactionLocation] {
+        self.transactions.get(txid).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn address(&self, address: &str) -> &[TransactionLocation] {
+        self.addresses.get(address).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+fn remove_locations(index: &mut HashMap<String, Vec<TransactionLocation>>, key: &str, block_hash: &str) {
+    if let Some(locations) = index.get_mut(key) {
+        locations.retain(|location| location.block_hash != block_hash);
+        if locations.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+impl BlockDAG {
+    /// Turns the transaction index on, building it from the stored blocks.
+    pub fn enable_tx_index(&mut self) {
+        self.tx_index = Some(TransactionIndex::default());
+        self.rebuild_tx_index();
+    }
+
+    pub fn tx_index(&self) -> Option<&TransactionIndex> {
+        self.tx_index.as_ref()
+    }
+
+    /// Reindexes every stored block, if the index is enabled.
+    pub fn rebuild_tx_index(&mut self) {
+        let index = match self.tx_index.as_mut() {
+            Some(index) => index,
+            None => return,
+        };
+        *index = TransactionIndex::default();
+        for block in self.blocks.values() {
+            index.add_block(block);
+        }
+        info!(target: "storage", "Transaction index built over {} blocks", self.blocks.len());
+    }
+
+    pub fn acceptance_status(&self, block_hash: &str) -> AcceptanceStatus {
+        match self.virtual_selected_parent() {
+            Some(tip) if self.is_dag_ancestor_of(block_hash, &tip) => AcceptanceStatus::Accepted,
+            _ => AcceptanceStatus::Unmerged,
+        }
+    }
+}
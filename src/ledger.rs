@@ -0,0 +1,220 @@
+// src/ledger.rs
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use log::debug;
+use crate::blockdag::BlockDAG;
+use crate::ghostdag::GhostdagData;
+use crate::transaction::Transaction;
+
+/// Account balances and coin supply after applying a set of blocks.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LedgerState {
+    pub balances: BTreeMap<String, u64>,
+    pub supply: u64,
+    /// Nonce of each account's latest applied transaction.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nonces: BTreeMap<String, u64>,
+}
+
+/// Merkle path from one account's leaf to a ledger state root.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BalanceProof {
+    pub address: String,
+    pub balance: u64,
+    pub leaf_index: usize,
+    pub siblings: Vec<String>,
+}
+
+impl LedgerState {
+    /// Applies `transactions` in order and returns those it skipped because their sender could
+    /// no longer cover them or had already used their nonce: of two parallel blocks spending the
+    /// same funds, the one ordered first wins.
+    pub fn apply_transactions<'a>(&mut self, transactions: &'a [Transaction]) -> Vec<&'a Transaction> {
+        let mut skipped = vec![];
+        for tx in transactions {
+            if tx.sender == "0" {
+                self.supply += tx.amount;
+            } else {
+                // Transactions without a nonce predate them; validation no longer admits new ones
+                let nonce_ok = tx.nonce == 0 || tx.nonce == self.nonce(&tx.sender) + 1;
+                match self.balances.get_mut(&tx.sender) {
+                    Some(balance) if nonce_ok && *balance >= tx.amount => *balance -= tx.amount,
+                    _ => {
+                        skipped.push(tx);
+                        continue;
+                    }
+                }
+                if tx.nonce != 0 {
+                    self.nonces.insert(tx.sender.clone(), tx.nonce);
+                }
+            }
+            *self.balances.entry(tx.receiver.clone()).or_insert(0) += tx.amount;
+        }
+        self.balances.retain(|_, balance| *balance > 0);
+        skipped
+    }
+
+    pub fn balance(&self, address: &str) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+
+    /// Nonce of the latest transaction applied from `address`, 0 if none carried one.
+    pub fn nonce(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
+    }
+
+    /// Checks that each sender's transactions carry the nonces following its last one, in order.
+    pub fn check_nonces(&self, transactions: &[Transaction]) -> Result<(), Error> {
+        let mut next: BTreeMap<&str, u64> = BTreeMap::new();
+        for tx in transactions.iter().filter(|tx| tx.sender != "0") {
+            let expected = next.entry(&tx.sender).or_insert_with(|| self.nonce(&tx.sender) + 1);
+            if tx.nonce != *expected {
+                return Err(Error::new(ErrorKind::InvalidData, format!(
+                    "Transaction {} has nonce {} but {} is next for {}", tx.txid(), tx.nonce, expected, tx.sender)));
+            }
+            *expected += 1;
+        }
+        Ok(())
+    }
+
+    /// Merkle root over the supply, every balance in address order, then every account nonce.
+    pub fn commitment(&self) -> String {
+        hex::encode(merkle_root(self.leaves()))
+    }
+
+    /// Proof that `address` holds its current balance, checkable against `commitment()`.
+    pub fn balance_proof(&self, address: &str) -> Option<BalanceProof> {
+        let balance = *self.balances.get(address)?;
+        let leaf_index = 1 + self.balances.keys().position(|other| other == address)?;
+        let mut siblings = vec![];
+        let mut level = self.leaves();
+        let mut index = leaf_index;
+        while level.len() > 1 {
+            siblings.push(hex::encode(level.get(index ^ 1).unwrap_or(&level[index])));
+            level = level.chunks(2).map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0]))).collect();
+            index /= 2;
+        }
+        Some(BalanceProof { address: address.to_string(), balance, leaf_index, siblings })
+    }
+
+    fn leaves(&self) -> Vec<[u8; 32]> {
+        let mut leaves = vec![hash_leaf(&format!("supply:{}", self.supply))];
+        leaves.extend(self.balances.iter().map(|(address, balance)| hash_leaf(&format!("{}:{}", address, balance))));
+        leaves.extend(self.nonces.iter().map(|(address, nonce)| hash_leaf(&format!("nonce:{}:{}", address, nonce))));
+        leaves
+    }
+}
+
+impl BalanceProof {
+    /// Whether this proof leads to the given state root, e.g. from a block header.
+    pub fn verify(&self, root: &str) -> bool {
+        let mut node = hash_leaf(&format!("{}:{}", self.address, self.balance));
+        let mut index = self.leaf_index;
+        for sibling in &self.siblings {
+            let sibling = match hex::decode(sibling).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()) {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            node = if index.is_multiple_of(2) { hash_pair(&node, &sibling) } else { hash_pair(&sibling, &node) };
+            index /= 2;
+        }
+        hex::encode(node) == root
+    }
+}
+
+/// Root of a Merkle tree over `leaves`, an odd node paired with itself; the hash of an empty
+/// leaf if there are none.
+pub(crate) fn merkle_root(mut level: Vec<[u8; 32]>) -> [u8; 32] {
+    if level.is_empty() {
+        return hash_leaf("");
+    }
+    while level.len() > 1 {
+        level = level.chunks(2).map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0]))).collect();
+    }
+    level[0]
+}
+
+pub(crate) fn hash_leaf(data: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(data.as_bytes());
+    hasher.finalize().into()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+impl BlockDAG {
+    /// Ledger state of the past of a stored block at or above the pruning point.
+    pub fn ledger_at(&self, hash: &str) -> Result<LedgerState, Error> {
+        if !self.blocks.contains_key(hash) {
+            return Err(Error::new(ErrorKind::NotFound, format!("Block {} is not stored", hash)));
+        }
+        if !self.is_dag_ancestor_of(&self.pruning_point, hash) {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Block {} is not in the future of the pruning point", hash)));
+        }
+        Ok(self.ledger_after(&self.ghostdag[hash]))
+    }
+
+    /// Ledger state of the past of a new block with the given parents.
+    pub fn ledger_for_parents(&self, parents: &[String]) -> Result<LedgerState, Error> {
+        for parent in parents {
+            if !self.blocks.contains_key(parent) || !self.is_dag_ancestor_of(&self.pruning_point, parent) {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("Parent {} is not in the future of the pruning point", parent)));
+            }
+        }
+        Ok(self.ledger_after(&self.compute_ghostdag(parents)))
+    }
+
+    /// Ledger state the virtual block sees, i.e. that of the past of the current tips.
+    pub fn virtual_ledger(&self) -> LedgerState {
+        self.ledger_after(&self.compute_ghostdag(&self.tips))
+    }
+
+    /// Nonce the next transaction from `address` must carry, after those waiting in the mempool.
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let pending = self.pending_transactions.iter().filter(|tx| tx.sender == address).count() as u64;
+        self.virtual_ledger().nonce(address) + pending + 1
+    }
+
+    /// State root a block with the given parents must commit to.
+    pub fn state_commitment_for(&self, parents: &[String]) -> Result<String, Error> {
+        Ok(self.ledger_for_parents(parents)?.commitment())
+    }
+
+    /// Applies the past of a block with GHOSTDAG data `data` on top of the pruning snapshot: each
+    /// block of its selected chain, oldest first, accepts its selected parent and blue merged blocks
+    /// in consensus order. Red blocks' transactions never take effect, and neither do spends that
+    /// an earlier block in the order already made impossible.
+    pub(crate) fn ledger_after(&self, data: &GhostdagData) -> LedgerState {
+        let mut chain = vec![data];
+        while let Some(selected_parent) = chain.last().and_then(|data| data.selected_parent.as_ref()) {
+            // Below the pruning point everything is folded into the snapshot
+            match self.ghostdag.get(selected_parent).filter(|_| self.blocks.contains_key(selected_parent)) {
+                Some(data) => chain.push(data),
+                None => break,
+            }
+        }
+        let mut ledger = self.pruning_snapshot.clone();
+        for data in chain.iter().rev() {
+            for hash in data.mergeset_blues() {
+                let block = match self.blocks.get(hash) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                for tx in ledger.apply_transactions(&block.transactions) {
+                    debug!(target: "consensus", "Transaction {} in block {} conflicts with an earlier spend and is not applied", tx.txid(), hash);
+                }
+            }
+        }
+        ledger
+    }
+}
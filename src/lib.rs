@@ -2,8 +2,34 @@
 
 pub mod block;
 pub mod blockdag;
+pub mod config;
 pub mod constants;
+pub mod events;
+pub mod explorer;
+pub mod finality;
+pub mod ghostdag;
+pub mod http;
+pub mod indexer;
+pub mod ledger;
+pub mod logging;
+pub mod maturity;
 pub mod messages;
+pub mod metrics;
+pub mod mining;
 pub mod network;
+pub mod node;
+pub mod params;
+pub mod pool;
+pub mod pow;
+pub mod pruning;
+pub mod reachability;
+pub mod rest;
+pub mod rpc;
+pub mod schema;
+pub mod shutdown;
+pub mod snapshot;
+pub mod storage;
 pub mod transaction;
+pub mod transport;
 pub mod wallet;
+pub mod ws;
@@ -0,0 +1,216 @@
+// src/logging.rs
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::config::NodeConfig;
+
+/// Targets the node logs under, one per subsystem.
+pub const SUBSYSTEMS: [&str; 7] = ["node", "consensus", "network", "mining", "storage", "wallet", "rpc"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log shippers.
+    Json,
+}
+
+/// Verbosity per target, written like `info,network=debug,mining=warn`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    targets: BTreeMap<String, LevelFilter>,
+}
+
+impl LogFilter {
+    pub fn parse(spec: &str) -> Result<LogFilter, Error> {
+        let mut filter = LogFilter { default: LevelFilter::Info, targets: BTreeMap::new() };
+        for directive in spec.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target.trim()), level),
+                None => (None, directive),
+            };
+            let level: LevelFilter = level.trim().parse()
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("Invalid log level: {}", level)))?;
+            match target {
+                Some(target) => {
+                    filter.targets.insert(target.to_string(), level);
+                }
+                None => filter.default = level,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Level for a target; `network` also covers `network::peer` and the like.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets.iter()
+            .filter(|(prefix, _)| target == prefix.as_str() || target.starts_with(&format!("{}::", prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.values().copied().chain([self.default]).max().unwrap_or(LevelFilter::Off)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// Log file that is renamed to `<name>.1` (shifting older ones up) once it reaches `max_size`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<RotatingFile, Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path, file, size, max_size, max_files })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                if rotated(n).exists() {
+                    fs::rename(rotated(n), rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+struct Logger {
+    filter: RwLock<LogFilter>,
+    format: LogFormat,
+    /// `None` writes to stdout.
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let filter = self.filter.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        metadata.level() <= filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = format_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis());
+        let line = match self.format {
+            LogFormat::Text => format!("{} {:<5} {}: {}\n", timestamp, record.level(), record.target(), record.args()),
+            LogFormat::Json => format!("{}\n", json!({
+                "timestamp": timestamp,
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })),
+        };
+        match &self.file {
+            Some(file) => {
+                let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Err(e) = file.write_line(&line) {
+                    eprintln!("Failed to write log file {}: {}", file.path.display(), e);
+                }
+            }
+            None => print!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).file.flush();
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Installs the node logger as configured. Call once, before anything logs.
+pub fn init(config: &NodeConfig) -> Result<(), Error> {
+    let filter = LogFilter::parse(&config.log_level)?;
+    let file = match config.log_path() {
+        Some(path) => Some(Mutex::new(RotatingFile::open(path, config.log_max_size, config.log_max_files)?)),
+        None => None,
+    };
+    let max_level = filter.max_level();
+    let logger = LOGGER.get_or_init(|| Logger { filter: RwLock::new(filter), format: config.log_format, file });
+    log::set_logger(logger).map_err(|_| Error::other("A logger is already installed"))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Current filter, e.g. `info,network=debug`.
+pub fn filter() -> Option<String> {
+    LOGGER.get().map(|logger| logger.filter.read().unwrap_or_else(|poisoned| poisoned.into_inner()).to_string())
+}
+
+/// Replaces the filter of the running logger.
+pub fn set_filter(spec: &str) -> Result<String, Error> {
+    let logger = LOGGER.get().ok_or_else(|| Error::other("Logging is not initialized"))?;
+    let filter = LogFilter::parse(spec)?;
+    log::set_max_level(filter.max_level());
+    let applied = filter.to_string();
+    *logger.filter.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = filter;
+    Ok(applied)
+}
+
+/// RFC 3339 UTC timestamp with milliseconds.
+fn format_timestamp(millis: u128) -> String {
+    let seconds = (millis / 1000) as i64;
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60, millis % 1000)
+}
+
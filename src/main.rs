@@ -1,49 +1,25 @@
 // src/main.rs
 
-use std::sync::{Arc, Mutex};
-use std::collections::HashSet;
-use tokio::time::{sleep, Duration};
-use blockdag::blockdag::BlockDAG;
-use blockdag::network::{start_server, connect_to_server};
+use log::{error, info};
+use blockdag::config::NodeConfig;
+use blockdag::logging;
+use blockdag::node::Node;
+use blockdag::shutdown::listen_for_signals;
 use blockdag::wallet::Wallet;
 
 #[tokio::main]
 async fn main() {
-    let dag = Arc::new(Mutex::new(BlockDAG::load_from_file("blockdag.json").unwrap_or_else(|_| BlockDAG::new())));
-    let peers = Arc::new(Mutex::new(HashSet::new()));
-    let wallet = Wallet::new();
+    let config = NodeConfig::load().expect("Invalid configuration");
+    logging::init(&config).expect("Failed to set up logging");
+    let mining_address = config.mining_address.clone().unwrap_or_else(|| Wallet::new().get_address());
 
-    println!("Wallet Address: {}", wallet.get_address());
+    info!(target: "wallet", "Wallet Address: {}", mining_address);
 
-    // Start the server
-    let dag_server = Arc::clone(&dag);
-    let peers_server = Arc::clone(&peers);
-    tokio::spawn(async move {
-        start_server(dag_server, 8080, peers_server).await;
-    });
-
-    // Connect to the server (if needed)
-    let dag_client = Arc::clone(&dag);
-    let peers_client = Arc::clone(&peers);
-    tokio::spawn(async move {
-        sleep(Duration::from_secs(1)).await; // Wait a bit for the server to start
-        connect_to_server("127.0.0.1:8080", dag_client, peers_client).await;
-    });
-
-    // Continuous mining loop
-    loop {
-        {
-            let mut dag = dag.lock().unwrap();
-            if let Some(new_block) = dag.create_block(wallet.get_address().as_str()) {
-                println!("New Block Created: {:?}", new_block);
-            }
-            // Save the DAG state after each mined block
-            dag.save_to_file("blockdag.json").expect("Failed to save BlockDAG to file");
-            // Calculate and print balance
-            let balance = dag.get_balance(wallet.get_address().as_str());
-            println!("Current Balance: {}", balance);
-        }
-        // Simulate mining time
-        sleep(Duration::from_secs(1)).await;
+    let node = Node::builder(config).miner(mining_address).start().await.expect("Failed to start node");
+    tokio::spawn(listen_for_signals(node.shutdown_handle()));
+    node.wait_for_shutdown().await;
+    if let Err(e) = node.stop().await {
+        error!(target: "storage", "Failed to flush state on shutdown: {}", e);
+        std::process::exit(1);
     }
 }
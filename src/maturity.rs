@@ -0,0 +1,118 @@
+// src/maturity.rs
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use serde::Serialize;
+use log::debug;
+use crate::blockdag::BlockDAG;
+use crate::ledger::LedgerState;
+use crate::transaction::Transaction;
+
+/// An address's balance, split by whether its mined rewards may be spent yet.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Balance {
+    pub total: u64,
+    pub spendable: u64,
+    /// Rewards from blocks less than `coinbase_maturity` blue score below the virtual.
+    pub immature: u64,
+}
+
+impl BlockDAG {
+    /// Blue score the next block on the current tips would get.
+    pub fn virtual_blue_score(&self) -> u64 {
+        self.compute_ghostdag(&self.tips).blue_score
+    }
+
+    pub fn balance(&self, address: &str) -> Balance {
+        let total = self.get_balance(address);
+        let immature = self.immature_rewards(address, self.virtual_blue_score(), |_| true).min(total);
+        Balance { total, spendable: total - immature, immature }
+    }
+
+    /// Coinbase rewards to `address` in the stored blocks selected by `in_past` that lie less than
+    /// `coinbase_maturity` blue score below `blue_score`. Pruned blocks are always deep enough.
+    fn immature_rewards<F: Fn(&str) -> bool>(&self, address: &str, blue_score: u64, in_past: F) -> u64 {
+        let maturity = self.params().coinbase_maturity;
+        self.blocks.iter()
+            .filter(|(hash, _)| self.blue_score(hash) + maturity > blue_score && in_past(hash))
+            .flat_map(|(_, block)| &block.transactions)
+            .filter(|tx| tx.sender == "0" && tx.receiver == address)
+            .map(|tx| tx.amount)
+            .sum()
+    }
+
+    /// Spendable balance of `address` for a block on `parents`, whose past has the state `ledger`.
+    fn spendable_for(&self, address: &str, parents: &[String], blue_score: u64, ledger: &LedgerState) -> u64 {
+        let immature = self.immature_rewards(address, blue_score, |hash| parents.iter().any(|parent| self.is_dag_ancestor_of(hash, parent)));
+        ledger.balance(address).saturating_sub(immature)
+    }
+
+    /// Checks that no sender in a block on `parents` spends more than the mature part of its
+    /// balance in the block's past, described by `ledger`.
+    pub fn check_spendable(&self, parents: &[String], ledger: &LedgerState, transactions: &[Transaction]) -> Result<(), Error> {
+        let blue_score = self.compute_ghostdag(parents).blue_score;
+        let mut spends: HashMap<&str, u64> = HashMap::new();
+        for tx in transactions.iter().filter(|tx| tx.sender != "0") {
+            *spends.entry(&tx.sender).or_insert(0) += tx.amount;
+        }
+        for (sender, spent) in spends {
+            let spendable = self.spendable_for(sender, parents, blue_score, ledger);
+            if spent > spendable {
+                return Err(Error::new(ErrorKind::InvalidData, format!("{} spends {} but only {} of its balance is mature", sender, spent, spendable)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pending transactions a block on `parents` can include, in mempool order, leaving out those
+    /// their sender can't cover with mature funds and those that don't continue its nonces.
+    pub(crate) fn spendable_transactions(&self, parents: &[String], ledger: &LedgerState) -> Vec<Transaction> {
+        let blue_score = self.compute_ghostdag(parents).blue_score;
+        let mut available: HashMap<&str, u64> = HashMap::new();
+        let mut nonces: HashMap<&str, u64> = HashMap::new();
+        let mut included = vec![];
+        for tx in &self.pending_transactions {
+            let funds = available.entry(&tx.sender).or_insert_with(|| self.spendable_for(&tx.sender, parents, blue_score, ledger));
+            let nonce = nonces.entry(&tx.sender).or_insert_with(|| ledger.nonce(&tx.sender));
+            if tx.nonce != *nonce + 1 {
+                debug!(target: "mining", "Leaving out transaction {}: nonce {} does not follow {}", tx.txid(), tx.nonce, nonce);
+                continue;
+            }
+            if tx.amount > *funds {
+                debug!(target: "mining", "Leaving out transaction {}: sender has only {} spendable", tx.txid(), funds);
+                continue;
+            }
+            *funds -= tx.amount;
+            *nonce += 1;
+            included.push(tx.clone());
+        }
+        included
+    }
+
+    /// Checks a transaction offered to the mempool: its signature, its nonce, and its sender's
+    /// spendable balance less what the sender's other pending transactions already spend.
+    pub fn check_mempool_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        if transaction.sender == "0" {
+            return Err(Error::new(ErrorKind::InvalidInput, "Coinbase transactions can't be relayed"));
+        }
+        if !BlockDAG::verify_transaction(transaction) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Transaction signature is invalid"));
+        }
+        let next_nonce = self.next_nonce(&transaction.sender);
+        if transaction.nonce != next_nonce {
+            return Err(Error::new(ErrorKind::InvalidInput, format!(
+                "Transaction has nonce {} but {} is next for {}", transaction.nonce, next_nonce, transaction.sender)));
+        }
+        let balance = self.balance(&transaction.sender);
+        let pending: u64 = self.pending_transactions.iter().filter(|tx| tx.sender == transaction.sender).map(|tx| tx.amount).sum();
+        let available = balance.spendable.saturating_sub(pending);
+        if transaction.amount > available {
+            return Err(Error::new(ErrorKind::InvalidInput, format!(
+                "Transaction spends {} but {} has only {} spendable ({} immature, {} pending)",
+                transaction.amount, transaction.sender, available, balance.immature, pending,
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 // src/messages.rs
 
 use crate::block::Block;
+use crate::pruning::PruningPointProof;
 use crate::transaction::Transaction;
 use serde::{Serialize, Deserialize};
 
@@ -11,15 +12,31 @@ pub enum Message {
     RequestTip,
     Tip(String),
     NewTransaction(Transaction),
+    RequestPruningPointProof,
+    PruningPointProof(Box<PruningPointProof>),
     Unknown,
 }
 
 impl Message {
-    pub fn from_str(s: &str) -> Self {
+    pub fn from_json(s: &str) -> Self {
         serde_json::from_str(s).unwrap_or(Message::Unknown)
     }
 
-    pub fn to_string(&self) -> String {
+    pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /// Variant name, used to label metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::RequestBlock(_) => "RequestBlock",
+            Message::NewBlock(_) => "NewBlock",
+            Message::RequestTip => "RequestTip",
+            Message::Tip(_) => "Tip",
+            Message::NewTransaction(_) => "NewTransaction",
+            Message::RequestPruningPointProof => "RequestPruningPointProof",
+            Message::PruningPointProof(_) => "PruningPointProof",
+            Message::Unknown => "Unknown",
+        }
+    }
 }
@@ -0,0 +1,147 @@
+// src/metrics.rs
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::blockdag::BlockDAG;
+use crate::http::{HttpRequest, HttpResponse};
+use crate::rpc::RpcContext;
+
+/// Path the Prometheus endpoint is served at, next to JSON-RPC.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// A node's counters, recorded where things happen and rendered on scrape. Each node keeps its
+/// own, shared by its DAG, miner, peer sessions and RPC server.
+///
+/// Gauges that mirror DAG state (tips, mempool, difficulty…) are read from the DAG at scrape
+/// time instead of being kept here.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    blocks_accepted: AtomicU64,
+    blocks_rejected: Mutex<BTreeMap<&'static str, u64>>,
+    peer_connections: AtomicU64,
+    blocks_mined: AtomicU64,
+    hashes: AtomicU64,
+    mining_micros: AtomicU64,
+    /// Hashes per second over the last proof-of-work search, as f64 bits.
+    hash_rate: AtomicU64,
+    best_peer_index: AtomicU64,
+    messages: Mutex<BTreeMap<MessageLabels, MessageCounts>>,
+}
+
+/// Direction ("in" or "out") and message type.
+type MessageLabels = (&'static str, &'static str);
+/// Messages and payload bytes.
+type MessageCounts = (u64, u64);
+
+impl Metrics {
+    pub fn block_accepted(&self) {
+        self.blocks_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn block_rejected(&self, reason: &'static str) {
+        *self.blocks_rejected.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn peer_connected(&self) {
+        self.peer_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn peer_disconnected(&self) {
+        self.peer_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn block_mined(&self) {
+        self.blocks_mined.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a proof-of-work search, solved or not, that took `hashes` attempts over `micros` microseconds.
+    pub fn hashes_computed(&self, hashes: u64, micros: u64) {
+        self.hashes.fetch_add(hashes, Ordering::Relaxed);
+        self.mining_micros.fetch_add(micros, Ordering::Relaxed);
+        let rate = hashes as f64 * 1_000_000.0 / micros.max(1) as f64;
+        self.hash_rate.store(rate.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Remembers the highest block index a peer has sent, to measure how far behind we are.
+    pub fn peer_block_seen(&self, index: u64) {
+        self.best_peer_index.fetch_max(index, Ordering::Relaxed);
+    }
+
+    /// Counts a P2P message of type `kind` and its size on the wire, `direction` being "in" or "out".
+    pub fn message(&self, direction: &'static str, kind: &'static str, bytes: usize) {
+        let mut messages = self.messages.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entry = messages.entry((direction, kind)).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += bytes as u64;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self, dag: &BlockDAG) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: f64| {
+            let _ = write!(out, "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n");
+        };
+        let gauge = |out: &mut String, name: &str, help: &str, value: f64| {
+            let _ = write!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n");
+        };
+
+        counter(&mut out, "blockdag_blocks_accepted_total", "Blocks added to the DAG.", self.blocks_accepted.load(Ordering::Relaxed) as f64);
+        out.push_str("# HELP blockdag_blocks_rejected_total Blocks refused, by reason.\n# TYPE blockdag_blocks_rejected_total counter\n");
+        for (reason, count) in self.blocks_rejected.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter() {
+            let _ = writeln!(out, "blockdag_blocks_rejected_total{{reason=\"{reason}\"}} {count}");
+        }
+
+        let local_index = dag.virtual_selected_parent()
+            .and_then(|hash| dag.blocks.get(&hash).map(|block| block.index))
+            .unwrap_or(0);
+        let best_index = self.best_peer_index.load(Ordering::Relaxed).max(local_index);
+        gauge(&mut out, "blockdag_block_count", "Blocks known, including pruned ones.", dag.known_block_count() as f64);
+        gauge(&mut out, "blockdag_blue_score", "Blue score of the virtual selected parent.", dag.virtual_selected_parent().map(|hash| dag.blue_score(&hash)).unwrap_or(0) as f64);
+        gauge(&mut out, "blockdag_tips", "Current DAG tips.", dag.tips.len() as f64);
+        gauge(&mut out, "blockdag_mempool_transactions", "Transactions waiting to be mined.", dag.pending_transactions.len() as f64);
+        gauge(&mut out, "blockdag_difficulty", "Current proof-of-work difficulty.", dag.difficulty as f64);
+        gauge(&mut out, "blockdag_supply", "Coins issued so far.", dag.current_supply as f64);
+        gauge(&mut out, "blockdag_peer_index", "Highest block index received from a peer.", best_index as f64);
+        gauge(&mut out, "blockdag_sync_progress", "Local selected tip index over the best index seen, from 0 to 1.", if best_index == 0 { 1.0 } else { local_index as f64 / best_index as f64 });
+        gauge(&mut out, "blockdag_peers", "Open P2P connections.", self.peer_connections.load(Ordering::Relaxed) as f64);
+
+        counter(&mut out, "blockdag_blocks_mined_total", "Blocks this node found proof of work for.", self.blocks_mined.load(Ordering::Relaxed) as f64);
+        counter(&mut out, "blockdag_hashes_total", "Proof-of-work hashes computed while mining.", self.hashes.load(Ordering::Relaxed) as f64);
+        counter(&mut out, "blockdag_mining_seconds_total", "Time spent searching for proof of work.", self.mining_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0);
+        gauge(&mut out, "blockdag_hash_rate", "Hashes per second over the last proof-of-work search.", f64::from_bits(self.hash_rate.load(Ordering::Relaxed)));
+
+        let messages = self.messages.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        out.push_str("# HELP blockdag_messages_total P2P messages, by direction and type.\n# TYPE blockdag_messages_total counter\n");
+        for ((direction, kind), (count, _)) in messages.iter() {
+            let _ = writeln!(out, "blockdag_messages_total{{direction=\"{direction}\",type=\"{kind}\"}} {count}");
+        }
+        out.push_str("# HELP blockdag_message_bytes_total P2P payload bytes, by direction and type.\n# TYPE blockdag_message_bytes_total counter\n");
+        for ((direction, kind), (_, bytes)) in messages.iter() {
+            let _ = writeln!(out, "blockdag_message_bytes_total{{direction=\"{direction}\",type=\"{kind}\"}} {bytes}");
+        }
+        out
+    }
+}
+
+impl BlockDAG {
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Records block acceptance and rejections into `metrics`, e.g. the node's, instead of the DAG's own.
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = metrics;
+    }
+}
+
+pub fn handle_metrics(context: &RpcContext, request: &HttpRequest) -> HttpResponse {
+    if request.method != "GET" {
+        return HttpResponse::text(405, "Metrics are read with GET");
+    }
+    let dag = context.dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut response = HttpResponse::text(200, &context.metrics.render(&dag));
+    response.content_type = "text/plain; version=0.0.4; charset=utf-8";
+    response
+}
@@ -1,51 +1,29 @@
-// src/bin/miner.rs
+// src/miner.rs
 
-use std::sync::{Arc, Mutex};
-use std::collections::HashSet;
-use tokio::time::{sleep, Duration};
-use blockdag::blockdag::BlockDAG;
-use blockdag::network::{start_server, connect_to_server};
+use log::info;
+use blockdag::config::NodeConfig;
+use blockdag::logging;
+use blockdag::mining::{run_remote_miner, MiningEngine, DEFAULT_MINING_INTERVAL};
+use blockdag::pool::run_pool_worker;
+use blockdag::rpc::RpcClient;
+use blockdag::shutdown::{listen_for_signals, Shutdown};
 
+/// Mines against a node's JSON-RPC server (`--rpc-connect`, or the local node's RPC port)
+/// without running a node itself, or for a pool given with `--pool-connect`.
 #[tokio::main]
 async fn main() {
-    // Hard-coded wallet address
-    let wallet_address = "7bf2b2f920a612a724a490b7b2dbea0199f8ae4fa3f595a930cf5f4c0d446308";
+    let config = NodeConfig::load().expect("Invalid configuration");
+    logging::init(&config).expect("Failed to set up logging");
+    let mining_address = config.mining_address.clone().expect("A payout address is required (--mining-address or BLOCKDAG_MINING_ADDRESS)");
 
-    println!("Using Wallet Address: {}", wallet_address);
+    info!(target: "wallet", "Using Wallet Address: {}", mining_address);
 
-    let dag = Arc::new(Mutex::new(
-        BlockDAG::load_from_file("blockdag.json").unwrap_or_else(|_| BlockDAG::new()),
-    ));
-    let peers = Arc::new(Mutex::new(HashSet::new()));
-
-    // Start the server
-    let dag_server = Arc::clone(&dag);
-    let peers_server = Arc::clone(&peers);
-    tokio::spawn(async move {
-        start_server(dag_server, 8080, peers_server).await;
-    });
-
-    // Connect to the server (if needed)
-    let dag_client = Arc::clone(&dag);
-    let peers_client = Arc::clone(&peers);
-    tokio::spawn(async move {
-        sleep(Duration::from_secs(1)).await; // Wait a bit for the server to start
-        connect_to_server("127.0.0.1:8080", dag_client, peers_client).await;
-    });
-
-    // Continuous mining loop
-    loop {
-        {
-            let mut dag = dag.lock().unwrap();
-            if let Some(new_block) = dag.create_block(wallet_address) {
-                println!("New Block Created: {:?}", new_block);
-                dag.save_to_file("blockdag.json").unwrap();
-            }
-            // Calculate and print balance
-            let balance = dag.get_balance(wallet_address);
-            println!("Current Balance: {}", balance);
-        }
-        // Simulate mining time
-        sleep(Duration::from_secs(1)).await;
+    let shutdown = Shutdown::new();
+    tokio::spawn(listen_for_signals(shutdown.clone()));
+    let engine = MiningEngine::new(config.mining_threads());
+    match config.pool_connect.clone() {
+        Some(pool) => run_pool_worker(pool, mining_address, engine, shutdown).await,
+        None => run_remote_miner(RpcClient::new(config.rpc_url()), engine, mining_address, DEFAULT_MINING_INTERVAL, shutdown).await,
     }
+    info!(target: "mining", "Stopped cleanly");
 }
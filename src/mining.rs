@@ -0,0 +1,291 @@
+// src/mining.rs
+
+use std::io::Error;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{debug, error, info, warn};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval, sleep};
+use crate::block::{Block, BlockTemplate};
+use crate::blockdag::BlockDAG;
+use crate::events::NodeEvent;
+use crate::metrics::Metrics;
+use crate::pow::PowHash;
+use crate::rpc::RpcClient;
+use crate::shutdown::Shutdown;
+
+/// Pause between mined blocks unless configured otherwise.
+pub const DEFAULT_MINING_INTERVAL: Duration = Duration::from_secs(1);
+/// Nonces a worker tries between checks for cancellation.
+const CANCEL_CHECK_INTERVAL: u64 = 1024;
+/// How often a remote miner asks the node whether its tips moved.
+const TIP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Pause before a remote miner retries after the node failed or refused it.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Proof-of-work search split across worker threads: worker `i` of `n` tries nonces `i`, `i + n`, ….
+/// When the whole nonce space is exhausted the coinbase extra nonce is bumped and the search restarts.
+#[derive(Debug, Clone)]
+pub struct MiningEngine {
+    threads: usize,
+    max_nonce: u64,
+    /// Hashes per second over the last search, as f64 bits.
+    hash_rate: Arc<AtomicU64>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl MiningEngine {
+    pub fn new(threads: usize) -> MiningEngine {
+        MiningEngine { threads: threads.max(1), max_nonce: u64::MAX, hash_rate: Arc::new(AtomicU64::new(0)), metrics: None }
+    }
+
+    /// Caps the nonces tried per extra nonce; the full `u64` range by default.
+    pub fn max_nonce(mut self, max_nonce: u64) -> Self {
+        self.max_nonce = max_nonce;
+        self
+    }
+
+    /// Records hashes and mined blocks in a node's metrics.
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Hashes per second over the last search.
+    pub fn hash_rate(&self) -> f64 {
+        f64::from_bits(self.hash_rate.load(Ordering::Relaxed))
+    }
+
+    /// Searches for a block on `template`, blocking until one is found or `cancel` is set.
+    /// Templates for an unknown proof-of-work function yield nothing.
+    pub fn solve(&self, template: &BlockTemplate, cancel: &AtomicBool) -> Option<Block> {
+        let pow = match template.pow() {
+            Ok(pow) => pow,
+            Err(e) => {
+                error!(target: "mining", "Cannot mine template: {}", e);
+                return None;
+            }
+        };
+        let start = Instant::now();
+        let hashes = AtomicU64::new(0);
+        let mut block = None;
+        for extra_nonce in 0..=u64::MAX {
+            // The first pass keeps whatever extra nonce the template came with, e.g. from a pool
+            let candidate = if extra_nonce == 0 { template.clone() } else { template.with_extra_nonce(extra_nonce) };
+            if let Some(nonce) = self.search(&candidate, pow, cancel, &hashes) {
+                block = Some(candidate.into_block(pow, nonce));
+                break;
+            }
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+            debug!(target: "mining", "Nonce space exhausted, rolling extra nonce to {}", extra_nonce + 1);
+        }
+
+        let (hashes, micros) = (hashes.into_inner(), start.elapsed().as_micros() as u64);
+        let rate = hashes as f64 * 1_000_000.0 / micros.max(1) as f64;
+        self.hash_rate.store(rate.to_bits(), Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.hashes_computed(hashes, micros);
+            if block.is_some() {
+                metrics.block_mined();
+            }
+        }
+        block
+    }
+
+    /// One pass over the nonce space of `template`, counting attempts into `hashes`.
+    fn search(&self, template: &BlockTemplate, pow: &dyn PowHash, cancel: &AtomicBool, hashes: &AtomicU64) -> Option<u64> {
+        let found = AtomicBool::new(false);
+        let solution = Mutex::new(None);
+        let step = self.threads as u64;
+        let transactions_root = Block::transactions_root(&template.transactions);
+        thread::scope(|scope| {
+            for worker in 0..step {
+                let (found, solution, transactions_root) = (&found, &solution, &transactions_root);
+                scope.spawn(move || {
+                    let mut nonce = Some(worker);
+                    let mut tried = 0;
+                    while let Some(current) = nonce.filter(|nonce| *nonce <= self.max_nonce) {
+                        if tried % CANCEL_CHECK_INTERVAL == 0 && (cancel.load(Ordering::Relaxed) || found.load(Ordering::Relaxed)) {
+                            break;
+                        }
+                        tried += 1;
+                        if Block::meets_difficulty(&template.hash_with_root(pow, current, transactions_root), template.difficulty) {
+                            found.store(true, Ordering::Relaxed);
+                            solution.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get_or_insert(current);
+                            break;
+                        }
+                        nonce = current.checked_add(step);
+                    }
+                    hashes.fetch_add(tried, Ordering::Relaxed);
+                });
+            }
+        });
+        solution.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// How a mining attempt on one template ended.
+#[derive(Debug)]
+pub enum MiningOutcome {
+    /// The block was found and added to the DAG.
+    Mined(Box<Block>),
+    /// New tips arrived before a nonce was found, so the template was abandoned.
+    Stale,
+    Stopped,
+}
+
+fn lock_dag(dag: &Mutex<BlockDAG>) -> MutexGuard<'_, BlockDAG> {
+    dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Mines one block paying `address`. The DAG is only locked to take a template and to submit
+/// the result; the nonce search runs on the engine's threads while the node keeps serving peers.
+pub async fn mine_next_block(dag: &Mutex<BlockDAG>, engine: &MiningEngine, address: &str, shutdown: &Shutdown) -> Result<MiningOutcome, Error> {
+    let (template, mut events) = {
+        let dag = lock_dag(dag);
+        (dag.block_template(address)?, dag.events().subscribe())
+    };
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut search = tokio::task::spawn_blocking({
+        let (engine, cancel) = (engine.clone(), cancel.clone());
+        move || engine.solve(&template, &cancel)
+    });
+
+    let outcome = loop {
+        tokio::select! {
+            block = &mut search => break block.map_err(Error::other)?,
+            event = events.recv() => match event {
+                // Missed events may have changed the tips too
+                Ok(NodeEvent::TipsChanged { .. }) | Err(RecvError::Lagged(_)) => {
+                    cancel.store(true, Ordering::Relaxed);
+                    let _ = search.await;
+                    return Ok(MiningOutcome::Stale);
+                }
+                _ => {}
+            },
+            _ = shutdown.triggered() => {
+                cancel.store(true, Ordering::Relaxed);
+                let _ = search.await;
+                return Ok(MiningOutcome::Stopped);
+            }
+        }
+    };
+    let block = match outcome {
+        Some(block) => block,
+        None => return Ok(MiningOutcome::Stopped),
+    };
+
+    let mut dag = lock_dag(dag);
+    dag.submit_block(block.clone())?;
+    // Commit the block and the state it changed
+    dag.persist_block(&block.hash)?;
+    Ok(MiningOutcome::Mined(Box::new(block)))
+}
+
+/// Mines blocks paying `address` until shutdown, pausing `interval` after each one.
+pub async fn run_miner(dag: Arc<Mutex<BlockDAG>>, engine: MiningEngine, address: String, interval: Duration, shutdown: Shutdown) {
+    info!(target: "mining", "Mining with {} threads", engine.threads());
+    while let Some(_guard) = shutdown.guard() {
+        if shutdown.is_triggered() {
+            break;
+        }
+        match mine_next_block(&dag, &engine, &address, &shutdown).await {
+            Ok(MiningOutcome::Mined(block)) => {
+                info!(target: "mining", "Mined block {} at index {} with {} transactions ({:.0} H/s)", block.hash, block.index, block.transactions.len(), engine.hash_rate());
+                let balance = lock_dag(&dag).balance(&address);
+                info!(target: "wallet", "Current Balance: {} ({} immature)", balance.total, balance.immature);
+            }
+            Ok(MiningOutcome::Stale) => {
+                debug!(target: "mining", "Tips changed while mining, starting on a new template");
+                continue;
+            }
+            Ok(MiningOutcome::Stopped) => break,
+            Err(e) => error!(target: "mining", "Failed to mine block: {}", e),
+        }
+        tokio::select! {
+            _ = sleep(interval) => {}
+            _ = shutdown.triggered() => {}
+        }
+    }
+}
+
+/// Mines one block through a node's JSON-RPC interface: `getBlockTemplate`, a local nonce search,
+/// then `submitBlock`. The node's tips are polled meanwhile so stale templates are abandoned.
+pub async fn mine_remote_block(client: &RpcClient, engine: &MiningEngine, address: &str, shutdown: &Shutdown) -> Result<MiningOutcome, Error> {
+    let tips = client.call("getTips", json!([])).await?;
+    let template: BlockTemplate = serde_json::from_value(client.call("getBlockTemplate", json!([address])).await?)?;
+    template.pow()?;
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut search = tokio::task::spawn_blocking({
+        let (engine, cancel) = (engine.clone(), cancel.clone());
+        move || engine.solve(&template, &cancel)
+    });
+
+    let mut poll = interval(TIP_POLL_INTERVAL);
+    poll.tick().await;
+    let outcome = loop {
+        tokio::select! {
+            block = &mut search => break block.map_err(Error::other)?,
+            _ = poll.tick() => match client.call("getTips", json!([])).await {
+                Ok(current) if current != tips => {
+                    cancel.store(true, Ordering::Relaxed);
+                    let _ = search.await;
+                    return Ok(MiningOutcome::Stale);
+                }
+                Ok(_) => {}
+                // Keep searching; the submission will tell whether the node is back
+                Err(e) => debug!(target: "mining", "Failed to poll tips from {}: {}", client.url(), e),
+            },
+            _ = shutdown.triggered() => {
+                cancel.store(true, Ordering::Relaxed);
+                let _ = search.await;
+                return Ok(MiningOutcome::Stopped);
+            }
+        }
+    };
+    let block = match outcome {
+        Some(block) => block,
+        None => return Ok(MiningOutcome::Stopped),
+    };
+    client.call("submitBlock", json!([block])).await?;
+    Ok(MiningOutcome::Mined(Box::new(block)))
+}
+
+/// Mines blocks paying `address` on the node behind `client` until shutdown, pausing `interval`
+/// after each one.
+pub async fn run_remote_miner(client: RpcClient, engine: MiningEngine, address: String, interval: Duration, shutdown: Shutdown) {
+    info!(target: "mining", "Mining against {} with {} threads", client.url(), engine.threads());
+    while let Some(_guard) = shutdown.guard() {
+        if shutdown.is_triggered() {
+            break;
+        }
+        let pause = match mine_remote_block(&client, &engine, &address, &shutdown).await {
+            Ok(MiningOutcome::Mined(block)) => {
+                info!(target: "mining", "Submitted block {} at index {} with {} transactions ({:.0} H/s)", block.hash, block.index, block.transactions.len(), engine.hash_rate());
+                interval
+            }
+            Ok(MiningOutcome::Stale) => {
+                debug!(target: "mining", "Node tips changed while mining, fetching a new template");
+                continue;
+            }
+            Ok(MiningOutcome::Stopped) => break,
+            Err(e) => {
+                warn!(target: "mining", "Mining against {} failed: {}", client.url(), e);
+                RETRY_DELAY
+            }
+        };
+        tokio::select! {
+            _ = sleep(pause) => {}
+            _ = shutdown.triggered() => {}
+        }
+    }
+}
@@ -1,41 +1,97 @@
 // src/network.rs
 
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use std::collections::HashSet;
+use tokio::net::TcpListener;
+use log::{debug, error, info, warn};
 use crate::blockdag::BlockDAG;
 use crate::messages::Message;
+use crate::shutdown::Shutdown;
+use crate::transport::{Connection, NodeIdentity, PeerInfo, Peers, TransportMode};
+use std::io::{Error, ErrorKind};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-pub async fn start_server(dag: Arc<Mutex<BlockDAG>>, port: u16, peers: Arc<Mutex<HashSet<String>>>) {
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await.unwrap();
-    println!("Server running on port {}", port);
+/// How long a fresh node waits for every peer it asked for a pruning point proof before it
+/// settles for the heaviest one received.
+const PROOF_WAIT: Duration = Duration::from_secs(5);
+
+/// Accepts peers on `listen` until `shutdown` is triggered.
+pub async fn start_server(dag: Arc<Mutex<BlockDAG>>, listen: String, peers: Peers, mode: TransportMode, identity: Arc<NodeIdentity>, shutdown: Shutdown) {
+    let listener = TcpListener::bind(&listen).await.unwrap();
+    serve_peers(listener, dag, peers, mode, identity, shutdown).await;
+}
+
+/// Like [`start_server`], on a socket the caller has already bound.
+pub async fn serve_peers(listener: TcpListener, dag: Arc<Mutex<BlockDAG>>, peers: Peers, mode: TransportMode, identity: Arc<NodeIdentity>, shutdown: Shutdown) {
+    let magic = dag.lock().expect("Mutex lock poisoned").params().magic;
+    let listen = listener.local_addr().map(|address| address.to_string()).unwrap_or_default();
+    info!(target: "network", "Server running on {} ({:?} transport, node id {})", listen, mode, identity.node_id());
 
     loop {
-        let (socket, addr) = listener.accept().await.unwrap();
+        let (socket, addr) = tokio::select! {
+            accepted = listener.accept() => accepted.unwrap(),
+            _ = shutdown.triggered() => break,
+        };
         let dag = dag.clone();
         let peers = peers.clone();
+        let identity = identity.clone();
+        let shutdown = shutdown.clone();
 
         tokio::spawn(async move {
-            handle_connection(dag, peers, socket, addr.to_string()).await;
+            match Connection::accept(socket, magic, mode, &identity).await {
+                Ok(conn) => {
+                    let metrics = dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).metrics();
+                    handle_connection(dag, peers, conn.with_metrics(metrics), addr.to_string(), shutdown).await
+                }
+                Err(e) => warn!(target: "network", "Handshake with {} failed: {}", addr, e),
+            }
         });
     }
+    info!(target: "network", "Stopped accepting peers on {}", listen);
 }
 
-pub async fn handle_connection(dag: Arc<Mutex<BlockDAG>>, peers: Arc<Mutex<HashSet<String>>>, mut socket: TcpStream, addr: String) {
-    let mut buffer = [0; 1024];
-
-    match socket.read(&mut buffer).await {
-        Ok(size) if size > 0 => {
-            let message = String::from_utf8_lossy(&buffer[..size]);
-            let msg = Message::from_str(&message);
-            handle_message(dag, peers, msg, socket, addr).await;
+/// Serves a peer session until either side closes it or `shutdown` is triggered; a message
+/// already being handled is finished first.
+pub async fn handle_connection(dag: Arc<Mutex<BlockDAG>>, peers: Peers, mut conn: Connection, addr: String, shutdown: Shutdown) {
+    let _guard = match shutdown.guard() {
+        Some(guard) => guard,
+        None => return,
+    };
+    register_peer(&peers, &conn, &addr);
+    let metrics = dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).metrics();
+    metrics.peer_connected();
+    loop {
+        let received = tokio::select! {
+            received = conn.recv() => received,
+            _ = shutdown.triggered() => {
+                if let Err(e) = conn.close().await {
+                    debug!(target: "network", "Failed to close session with {}: {}", addr, e);
+                }
+                debug!(target: "network", "Closed session with {}", addr);
+                break;
+            }
+        };
+        match received {
+            Ok(Some(msg)) => {
+                // The block stays queued for the next write, but the peer shouldn't count on it being stored
+                if let Err(e) = handle_message(dag.clone(), peers.clone(), msg, &mut conn, addr.clone()).await {
+                    error!(target: "storage", "Dropping session with {}: {}", addr, e);
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!(target: "network", "Connection with {} failed: {}", addr, e);
+                break;
+            }
         }
-        _ => (),
     }
+    let abandoned = dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).abandon_pruning_point_proof(&addr);
+    log_proof_outcome(abandoned, &addr);
+    metrics.peer_disconnected();
 }
 
-pub async fn handle_message(dag: Arc<Mutex<BlockDAG>>, peers: Arc<Mutex<HashSet<String>>>, msg: Message, mut socket: TcpStream, addr: String) {
+/// Handles one message from a peer. Fails only if an accepted block could not be stored.
+pub async fn handle_message(dag: Arc<Mutex<BlockDAG>>, peers: Peers, msg: Message, conn: &mut Connection, addr: String) -> Result<(), Error> {
     match msg {
         Message::RequestBlock(hash) => {
             let block = {
@@ -49,31 +105,28 @@ pub async fn handle_message(dag: Arc<Mutex<BlockDAG>>, peers: Arc<Mutex<HashSet<
             };
 
             if let Some(block) = block {
-                let response = Message::NewBlock(block).to_string();
-                socket.write_all(response.as_bytes()).await.unwrap();
+                if let Err(e) = conn.send(&Message::NewBlock(block)).await {
+                    warn!(target: "network", "Failed to send block to {}: {}", addr, e);
+                }
             }
         }
         Message::NewBlock(block) => {
-            match dag.lock() {
-                Ok(mut dag_guard) => {
-                    if dag_guard.validate_block(&block) {
-                        dag_guard.blocks.insert(block.hash.clone(), block.clone());
-                        dag_guard.update_tips(block.hash.clone());
-                        println!("New block added: {:?}", block);
-                    } else {
-                        println!("Invalid block received: {:?}", block);
-                    }
-                }
-                Err(poisoned) => {
-                    let mut dag_guard = poisoned.into_inner();
-                    if dag_guard.validate_block(&block) {
-                        dag_guard.blocks.insert(block.hash.clone(), block.clone());
-                        dag_guard.update_tips(block.hash.clone());
-                        println!("New block added: {:?}", block);
-                    } else {
-                        println!("Invalid block received: {:?}", block);
+            let mut dag_guard = match dag.lock() {
+                Ok(dag_guard) => dag_guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let (hash, index, transaction_count) = (block.hash.clone(), block.index, block.transactions.len());
+            match dag_guard.submit_block(block) {
+                Ok(()) => {
+                    // Only accepted blocks count towards sync progress, or one bogus index would pin it
+                    dag_guard.metrics.peer_block_seen(index);
+                    if let Err(e) = dag_guard.persist_block(&hash) {
+                        return Err(Error::new(e.kind(), format!("failed to persist block {}: {}", hash, e)));
                     }
+                    info!(target: "network", "Accepted block {} from {} ({} transactions)", hash, addr, transaction_count);
                 }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => debug!(target: "network", "Ignored known block {} from {}", hash, addr),
+                Err(e) => warn!(target: "network", "Rejected block {} from {}: {}", hash, addr, e),
             }
         }
         Message::RequestTip => {
@@ -88,66 +141,118 @@ pub async fn handle_message(dag: Arc<Mutex<BlockDAG>>, peers: Arc<Mutex<HashSet<
             };
 
             if !tips.is_empty() {
-                let response = Message::Tip(tips[0].clone()).to_string();
-                socket.write_all(response.as_bytes()).await.unwrap();
+                if let Err(e) = conn.send(&Message::Tip(tips[0].clone())).await {
+                    warn!(target: "network", "Failed to send tip to {}: {}", addr, e);
+                }
             }
         }
         Message::Tip(hash) => {
-            println!("Received tip from peer: {}", hash);
+            debug!(target: "network", "Received tip from peer: {}", hash);
             let need_request_block = {
                 match dag.lock() {
-                    Ok(dag_guard) => !dag_guard.blocks.contains_key(&hash),
+                    Ok(dag_guard) => !dag_guard.has_block(&hash),
                     Err(poisoned) => {
                         let dag_guard = poisoned.into_inner();
-                        !dag_guard.blocks.contains_key(&hash)
+                        !dag_guard.has_block(&hash)
                     }
                 }
             };
             if need_request_block {
-                let request = Message::RequestBlock(hash).to_string();
-                socket.write_all(request.as_bytes()).await.unwrap();
+                if let Err(e) = conn.send(&Message::RequestBlock(hash)).await {
+                    warn!(target: "network", "Failed to request block from {}: {}", addr, e);
+                }
             }
         }
         Message::NewTransaction(transaction) => {
-            match dag.lock() {
-                Ok(mut dag_guard) => {
-                    dag_guard.add_transaction(transaction);
-                    println!("New transaction added");
+            let txid = transaction.txid();
+            let added = match dag.lock() {
+                Ok(mut dag_guard) => dag_guard.add_transaction(transaction),
+                Err(poisoned) => poisoned.into_inner().add_transaction(transaction),
+            };
+            match added {
+                Ok(()) => debug!(target: "network", "Added transaction {} from {}", txid, addr),
+                Err(e) => debug!(target: "network", "Rejected transaction {} from {}: {}", txid, addr, e),
+            }
+        }
+        Message::RequestPruningPointProof => {
+            let proof = {
+                match dag.lock() {
+                    Ok(dag_guard) => dag_guard.pruning_point_proof(),
+                    Err(poisoned) => poisoned.into_inner().pruning_point_proof(),
                 }
-                Err(poisoned) => {
-                    let mut dag_guard = poisoned.into_inner();
-                    dag_guard.add_transaction(transaction);
-                    println!("New transaction added");
+            };
+            if let Some(proof) = proof {
+                if let Err(e) = conn.send(&Message::PruningPointProof(Box::new(proof))).await {
+                    warn!(target: "network", "Failed to send pruning point proof to {}: {}", addr, e);
                 }
             }
         }
+        Message::PruningPointProof(proof) => {
+            let received = match dag.lock() {
+                Ok(mut dag_guard) => dag_guard.receive_pruning_point_proof(&addr, *proof),
+                Err(poisoned) => poisoned.into_inner().receive_pruning_point_proof(&addr, *proof),
+            };
+            log_proof_outcome(received, &addr);
+        }
         Message::Unknown => {
-            println!("Received unknown message");
+            warn!(target: "network", "Received unknown message");
         }
     }
 
-    let mut peers_guard = peers.lock().expect("Mutex lock poisoned");
-    peers_guard.insert(addr);
+    register_peer(&peers, conn, &addr);
+    Ok(())
+}
+
+fn log_proof_outcome(outcome: Result<Option<String>, Error>, addr: &str) {
+    match outcome {
+        Ok(Some(pruning_point)) => info!(target: "network", "Bootstrapped from pruning point {} after hearing from {}", pruning_point, addr),
+        Ok(None) => {}
+        Err(e) => warn!(target: "network", "Rejected pruning point proof from {}: {}", addr, e),
+    }
 }
 
-pub async fn connect_to_server(address: &str, dag: Arc<Mutex<BlockDAG>>, peers: Arc<Mutex<HashSet<String>>>) {
-    match TcpStream::connect(address).await {
-        Ok(mut socket) => {
-            let request = Message::RequestTip.to_string();
-            socket.write_all(request.as_bytes()).await.unwrap();
+fn register_peer(peers: &Peers, conn: &Connection, addr: &str) {
+    let mut peers_guard = peers.lock().expect("Mutex lock poisoned");
+    peers_guard.insert(addr.to_string(), PeerInfo {
+        address: addr.to_string(),
+        node_id: conn.remote_id.clone(),
+        encrypted: conn.is_encrypted(),
+    });
+}
 
-            let mut buffer = [0; 1024];
-            match socket.read(&mut buffer).await {
-                Ok(size) if size > 0 => {
-                    let response = String::from_utf8_lossy(&buffer[..size]);
-                    let msg = Message::from_str(&response);
-                    handle_message(dag, peers, msg, socket, address.to_string()).await;
+pub async fn connect_to_server(address: &str, dag: Arc<Mutex<BlockDAG>>, peers: Peers, mode: TransportMode, identity: Arc<NodeIdentity>, shutdown: Shutdown) {
+    let (magic, metrics) = {
+        let dag_guard = dag.lock().expect("Mutex lock poisoned");
+        (dag_guard.params().magic, dag_guard.metrics())
+    };
+    match Connection::connect(address, magic, mode, &identity).await {
+        Ok(conn) => {
+            let mut conn = conn.with_metrics(metrics);
+            let fresh = dag.lock().expect("Mutex lock poisoned").blocks.len() <= 1;
+            if fresh {
+                dag.lock().expect("Mutex lock poisoned").request_pruning_point_proof(address);
+                if let Err(e) = conn.send(&Message::RequestPruningPointProof).await {
+                    warn!(target: "network", "Failed to request pruning point proof: {}", e);
+                    log_proof_outcome(dag.lock().expect("Mutex lock poisoned").abandon_pruning_point_proof(address), address);
+                    return;
                 }
-                _ => (),
+                // Peers that have no proof to serve never answer
+                let dag = dag.clone();
+                let address = address.to_string();
+                tokio::spawn(async move {
+                    tokio::time::sleep(PROOF_WAIT).await;
+                    let finished = dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).finish_pruning_point_proof_requests(true);
+                    log_proof_outcome(finished, &address);
+                });
+            }
+            if let Err(e) = conn.send(&Message::RequestTip).await {
+                warn!(target: "network", "Failed to request tip: {}", e);
+                return;
             }
+            handle_connection(dag, peers, conn, address.to_string(), shutdown).await;
         }
         Err(e) => {
-            println!("Failed to connect: {}", e);
+            warn!(target: "network", "Failed to connect: {}", e);
         }
     }
 }
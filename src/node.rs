@@ -0,0 +1,213 @@
+// src/node.rs
+
+use std::collections::HashMap;
+use std::io::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::info;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+use crate::blockdag::BlockDAG;
+use crate::config::NodeConfig;
+use crate::metrics::Metrics;
+use crate::mining::{run_miner, MiningEngine, DEFAULT_MINING_INTERVAL};
+use crate::network::{connect_to_server, serve_peers};
+use crate::rpc::{serve_rpc, RpcContext};
+use crate::shutdown::Shutdown;
+use crate::storage::{LogStore, MemoryStore, Storage};
+use crate::transport::{NodeIdentity, Peers};
+
+/// How long [`Node::stop`] waits for peer sessions and the miner to finish.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Configured peers are dialed after this delay, once our own server is up.
+const PEER_CONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Sets up a [`Node`]: storage defaults to the database in the configured data directory, and
+/// mining is off unless a payout address is given.
+pub struct NodeBuilder {
+    config: NodeConfig,
+    store: Option<Box<dyn Storage>>,
+    identity: Option<NodeIdentity>,
+    mining_address: Option<String>,
+    mining_interval: Duration,
+    shutdown: Shutdown,
+}
+
+impl NodeBuilder {
+    pub fn store(mut self, store: Box<dyn Storage>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Keeps the DAG in memory only, e.g. for tests.
+    pub fn in_memory(self) -> Self {
+        self.store(Box::new(MemoryStore::new()))
+    }
+
+    pub fn identity(mut self, identity: NodeIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Mines blocks paying `address`.
+    pub fn miner(mut self, address: impl Into<String>) -> Self {
+        self.mining_address = Some(address.into());
+        self
+    }
+
+    /// Pause between mined blocks.
+    pub fn mining_interval(mut self, interval: Duration) -> Self {
+        self.mining_interval = interval;
+        self
+    }
+
+    /// Shares a shutdown handle created elsewhere instead of a fresh one.
+    pub fn shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Opens the DAG, binds the P2P and RPC sockets and spawns the node's tasks.
+    pub async fn start(self) -> Result<Node, Error> {
+        let config = self.config;
+        let params = config.params()?;
+        let (store, import) = match self.store {
+            Some(store) => (store, None),
+            None => {
+                config.ensure_data_dir()?;
+                (Box::new(LogStore::open(config.db_path())?) as Box<dyn Storage>, Some(config.dag_path()))
+            }
+        };
+        let metrics = Arc::new(Metrics::default());
+        let mut dag = BlockDAG::open(store, params, import.as_deref())?;
+        dag.set_metrics(metrics.clone());
+        dag.prune_headers = config.prune_headers;
+        if config.tx_index {
+            dag.enable_tx_index();
+        }
+        let dag = Arc::new(Mutex::new(dag));
+        let peers: Peers = Arc::new(Mutex::new(HashMap::new()));
+        let identity = Arc::new(match self.identity {
+            Some(identity) => identity,
+            // Nodes on an in-memory store leave nothing behind, their key included
+            None if import.is_none() => NodeIdentity::generate(),
+            None => NodeIdentity::load_or_generate(&config.node_key_path())?,
+        });
+        let shutdown = self.shutdown;
+
+        let listener = TcpListener::bind(config.listen_socket()).await?;
+        let p2p_address = listener.local_addr()?;
+        tokio::spawn(serve_peers(listener, dag.clone(), peers.clone(), config.transport, identity.clone(), shutdown.clone()));
+
+        let rpc_address = if config.disable_rpc {
+            None
+        } else {
+            let listener = TcpListener::bind(config.rpc_socket()).await?;
+            let address = listener.local_addr()?;
+            let context = RpcContext { dag: dag.clone(), peers: peers.clone(), shutdown: shutdown.clone(), metrics: metrics.clone() };
+            tokio::spawn(serve_rpc(listener, context));
+            Some(address)
+        };
+
+        let node = Node { config, dag, peers, identity, shutdown, metrics, p2p_address, rpc_address };
+        for peer in node.config.peers.clone() {
+            node.connect_after(peer, PEER_CONNECT_DELAY);
+        }
+        if let Some(address) = self.mining_address {
+            let engine = MiningEngine::new(node.config.mining_threads()).metrics(node.metrics.clone());
+            tokio::spawn(run_miner(node.dag.clone(), engine, address, self.mining_interval, node.shutdown.clone()));
+        }
+        Ok(node)
+    }
+}
+
+/// A running full node: DAG and mempool, P2P server, optional RPC server and miner.
+pub struct Node {
+    config: NodeConfig,
+    dag: Arc<Mutex<BlockDAG>>,
+    peers: Peers,
+    identity: Arc<NodeIdentity>,
+    shutdown: Shutdown,
+    metrics: Arc<Metrics>,
+    p2p_address: SocketAddr,
+    rpc_address: Option<SocketAddr>,
+}
+
+impl Node {
+    pub fn builder(config: NodeConfig) -> NodeBuilder {
+        NodeBuilder {
+            config,
+            store: None,
+            identity: None,
+            mining_address: None,
+            mining_interval: DEFAULT_MINING_INTERVAL,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    pub fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+
+    pub fn dag(&self) -> Arc<Mutex<BlockDAG>> {
+        self.dag.clone()
+    }
+
+    pub fn peers(&self) -> Peers {
+        self.peers.clone()
+    }
+
+    pub fn identity(&self) -> &NodeIdentity {
+        &self.identity
+    }
+
+    /// Address the P2P server is bound to, useful when configured with port 0.
+    pub fn p2p_address(&self) -> SocketAddr {
+        self.p2p_address
+    }
+
+    pub fn rpc_address(&self) -> Option<SocketAddr> {
+        self.rpc_address
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    pub fn rpc_context(&self) -> RpcContext {
+        RpcContext { dag: self.dag.clone(), peers: self.peers.clone(), shutdown: self.shutdown.clone(), metrics: self.metrics.clone() }
+    }
+
+    /// Opens a session with the peer at `address` in the background.
+    pub fn connect(&self, address: impl Into<String>) {
+        self.connect_after(address.into(), Duration::ZERO);
+    }
+
+    fn connect_after(&self, address: String, delay: Duration) {
+        let (dag, peers, identity, shutdown) = (self.dag.clone(), self.peers.clone(), self.identity.clone(), self.shutdown.clone());
+        let mode = self.config.transport;
+        tokio::spawn(async move {
+            sleep(delay).await;
+            connect_to_server(&address, dag, peers, mode, identity, shutdown).await;
+        });
+    }
+
+    /// Resolves once something (a signal, the RPC `stop` method) asks the node to stop.
+    pub async fn wait_for_shutdown(&self) {
+        self.shutdown.triggered().await
+    }
+
+    /// Stops mining and peer sessions, waits for in-flight work and flushes the DAG's state.
+    pub async fn stop(self) -> Result<(), Error> {
+        self.shutdown.trigger("node stopped");
+        self.shutdown.drain(SHUTDOWN_TIMEOUT).await;
+        self.dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).flush()?;
+        info!(target: "node", "Stopped cleanly");
+        Ok(())
+    }
+}
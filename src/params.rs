@@ -0,0 +1,243 @@
+// src/params.rs
+
+use crate::block::Block;
+use crate::pow::{PowHash, BLAKE3, SHA256, SHA256D};
+use crate::constants::{INITIAL_BLOCK_REWARD, HALVING_INTERVAL, TARGET_BLOCK_TIME, DIFFICULTY_ADJUSTMENT_INTERVAL, TOTAL_SUPPLY};
+
+/// Fixed genesis block of a network; every node must derive the same hash from it.
+pub struct GenesisParams {
+    pub timestamp: u128,
+    pub nonce: u64,
+    pub difficulty: u64,
+    pub message: &'static str,
+    pub hash: &'static str,
+}
+
+/// Consensus and networking parameters of one network profile.
+pub struct NetworkParams {
+    pub name: &'static str,
+    pub genesis: GenesisParams,
+    pub initial_block_reward: u64,
+    pub halving_interval: u64,
+    pub total_supply: u64,
+    pub target_block_time: u128,
+    pub difficulty_window: usize,
+    pub min_difficulty: u64,
+    pub no_retargeting: bool,
+    /// Hash function blocks are mined with; changing it changes every block hash, genesis included.
+    pub pow: &'static dyn PowHash,
+    pub ghostdag_k: u64,
+    /// Blue score depth below the virtual a block needs before its coinbase can be spent.
+    pub coinbase_maturity: u64,
+    /// Blue score depth below the virtual beyond which the selected chain can't be reorganized.
+    pub finality_depth: u64,
+    /// Blue score depth below the virtual at which blocks are pruned; larger than `finality_depth`.
+    pub pruning_depth: u64,
+    pub magic: [u8; 4],
+    pub default_port: u16,
+    pub default_rpc_port: u16,
+}
+
+pub static MAINNET: NetworkParams = NetworkParams {
+    name: "mainnet",
+    genesis: GenesisParams {
+        timestamp: 1716213827832,
+        nonce: 34539,
+        difficulty: 4,
+        message: "Genesis Block - Welcome to BlockDAG!",
+        hash: "00009710be19b59523c2664d645f5837607aa6409a17776c876b51663e576534",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: HALVING_INTERVAL,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: TARGET_BLOCK_TIME,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 1,
+    no_retargeting: false,
+    pow: &SHA256,
+    ghostdag_k: 18,
+    coinbase_maturity: 100,
+    finality_depth: 1000,
+    pruning_depth: 2000,
+    magic: *b"BDAG",
+    default_port: 8080,
+    default_rpc_port: 8081,
+};
+
+pub static TESTNET: NetworkParams = NetworkParams {
+    name: "testnet",
+    genesis: GenesisParams {
+        timestamp: 1760745600000,
+        nonce: 6848,
+        difficulty: 4,
+        message: "Genesis Block - BlockDAG Testnet",
+        hash: "0000c77d1ecea5413a26a0bee85e77e0dfd0059ad605b0ad22ca2ffb6c2e37c2",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: HALVING_INTERVAL,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: TARGET_BLOCK_TIME,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 1,
+    no_retargeting: false,
+    pow: &SHA256,
+    ghostdag_k: 18,
+    coinbase_maturity: 100,
+    finality_depth: 1000,
+    pruning_depth: 2000,
+    magic: *b"BDTN",
+    default_port: 18080,
+    default_rpc_port: 18081,
+};
+
+// Testnet variants for trying other proof-of-work functions; otherwise identical to testnet.
+pub static TESTNET_SHA256D: NetworkParams = NetworkParams {
+    name: "testnet-sha256d",
+    genesis: GenesisParams {
+        timestamp: 1760745600000,
+        nonce: 97022,
+        difficulty: 4,
+        message: "Genesis Block - BlockDAG Testnet (SHA-256d)",
+        hash: "00008862f6e9e6c2cbf2d2e4ee53a0df92591aee6e9c9815f6e9cac91bb08b09",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: HALVING_INTERVAL,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: TARGET_BLOCK_TIME,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 1,
+    no_retargeting: false,
+    pow: &SHA256D,
+    ghostdag_k: 18,
+    coinbase_maturity: 100,
+    finality_depth: 1000,
+    pruning_depth: 2000,
+    magic: *b"BDT2",
+    default_port: 18180,
+    default_rpc_port: 18181,
+};
+
+pub static TESTNET_BLAKE3: NetworkParams = NetworkParams {
+    name: "testnet-blake3",
+    genesis: GenesisParams {
+        timestamp: 1760745600000,
+        nonce: 22670,
+        difficulty: 4,
+        message: "Genesis Block - BlockDAG Testnet (BLAKE3)",
+        hash: "00000972e6fba45654d2895f07747ed8fae18762b0c5d27d1cd3078033a52825",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: HALVING_INTERVAL,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: TARGET_BLOCK_TIME,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 1,
+    no_retargeting: false,
+    pow: &BLAKE3,
+    ghostdag_k: 18,
+    coinbase_maturity: 100,
+    finality_depth: 1000,
+    pruning_depth: 2000,
+    magic: *b"BDT3",
+    default_port: 18280,
+    default_rpc_port: 18281,
+};
+
+pub static SIMNET: NetworkParams = NetworkParams {
+    name: "simnet",
+    genesis: GenesisParams {
+        timestamp: 1760745600000,
+        nonce: 227,
+        difficulty: 2,
+        message: "Genesis Block - BlockDAG Simnet",
+        hash: "001c0cc53fca6434f4679f67faf827a39a9c66a0c3d2cdad4c0fee26c5f84d38",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: 150,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: 1000,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 1,
+    no_retargeting: false,
+    pow: &SHA256,
+    ghostdag_k: 18,
+    coinbase_maturity: 20,
+    finality_depth: 100,
+    pruning_depth: 200,
+    magic: *b"BDSM",
+    default_port: 18555,
+    default_rpc_port: 18556,
+};
+
+// Regtest never retargets and starts at difficulty 0, so any nonce is a valid solution.
+pub static REGTEST: NetworkParams = NetworkParams {
+    name: "regtest",
+    genesis: GenesisParams {
+        timestamp: 1760745600000,
+        nonce: 0,
+        difficulty: 0,
+        message: "Genesis Block - BlockDAG Regtest",
+        hash: "9e713c9fe9244b05d70b5e89ae32537efee0899f7610375990bf9ee74f6b4610",
+    },
+    initial_block_reward: INITIAL_BLOCK_REWARD,
+    halving_interval: 150,
+    total_supply: TOTAL_SUPPLY,
+    target_block_time: TARGET_BLOCK_TIME,
+    difficulty_window: DIFFICULTY_ADJUSTMENT_INTERVAL,
+    min_difficulty: 0,
+    no_retargeting: true,
+    pow: &SHA256,
+    ghostdag_k: 18,
+    coinbase_maturity: 10,
+    finality_depth: 50,
+    pruning_depth: 100,
+    magic: *b"BDRT",
+    default_port: 18444,
+    default_rpc_port: 18443,
+};
+
+impl NetworkParams {
+    pub fn by_name(name: &str) -> Option<&'static NetworkParams> {
+        match name {
+            "mainnet" => Some(&MAINNET),
+            "testnet" => Some(&TESTNET),
+            "testnet-sha256d" => Some(&TESTNET_SHA256D),
+            "testnet-blake3" => Some(&TESTNET_BLAKE3),
+            "simnet" => Some(&SIMNET),
+            "regtest" => Some(&REGTEST),
+            _ => None,
+        }
+    }
+
+    /// Whether a block may carry this difficulty: fixed at the genesis difficulty without
+    /// retargeting, otherwise never below `min_difficulty`.
+    pub fn allows_difficulty(&self, difficulty: u64) -> bool {
+        if self.no_retargeting {
+            difficulty == self.genesis.difficulty
+        } else {
+            difficulty >= self.min_difficulty
+        }
+    }
+
+    /// Rebuilds the genesis block and checks it against the hard-coded hash.
+    pub fn genesis_block(&self) -> Block {
+        let genesis = &self.genesis;
+        let previous_hashes = vec!["0".to_string()];
+        let hash = Block::calculate_hash(self.pow, 0, genesis.timestamp, &previous_hashes, genesis.nonce, &vec![], "");
+        assert_eq!(hash, genesis.hash, "Genesis block of {} does not match its hard-coded hash", self.name);
+        Block {
+            index: 0,
+            timestamp: genesis.timestamp,
+            previous_hashes,
+            hash,
+            nonce: genesis.nonce,
+            transactions: vec![],
+            weight: 0,
+            reward: 0,
+            difficulty: genesis.difficulty,
+            message: Some(genesis.message.to_string()),
+            state_commitment: String::new(),
+            transactions_root: String::new(),
+        }
+    }
+}
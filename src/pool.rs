@@ -0,0 +1,429 @@
+// src/pool.rs
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use log::{debug, info, warn};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep};
+use crate::block::{Block, BlockTemplate};
+use crate::mining::MiningEngine;
+use crate::rpc::{error_response, required, RpcClient, RpcError, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR, REJECTED};
+use crate::shutdown::Shutdown;
+
+/// Longest protocol line accepted; a job carries a whole block template.
+const MAX_LINE: usize = 4 * 1024 * 1024;
+/// How often the pool asks the node whether its tips moved.
+const TEMPLATE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Pause before a worker reconnects to a pool that went away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const NOT_LOGGED_IN: i64 = -32003;
+
+/// Work handed to one worker: a template with its own coinbase extra nonce, and the easier
+/// difficulty at which solutions count as shares.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub job_id: u64,
+    pub template: BlockTemplate,
+    pub share_difficulty: u64,
+}
+
+/// Share accounting for one worker, keyed by the payout address it logged in with.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStats {
+    pub accepted_shares: u64,
+    /// Shares for outdated jobs or below the share difficulty.
+    pub rejected_shares: u64,
+    pub blocks_found: u64,
+}
+
+/// Stratum-like mining pool: workers speak line-delimited JSON-RPC over TCP, receive `job`
+/// notifications built from the node's block templates and `submit` nonces meeting the share
+/// difficulty. Shares that also meet the block difficulty are forwarded to the node.
+///
+/// Worker methods: `login` (`[address]` or `{worker}`), `submit` (`[jobId, nonce]` or
+/// `{jobId, nonce}`) and `getStats`.
+#[derive(Clone)]
+pub struct PoolServer {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    client: RpcClient,
+    payout_address: String,
+    share_difficulty: Option<u64>,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    template: Option<BlockTemplate>,
+    tips: Value,
+    /// Also the coinbase extra nonce of the job, so no two jobs search the same space.
+    next_job_id: u64,
+    next_session_id: u64,
+    sessions: HashMap<u64, Session>,
+    workers: BTreeMap<String, WorkerStats>,
+}
+
+struct Session {
+    worker: Option<String>,
+    job: Option<Job>,
+    outgoing: mpsc::UnboundedSender<Value>,
+}
+
+/// A share that passed validation, to be credited and possibly forwarded as a block.
+struct Share {
+    worker: String,
+    block: Block,
+    is_block: bool,
+}
+
+impl PoolServer {
+    /// A pool mining on the node behind `client`, paying block rewards to `payout_address`.
+    /// The share difficulty defaults to one below the block difficulty.
+    pub fn new(client: RpcClient, payout_address: impl Into<String>, share_difficulty: Option<u64>) -> PoolServer {
+        let state = PoolState { next_job_id: 1, ..PoolState::default() };
+        PoolServer {
+            inner: Arc::new(PoolInner { client, payout_address: payout_address.into(), share_difficulty, state: Mutex::new(state) }),
+        }
+    }
+
+    /// Per-worker share accounting, e.g. to split payouts.
+    pub fn stats(&self) -> BTreeMap<String, WorkerStats> {
+        self.lock_state().workers.clone()
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, PoolState> {
+        self.inner.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Accepts workers on `listener` and keeps their jobs current until shutdown.
+    pub async fn run(self, listener: TcpListener, shutdown: Shutdown) {
+        if let Ok(address) = listener.local_addr() {
+            info!(target: "mining", "Pool server running on {}, mining against {}", address, self.inner.client.url());
+        }
+        tokio::spawn(self.clone().follow_templates(shutdown.clone()));
+        loop {
+            let (socket, address) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(target: "mining", "Failed to accept pool connection: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown.triggered() => break,
+            };
+            let pool = self.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                debug!(target: "mining", "Worker connected from {}", address);
+                if let Err(e) = pool.serve_worker(socket, shutdown).await {
+                    debug!(target: "mining", "Worker {} disconnected: {}", address, e);
+                }
+            });
+        }
+    }
+
+    /// Polls the node's tips and hands every worker a new job when they move.
+    async fn follow_templates(self, shutdown: Shutdown) {
+        let mut poll = interval(TEMPLATE_POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            if let Err(e) = self.refresh_template(false).await {
+                warn!(target: "mining", "Failed to fetch a block template from {}: {}", self.inner.client.url(), e);
+            }
+        }
+    }
+
+    /// Fetches a new template if the node's tips changed, or unconditionally when `force` is set.
+    async fn refresh_template(&self, force: bool) -> Result<(), Error> {
+        let tips = self.inner.client.call("getTips", json!([])).await?;
+        {
+            let state = self.lock_state();
+            if !force && state.template.is_some() && state.tips == tips {
+                return Ok(());
+            }
+        }
+        let template = self.inner.client.call("getBlockTemplate", json!([self.inner.payout_address])).await?;
+        let template: BlockTemplate = serde_json::from_value(template)?;
+        template.pow()?;
+        debug!(target: "mining", "New pool template at index {} with difficulty {}", template.index, template.difficulty);
+
+        let mut state = self.lock_state();
+        state.template = Some(template);
+        state.tips = tips;
+        let sessions: Vec<u64> = state.sessions.iter().filter(|(_, session)| session.worker.is_some()).map(|(id, _)| *id).collect();
+        for session in sessions {
+            self.issue_job(&mut state, session);
+        }
+        Ok(())
+    }
+
+    /// Replaces a session's job with a fresh one on the current template.
+    fn issue_job(&self, state: &mut PoolState, session_id: u64) {
+        let template = match &state.template {
+            Some(template) => template,
+            None => return,
+        };
+        let block_difficulty = template.difficulty;
+        let share_difficulty = self.inner.share_difficulty.unwrap_or(block_difficulty.saturating_sub(1)).min(block_difficulty);
+        let job = Job { job_id: state.next_job_id, template: template.with_extra_nonce(state.next_job_id), share_difficulty };
+        state.next_job_id += 1;
+        if let Some(session) = state.sessions.get_mut(&session_id) {
+            let _ = session.outgoing.send(json!({ "jsonrpc": "2.0", "method": "job", "params": job }));
+            session.job = Some(job);
+        }
+    }
+
+    async fn serve_worker(&self, socket: TcpStream, shutdown: Shutdown) -> Result<(), Error> {
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+        let (outgoing, mut queued) = mpsc::unbounded_channel();
+        let session_id = {
+            let mut state = self.lock_state();
+            let id = state.next_session_id;
+            state.next_session_id += 1;
+            state.sessions.insert(id, Session { worker: None, job: None, outgoing: outgoing.clone() });
+            id
+        };
+
+        // Replies and job notifications go out through the queue, in order
+        tokio::spawn(async move {
+            while let Some(message) = queued.recv().await {
+                if write_message(&mut writer, &message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let result = loop {
+            let message = tokio::select! {
+                message = read_message(&mut reader) => message,
+                _ = shutdown.triggered() => break Ok(()),
+            };
+            let response = match message {
+                Ok(Some(message)) => self.handle_message(session_id, message).await,
+                Ok(None) => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::InvalidData => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+                Err(e) => break Err(e),
+            };
+            if let Some(response) = response {
+                let _ = outgoing.send(response);
+            }
+        };
+        self.lock_state().sessions.remove(&session_id);
+        result
+    }
+
+    async fn handle_message(&self, session_id: u64, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let method = match message.get("method").and_then(Value::as_str) {
+            Some(method) => method.to_string(),
+            None => return Some(error_response(id.unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, "Missing method"))),
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method.as_str() {
+            "login" => self.login(session_id, &params).await,
+            "submit" => self.submit(session_id, &params).await,
+            "getStats" => Ok(json!(self.stats())),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    async fn login(&self, session_id: u64, params: &Value) -> Result<Value, RpcError> {
+        let worker: String = required(params, 0, "worker")?;
+        if self.lock_state().template.is_none() {
+            self.refresh_template(true).await.map_err(|e| RpcError::new(REJECTED, format!("Pool has no work: {}", e)))?;
+        }
+        let mut state = self.lock_state();
+        state.workers.entry(worker.clone()).or_default();
+        if let Some(session) = state.sessions.get_mut(&session_id) {
+            session.worker = Some(worker.clone());
+        }
+        self.issue_job(&mut state, session_id);
+        info!(target: "mining", "Worker {} logged in", worker);
+        Ok(json!({ "worker": worker }))
+    }
+
+    async fn submit(&self, session_id: u64, params: &Value) -> Result<Value, RpcError> {
+        let job_id: u64 = required(params, 0, "jobId")?;
+        let nonce: u64 = required(params, 1, "nonce")?;
+        let share = self.check_share(session_id, job_id, nonce)?;
+
+        let mut block_hash = None;
+        if share.is_block {
+            match self.inner.client.call("submitBlock", json!([share.block])).await {
+                Ok(_) => {
+                    info!(target: "mining", "Worker {} found block {}", share.worker, share.block.hash);
+                    self.lock_state().workers.entry(share.worker.clone()).or_default().blocks_found += 1;
+                    block_hash = Some(share.block.hash.clone());
+                }
+                Err(e) => warn!(target: "mining", "Node refused block {} from worker {}: {}", share.block.hash, share.worker, e),
+            }
+            // A new template comes with new jobs for every worker, this one included
+            match self.refresh_template(true).await {
+                Ok(()) => return Ok(json!({ "accepted": true, "block": block_hash })),
+                Err(e) => warn!(target: "mining", "Failed to fetch a block template from {}: {}", self.inner.client.url(), e),
+            }
+        }
+        let mut state = self.lock_state();
+        self.issue_job(&mut state, session_id);
+        Ok(json!({ "accepted": true, "block": block_hash }))
+    }
+
+    /// Validates a share against the session's current job and credits or debits its worker.
+    /// Each job accepts a single share; the worker gets a new job afterwards.
+    fn check_share(&self, session_id: u64, job_id: u64, nonce: u64) -> Result<Share, RpcError> {
+        let mut state = self.lock_state();
+        let session = state.sessions.get_mut(&session_id).expect("session registered while connected");
+        let worker = session.worker.clone().ok_or_else(|| RpcError::new(NOT_LOGGED_IN, "Log in before submitting shares"))?;
+        let verdict = match session.job.take() {
+            Some(job) if job.job_id == job_id => {
+                let block_difficulty = job.template.difficulty;
+                let pow = job.template.pow().map_err(|e| RpcError::new(REJECTED, e.to_string()))?;
+                let block = job.template.into_block(pow, nonce);
+                if Block::meets_difficulty(&block.hash, job.share_difficulty) {
+                    Ok(Share { is_block: Block::meets_difficulty(&block.hash, block_difficulty), worker: worker.clone(), block })
+                } else {
+                    Err("Share does not meet the share difficulty")
+                }
+            }
+            job => {
+                session.job = job;
+                Err("Stale or unknown job")
+            }
+        };
+        let stats = state.workers.entry(worker).or_default();
+        match verdict {
+            Ok(share) => {
+                stats.accepted_shares += 1;
+                Ok(share)
+            }
+            Err(reason) => {
+                stats.rejected_shares += 1;
+                Err(RpcError::new(REJECTED, reason))
+            }
+        }
+    }
+}
+
+/// Mines shares on the pool at `pool` (`host:port`), logged in as the payout address `worker`,
+/// until shutdown. Reconnects when the pool goes away.
+pub async fn run_pool_worker(pool: String, worker: String, engine: MiningEngine, shutdown: Shutdown) {
+    info!(target: "mining", "Mining on pool {} with {} threads", pool, engine.threads());
+    while let Some(_guard) = shutdown.guard() {
+        if shutdown.is_triggered() {
+            break;
+        }
+        match work_for_pool(&pool, &worker, &engine, &shutdown).await {
+            Ok(()) => break,
+            Err(e) => warn!(target: "mining", "Pool connection to {} failed: {}", pool, e),
+        }
+        tokio::select! {
+            _ = sleep(RECONNECT_DELAY) => {}
+            _ = shutdown.triggered() => {}
+        }
+    }
+}
+
+/// One pool session: searches each job at its share difficulty and submits what it finds.
+async fn work_for_pool(pool: &str, worker: &str, engine: &MiningEngine, shutdown: &Shutdown) -> Result<(), Error> {
+    let (reader, mut writer) = TcpStream::connect(pool).await?.into_split();
+    write_message(&mut writer, &json!({ "jsonrpc": "2.0", "method": "login", "params": [worker], "id": 0 })).await?;
+    // Read on a separate task so a half-read line is never dropped by the select below
+    let (received, mut incoming) = mpsc::unbounded_channel();
+    let reader = tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let message = read_message(&mut reader).await;
+            let done = !matches!(message, Ok(Some(_)));
+            if received.send(message).is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let (solutions, mut solved) = mpsc::unbounded_channel();
+    let mut cancel = Arc::new(AtomicBool::new(false));
+    let mut next_id = 1;
+    let result = loop {
+        tokio::select! {
+            Some(message) = incoming.recv() => {
+                let message = match message {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break Err(Error::new(ErrorKind::UnexpectedEof, "Pool closed the connection")),
+                    Err(e) => break Err(e),
+                };
+                if message.get("method").and_then(Value::as_str) == Some("job") {
+                    let job: Job = serde_json::from_value(message["params"].clone())?;
+                    job.template.pow()?;
+                    cancel.store(true, Ordering::Relaxed);
+                    cancel = Arc::new(AtomicBool::new(false));
+                    let (engine, cancel, solutions) = (engine.clone(), cancel.clone(), solutions.clone());
+                    tokio::task::spawn_blocking(move || {
+                        let mut template = job.template;
+                        template.difficulty = job.share_difficulty;
+                        if let Some(block) = engine.solve(&template, &cancel) {
+                            let _ = solutions.send((job.job_id, block));
+                        }
+                    });
+                } else if let Some(error) = message.get("error") {
+                    warn!(target: "mining", "Pool rejected request: {}", error["message"].as_str().unwrap_or("unknown error"));
+                } else if let Some(hash) = message["result"]["block"].as_str() {
+                    info!(target: "mining", "Share solved block {}", hash);
+                } else if message["result"]["accepted"] == true {
+                    debug!(target: "mining", "Share accepted ({:.0} H/s)", engine.hash_rate());
+                }
+            }
+            Some((job_id, block)) = solved.recv() => {
+                write_message(&mut writer, &json!({ "jsonrpc": "2.0", "method": "submit", "params": [job_id, block.nonce], "id": next_id })).await?;
+                next_id += 1;
+            }
+            _ = shutdown.triggered() => break Ok(()),
+        }
+    };
+    cancel.store(true, Ordering::Relaxed);
+    reader.abort();
+    result
+}
+
+/// Reads one line-delimited JSON message, skipping blank lines, or `None` at end of stream.
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>, Error> {
+    loop {
+        let mut line = Vec::new();
+        let read = (&mut *reader).take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with(b"\n") {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Message too long or truncated"));
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        return serde_json::from_slice(&line).map(Some).map_err(|e| Error::new(ErrorKind::InvalidData, e));
+    }
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<(), Error> {
+    let mut line = message.to_string();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await
+}
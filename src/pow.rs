@@ -0,0 +1,64 @@
+// src/pow.rs
+
+use sha2::{Digest, Sha256};
+
+/// Proof-of-work hash function. It turns a block's serialized header fields into the hash that
+/// identifies the block and is checked against the difficulty, and is chosen per network.
+pub trait PowHash: Send + Sync {
+    /// Name carried in block templates so remote miners use the same function.
+    fn name(&self) -> &'static str;
+    fn hash(&self, preimage: &[u8]) -> [u8; 32];
+}
+
+/// Single SHA-256, used by every network created before proof of work became pluggable.
+pub struct Sha256Pow;
+
+/// SHA-256 applied twice, as in Bitcoin.
+pub struct Sha256dPow;
+
+/// BLAKE3, a much cheaper hash that existing SHA-256 hardware can't run.
+pub struct Blake3Pow;
+
+impl PowHash for Sha256Pow {
+    fn name(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn hash(&self, preimage: &[u8]) -> [u8; 32] {
+        Sha256::digest(preimage).into()
+    }
+}
+
+impl PowHash for Sha256dPow {
+    fn name(&self) -> &'static str {
+        "sha256d"
+    }
+
+    fn hash(&self, preimage: &[u8]) -> [u8; 32] {
+        Sha256::digest(&Sha256::digest(preimage)).into()
+    }
+}
+
+impl PowHash for Blake3Pow {
+    fn name(&self) -> &'static str {
+        "blake3"
+    }
+
+    fn hash(&self, preimage: &[u8]) -> [u8; 32] {
+        blake3::hash(preimage).into()
+    }
+}
+
+pub static SHA256: Sha256Pow = Sha256Pow;
+pub static SHA256D: Sha256dPow = Sha256dPow;
+pub static BLAKE3: Blake3Pow = Blake3Pow;
+
+/// Looks up a proof-of-work function by the name it reports.
+pub fn by_name(name: &str) -> Option<&'static dyn PowHash> {
+    match name {
+        "sha256" => Some(&SHA256),
+        "sha256d" => Some(&SHA256D),
+        "blake3" => Some(&BLAKE3),
+        _ => None,
+    }
+}
@@ -0,0 +1,332 @@
+// src/pruning.rs
+
+use std::collections::{HashSet, VecDeque};
+use std::io::{Error, ErrorKind};
+use serde::{Serialize, Deserialize};
+use log::{info, warn};
+use crate::block::{Block, BlockHeader};
+use crate::blockdag::BlockDAG;
+use crate::ghostdag::GhostdagData;
+use crate::ledger::LedgerState;
+
+/// Pruning point proofs a fresh node asked its peers for, and the heaviest valid one so far.
+#[derive(Default)]
+pub(crate) struct ProofRequests {
+    pending: HashSet<String>,
+    best: Option<(u64, PruningPointProof)>,
+}
+
+/// Everything a new node needs to start from a pruning point instead of genesis.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PruningPointProof {
+    pub network: String,
+    pub pruning_point: Block,
+    pub pruning_point_ghostdag: GhostdagData,
+    /// Ledger state of the pruning point's past.
+    pub snapshot: LedgerState,
+    /// Blocks stored outside the pruning point's past, parents before children.
+    pub chain: Vec<Block>,
+    /// Headers of the pruning point's past, parents before children, starting at genesis.
+    #[serde(default)]
+    pub headers: Vec<BlockHeader>,
+}
+
+impl BlockDAG {
+    /// Moves the pruning point up the selected chain once a block is buried `pruning_depth`
+    /// blue score below the virtual, folding everything below it into the ledger snapshot.
+    pub fn update_pruning_point(&mut self) {
+        let depth = self.params().pruning_depth;
+        let mut candidate = match self.virtual_selected_parent() {
+            Some(tip) => tip,
+            None => return,
+        };
+        let tip_score = self.blue_score(&candidate);
+        if tip_score < depth {
+            return;
+        }
+        while self.blue_score(&candidate) > tip_score - depth && candidate != self.pruning_point {
+            candidate = match self.ghostdag.get(&candidate).and_then(|data| data.selected_parent.clone()) {
+                Some(selected_parent) => selected_parent,
+                None => return,
+            };
+        }
+        if candidate == self.pruning_point || self.blue_score(&candidate) <= self.blue_score(&self.pruning_point) {
+            return;
+        }
+        if !self.is_dag_ancestor_of(&self.pruning_point, &candidate) {
+            warn!(target: "consensus", "Pruning point candidate {} is not in the future of pruning point {}", candidate, self.pruning_point);
+            return;
+        }
+        self.prune_below(&candidate);
+    }
+
+    fn prune_below(&mut self, pruning_point: &str) {
+        // Bodies still stored in the past of the new pruning point
+        let mut below = vec![];
+        let mut visited = HashSet::new();
+        let mut queue: VecDeque<String> = self.parents_of(pruning_point).into();
+        while let Some(hash) = queue.pop_front() {
+            if !self.blocks.contains_key(&hash) || !visited.insert(hash.clone()) {
+                continue;
+            }
+            queue.extend(self.parents_of(&hash));
+            below.push(hash);
+        }
+        below.sort_by_key(|hash| (self.blue_score(hash), hash.clone()));
+        // The snapshot is the new pruning point's past, resolved the way its block saw it
+        self.pruning_snapshot = self.ledger_after(&self.ghostdag[pruning_point]);
+
+        for hash in &below {
+            let block = self.blocks.remove(hash).expect("block body present");
+            if let Some(index) = self.tx_index.as_mut() {
+                index.remove_block(&block);
+            }
+            if !self.prune_headers {
+                self.pruned_headers.insert(hash.clone(), block.header());
+            }
+            if let Some(data) = self.ghostdag.get_mut(hash) {
+                data.mergeset.clear();
+                data.mergeset_reds.clear();
+                data.blues_anticone_sizes.clear();
+            }
+        }
+        self.pruned_block_count += below.len() as u64;
+        self.newly_pruned.extend(below.iter().cloned());
+        self.pruning_point = pruning_point.to_string();
+        info!(target: "consensus", "Pruning point moved to {}, pruned {} blocks", pruning_point, below.len());
+    }
+
+    /// Builds the proof served to peers that want to sync from our pruning point; `None` if
+    /// the headers of its past were dropped.
+    pub fn pruning_point_proof(&self) -> Option<PruningPointProof> {
+        let pruning_point = self.blocks.get(&self.pruning_point)?.clone();
+        let headers = self.past_headers(&self.pruning_point)?;
+        // A block's blue score exceeds that of each of its parents
+        let mut chain: Vec<Block> = self.blocks.values()
+            .filter(|block| block.hash != self.pruning_point)
+            .cloned()
+            .collect();
+        chain.sort_by_key(|block| (self.blue_score(&block.hash), block.hash.clone()));
+        Some(PruningPointProof {
+            network: self.network.clone(),
+            pruning_point_ghostdag: self.ghostdag.get(&self.pruning_point).cloned().unwrap_or_default(),
+            pruning_point,
+            snapshot: self.pruning_snapshot.clone(),
+            chain,
+            headers,
+        })
+    }
+
+    /// Headers of the whole past of `hash`, parents before children; `None` if some are missing.
+    pub fn past_headers(&self, hash: &str) -> Option<Vec<BlockHeader>> {
+        let mut past = HashSet::new();
+        let mut headers = vec![];
+        let mut queue: VecDeque<String> = self.parents_of(hash).into();
+        while let Some(hash) = queue.pop_front() {
+            if !past.insert(hash.clone()) {
+                continue;
+            }
+            let header = match self.blocks.get(&hash) {
+                Some(block) => block.header(),
+                None => self.pruned_headers.get(&hash)?.clone(),
+            };
+            queue.extend(self.parents_of(&hash));
+            headers.push(header);
+        }
+        // A block's blue score exceeds that of each of its parents
+        headers.sort_by_key(|header| (self.blue_score(&header.hash), header.hash.clone()));
+        Some(headers)
+    }
+
+    /// Checks a proof against this DAG's network: the pruning point's past must lead back to
+    /// genesis with every header hashing to itself and carrying the difficulty its own past
+    /// requires, the snapshot must match the pruning point's state commitment, its GHOSTDAG data
+    /// must follow from its past, and the blocks above it must pass full validation. Returns the
+    /// blue score the proof's blocks reach, by which proofs from several peers are compared.
+    pub fn verify_pruning_point_proof(&self, proof: &PruningPointProof) -> Result<u64, Error> {
+        let fail = |reason: String| Err(Error::new(ErrorKind::InvalidData, format!("Invalid pruning point proof: {}", reason)));
+        let params = self.params();
+        if proof.network != self.network {
+            return fail("wrong network".to_string());
+        }
+        let genesis = params.genesis_block();
+        let starts_at_genesis = match proof.headers.first() {
+            Some(header) => *header == genesis.header(),
+            None => proof.pruning_point.hash == genesis.hash,
+        };
+        if !starts_at_genesis {
+            return fail("past does not start at genesis".to_string());
+        }
+        let mut past = HashSet::new();
+        for header in &proof.headers {
+            let parents: Vec<&String> = header.previous_hashes.iter().filter(|parent| *parent != "0").collect();
+            if header.hash != genesis.hash && (parents.is_empty() || parents.iter().any(|parent| !past.contains(parent.as_str()))) {
+                return fail("header is not linked to genesis".to_string());
+            }
+            if !past.insert(header.hash.as_str()) {
+                return fail("duplicate header".to_string());
+            }
+        }
+        let pruning_point = &proof.pruning_point;
+        if pruning_point.hash != genesis.hash && pruning_point.previous_hashes.iter().any(|parent| !past.contains(parent.as_str())) {
+            return fail("pruning point is not linked to its past".to_string());
+        }
+
+        // Difficulty is recomputed along the headers rather than taken on trust
+        let mut past_dag = BlockDAG::new(params);
+        past_dag.load_proof_past(proof);
+        for header in proof.headers.iter().skip(1) {
+            if header.compute_hash(params.pow).as_ref() != Some(&header.hash) {
+                return fail(format!("header {} does not match its contents", header.hash));
+            }
+            let required = past_dag.required_difficulty(&header.previous_hashes);
+            if header.difficulty != required || !params.allows_difficulty(header.difficulty) || !Block::meets_difficulty(&header.hash, header.difficulty) {
+                return fail(format!("header {} has invalid proof of work", header.hash));
+            }
+        }
+        if pruning_point.hash != genesis.hash {
+            let required = past_dag.required_difficulty(&pruning_point.previous_hashes);
+            if pruning_point.difficulty != required || !params.allows_difficulty(pruning_point.difficulty) || !self.has_valid_pow(pruning_point) {
+                return fail("pruning point has invalid proof of work".to_string());
+            }
+        }
+        // Genesis has an empty past; any other pruning point must commit to its snapshot
+        if pruning_point.hash == genesis.hash {
+            if proof.snapshot != LedgerState::default() {
+                return fail("snapshot of genesis is not empty".to_string());
+            }
+        } else if pruning_point.state_commitment.is_empty() {
+            return fail("pruning point carries no state commitment".to_string());
+        } else if pruning_point.state_commitment != proof.snapshot.commitment() {
+            return fail("snapshot does not match the pruning point's state commitment".to_string());
+        }
+        if past_dag.ghostdag[&pruning_point.hash].blue_score != proof.pruning_point_ghostdag.blue_score {
+            return fail("pruning point blue score does not follow from its past".to_string());
+        }
+
+        // Blocks above the pruning point are checked like any block a peer sends
+        past_dag.start_from_pruning_point(proof);
+        for block in &proof.chain {
+            let hash = block.hash.clone();
+            if let Err(e) = past_dag.submit_block(block.clone()) {
+                return fail(format!("block {} is invalid: {}", hash, e));
+            }
+        }
+        Ok(past_dag.compute_ghostdag(&past_dag.tips).blue_score)
+    }
+
+    /// Makes the proof's pruning point and the headers of its past the whole DAG, with GHOSTDAG
+    /// data computed from them.
+    fn load_proof_past(&mut self, proof: &PruningPointProof) {
+        let pruning_point = proof.pruning_point.hash.clone();
+        self.blocks.clear();
+        self.ghostdag.clear();
+        self.pruned_headers = proof.headers.iter().map(|header| (header.hash.clone(), header.clone())).collect();
+        self.blocks.insert(pruning_point.clone(), proof.pruning_point.clone());
+        self.pruning_point = pruning_point.clone();
+        self.tips = vec![pruning_point];
+        self.fill_ghostdag();
+    }
+
+    /// Sets up a DAG whose past was loaded from `proof` to continue from its pruning point.
+    fn start_from_pruning_point(&mut self, proof: &PruningPointProof) {
+        self.pruned_block_count = proof.headers.len() as u64;
+        self.pruning_snapshot = proof.snapshot.clone();
+        self.block_times.clear();
+        self.recount_from_pruning_point();
+    }
+
+    /// Block count, reward, supply and difficulty of a DAG bootstrapped from a pruning point.
+    fn recount_from_pruning_point(&mut self) {
+        self.block_count = self.pruned_block_count + self.blocks.len() as u64;
+        let halvings = self.block_count / self.params().halving_interval;
+        self.current_block_reward = self.params().initial_block_reward.checked_shr(halvings.min(64) as u32).unwrap_or(0);
+        self.current_supply = self.virtual_ledger().supply;
+        self.difficulty = self.required_difficulty(&self.tips);
+    }
+
+    /// Replaces a fresh DAG with the state described by a verified pruning point proof.
+    pub fn bootstrap_from_proof(&mut self, proof: PruningPointProof) -> Result<(), Error> {
+        if self.blocks.len() > 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Only a fresh DAG can be bootstrapped from a pruning point"));
+        }
+        self.verify_pruning_point_proof(&proof)?;
+        self.bootstrap_verified(proof)
+    }
+
+    fn bootstrap_verified(&mut self, proof: PruningPointProof) -> Result<(), Error> {
+        self.load_proof_past(&proof);
+        for header in &proof.headers {
+            if let Some(data) = self.ghostdag.get_mut(&header.hash) {
+                data.mergeset.clear();
+                data.mergeset_reds.clear();
+                data.blues_anticone_sizes.clear();
+            }
+        }
+        if self.prune_headers {
+            self.pruned_headers.clear();
+        }
+        self.start_from_pruning_point(&proof);
+
+        for block in proof.chain {
+            self.block_times.push(block.timestamp);
+            self.insert_block(block);
+        }
+        self.recount_from_pruning_point();
+        self.rebuild_tx_index();
+        self.rewrite_store()
+    }
+
+    /// Notes that `peer` was asked for its pruning point proof; only requested proofs are
+    /// considered.
+    pub fn request_pruning_point_proof(&mut self, peer: &str) {
+        self.proof_requests.pending.insert(peer.to_string());
+    }
+
+    /// Takes in a proof `peer` sent. Once every asked peer has answered, a fresh DAG starts from
+    /// the verified proof reaching the highest blue score; returns its pruning point if so.
+    pub fn receive_pruning_point_proof(&mut self, peer: &str, proof: PruningPointProof) -> Result<Option<String>, Error> {
+        if !self.proof_requests.pending.remove(peer) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Pruning point proof from {} was not requested", peer)));
+        }
+        // Verified now, so an invalid proof is reported against the peer that sent it
+        let verified = self.verify_pruning_point_proof(&proof);
+        if let Ok(blue_score) = verified {
+            let heavier = self.proof_requests.best.as_ref().is_none_or(|(best, _)| blue_score > *best);
+            if heavier {
+                self.proof_requests.best = Some((blue_score, proof));
+            }
+        }
+        let bootstrapped = self.finish_pruning_point_proof_requests(false)?;
+        verified.map(|_| bootstrapped)
+    }
+
+    /// Gives up on the proof of a peer that went away; see [`receive_pruning_point_proof`](Self::receive_pruning_point_proof).
+    pub fn abandon_pruning_point_proof(&mut self, peer: &str) -> Result<Option<String>, Error> {
+        if !self.proof_requests.pending.remove(peer) {
+            return Ok(None);
+        }
+        self.finish_pruning_point_proof_requests(false)
+    }
+
+    /// Bootstraps from the best proof received so far once no asked peer is left to answer, or
+    /// right away if `force`d because the others are taking too long.
+    pub fn finish_pruning_point_proof_requests(&mut self, force: bool) -> Result<Option<String>, Error> {
+        if !force && !self.proof_requests.pending.is_empty() {
+            return Ok(None);
+        }
+        let (blue_score, proof) = match self.proof_requests.best.take() {
+            Some(best) => best,
+            None => return Ok(None),
+        };
+        self.proof_requests.pending.clear();
+        // Only a fresh node starts over from a peer's pruning point
+        if self.blocks.len() > 1 || proof.pruning_point.hash == self.pruning_point {
+            return Ok(None);
+        }
+        let pruning_point = proof.pruning_point.hash.clone();
+        self.bootstrap_verified(proof)?;
+        info!(target: "consensus", "Started from pruning point {}, the heaviest proof received (blue score {})", pruning_point, blue_score);
+        Ok(Some(pruning_point))
+    }
+}
@@ -0,0 +1,199 @@
+// src/reachability.rs
+
+use std::collections::{HashMap, HashSet};
+
+/// Key of the virtual root every selected-parent tree hangs from, matching genesis' parent hash.
+const ROOT: &str = "0";
+
+/// Share of a parent's free interval kept for later siblings when a child is added.
+const SIBLING_RESERVE_DIVISOR: u64 = 8;
+
+/// Inclusive range of tree positions owned by a block and its selected-parent subtree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Interval {
+    fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ReachabilityNode {
+    interval: Interval,
+    parent: String,
+    children: Vec<String>,
+    /// Blocks that merged this one, ordered by interval; their intervals never overlap.
+    future_covering_set: Vec<String>,
+}
+
+/// Answers "is A in the past of B" without walking the DAG.
+///
+/// Every block gets an interval nested in its selected parent's, so tree ancestry is an interval
+/// check. A block outside B's selected chain is in B's past exactly when some block that merged
+/// it is a tree ancestor of B; those blocks are kept per block in a future covering set and found
+/// with a binary search. When a parent runs out of room, the nearest ancestor with enough space
+/// has its subtree's intervals reallocated.
+#[derive(Debug, Clone)]
+pub struct ReachabilityIndex {
+    nodes: HashMap<String, ReachabilityNode>,
+}
+
+impl Default for ReachabilityIndex {
+    fn default() -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(ROOT.to_string(), ReachabilityNode {
+            interval: Interval { start: 1, end: u64::MAX - 1 },
+            parent: String::new(),
+            children: vec![],
+            future_covering_set: vec![],
+        });
+        ReachabilityIndex { nodes }
+    }
+}
+
+impl ReachabilityIndex {
+    pub fn contains(&self, hash: &str) -> bool {
+        hash != ROOT && self.nodes.contains_key(hash)
+    }
+
+    /// Interval of a block's selected-parent subtree; a reindex may move it.
+    pub fn interval(&self, hash: &str) -> Option<Interval> {
+        self.nodes.get(hash).filter(|_| hash != ROOT).map(|node| node.interval)
+    }
+
+    /// Indexes a block given its GHOSTDAG selected parent and mergeset. Blocks whose selected
+    /// parent isn't indexed (genesis, a pruning point) become roots.
+    pub fn add_block(&mut self, hash: &str, selected_parent: Option<&str>, mergeset: &[String]) {
+        if self.nodes.contains_key(hash) {
+            return;
+        }
+        let parent = match selected_parent {
+            Some(parent) if self.nodes.contains_key(parent) => parent.to_string(),
+            _ => ROOT.to_string(),
+        };
+
+        let free = self.free_interval(&parent);
+        let interval = match free {
+            Some(free) => Interval { start: free.start, end: free.end - free.size() / SIBLING_RESERVE_DIVISOR },
+            None => Interval { start: 0, end: 0 }, // assigned by the reindex below
+        };
+        self.nodes.insert(hash.to_string(), ReachabilityNode {
+            interval,
+            parent: parent.clone(),
+            children: vec![],
+            future_covering_set: vec![],
+        });
+        self.nodes.get_mut(&parent).expect("parent is indexed").children.push(hash.to_string());
+        if free.is_none() {
+            self.reindex_towards(hash);
+        }
+
+        for merged in mergeset {
+            self.add_to_future_covering_set(merged, hash);
+        }
+    }
+
+    /// Whether `ancestor` is `descendant` or on its selected chain.
+    pub fn is_tree_ancestor_of(&self, ancestor: &str, descendant: &str) -> bool {
+        match (self.nodes.get(ancestor), self.nodes.get(descendant)) {
+            (Some(ancestor), Some(descendant)) => ancestor.interval.contains(&descendant.interval),
+            _ => false,
+        }
+    }
+
+    /// Whether `ancestor` is `descendant` or in its past.
+    pub fn is_dag_ancestor_of(&self, ancestor: &str, descendant: &str) -> bool {
+        if self.is_tree_ancestor_of(ancestor, descendant) {
+            return true;
+        }
+        let (node, target) = match (self.nodes.get(ancestor), self.nodes.get(descendant)) {
+            (Some(node), Some(target)) => (node, target.interval),
+            _ => return false,
+        };
+        // The only covering block that can contain `descendant` is the last one starting before it
+        let position = node.future_covering_set.partition_point(|hash| self.nodes[hash].interval.start <= target.start);
+        position > 0 && self.nodes[&node.future_covering_set[position - 1]].interval.contains(&target)
+    }
+
+    /// Unallocated tail of a node's interval, after its own slot and its children's intervals.
+    fn free_interval(&self, hash: &str) -> Option<Interval> {
+        let node = &self.nodes[hash];
+        let start = match node.children.last() {
+            Some(child) => self.nodes[child].interval.end + 1,
+            None => node.interval.start + 1,
+        };
+        if start > node.interval.end {
+            return None;
+        }
+        Some(Interval { start, end: node.interval.end })
+    }
+
+    fn add_to_future_covering_set(&mut self, hash: &str, merging: &str) {
+        let start = match self.nodes.get(merging) {
+            Some(node) => node.interval.start,
+            None => return,
+        };
+        let position = match self.nodes.get(hash) {
+            Some(node) => node.future_covering_set.partition_point(|other| self.nodes[other].interval.start < start),
+            None => return,
+        };
+        self.nodes.get_mut(hash).expect("block is indexed").future_covering_set.insert(position, merging.to_string());
+    }
+
+    fn subtree_sizes(&self, root: &str, sizes: &mut HashMap<String, u64>) {
+        let mut order = vec![root.to_string()];
+        let mut i = 0;
+        while i < order.len() {
+            order.extend(self.nodes[&order[i]].children.iter().filter(|child| !sizes.contains_key(*child)).cloned());
+            i += 1;
+        }
+        for hash in order.iter().rev() {
+            let size = 1 + self.nodes[hash].children.iter().map(|child| sizes[child]).sum::<u64>();
+            sizes.insert(hash.clone(), size);
+        }
+    }
+
+    /// Reallocates intervals below the nearest ancestor of a freshly added block that has room
+    /// for twice its subtree, handing all spare space down the path to the new block.
+    fn reindex_towards(&mut self, hash: &str) {
+        let mut path = HashSet::from([hash.to_string()]);
+        let mut root = self.nodes[hash].parent.clone();
+        let mut sizes = HashMap::new();
+        loop {
+            // Subtrees already measured are reused as the candidate root moves up
+            self.subtree_sizes(&root, &mut sizes);
+            if root == ROOT || self.nodes[&root].interval.size() >= 2 * sizes[&root] {
+                break;
+            }
+            path.insert(root.clone());
+            root = self.nodes[&root].parent.clone();
+        }
+
+        // Children off the path get twice their subtree size, the child on the path everything else
+        let mut stack = vec![root];
+        while let Some(hash) = stack.pop() {
+            let interval = self.nodes[&hash].interval;
+            let children = self.nodes[&hash].children.clone();
+            let mut start = interval.start + 1;
+            for (i, child) in children.iter().enumerate() {
+                let size = if path.contains(child) {
+                    let reserved_after: u64 = children[i + 1..].iter().map(|other| 2 * sizes[other]).sum();
+                    interval.end + 1 - start - reserved_after
+                } else {
+                    2 * sizes[child]
+                };
+                self.nodes.get_mut(child).expect("child is indexed").interval = Interval { start, end: start + size - 1 };
+                start += size;
+                stack.push(child.clone());
+            }
+        }
+    }
+}
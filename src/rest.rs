@@ -0,0 +1,94 @@
+// src/rest.rs
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::http::{HttpRequest, HttpResponse};
+use crate::rpc::RpcContext;
+
+/// Path prefix the explorer API is served under, next to JSON-RPC.
+pub const API_PREFIX: &str = "/api/";
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Answers read-only explorer requests:
+///
+/// - `GET /api/blocks/{hash}`, `/api/blocks/{hash}/parents`, `/api/blocks/{hash}/children`
+/// - `GET /api/transactions/{txid}`
+/// - `GET /api/addresses/{address}`, `/api/addresses/{address}/transactions`
+/// - `GET /api/tips`, `/api/supply`, `/api/difficulty`
+///
+/// Lists take `offset` and `limit` query parameters.
+pub fn handle_rest(context: &RpcContext, request: &HttpRequest) -> HttpResponse {
+    if request.method != "GET" {
+        return error(405, "The explorer API is read-only; use GET");
+    }
+    let segments: Vec<&str> = request.path[API_PREFIX.len()..].split('/').filter(|segment| !segment.is_empty()).collect();
+    let page = match Page::from_request(request) {
+        Ok(page) => page,
+        Err(message) => return error(400, &message),
+    };
+    let dag = context.dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    match segments.as_slice() {
+        ["blocks", hash] => {
+            let ghostdag = dag.ghostdag.get(*hash);
+            let mut body = match (dag.blocks.get(*hash), dag.pruned_headers.get(*hash)) {
+                (Some(block), _) => json!({ "block": block, "pruned": false }),
+                (None, Some(header)) => json!({ "header": header, "pruned": true }),
+                (None, None) => return error(404, &format!("Block {} not found", hash)),
+            };
+            body["ghostdag"] = json!(ghostdag);
+            body["chainBlock"] = json!(dag.is_chain_block(hash));
+            body["children"] = json!(dag.children_of(hash));
+            HttpResponse::json(200, &body)
+        }
+        ["blocks", hash, "parents"] if dag.has_block(hash) => page.respond(dag.parents_of(hash)),
+        ["blocks", hash, "children"] if dag.has_block(hash) => page.respond(dag.children_of(hash)),
+        ["blocks", hash, _] => error(404, &format!("Block {} not found", hash)),
+        ["transactions", txid] => match dag.find_transaction(txid) {
+            Some(entry) => HttpResponse::json(200, &json!(entry)),
+            None => error(404, &format!("Transaction {} not found", txid)),
+        },
+        ["addresses", address] => {
+            let balance = dag.balance(address);
+            HttpResponse::json(200, &json!({ "address": address, "balance": balance.total, "spendable": balance.spendable, "immature": balance.immature }))
+        }
+        ["addresses", address, "transactions"] => page.respond(dag.address_history(address)),
+        ["tips"] => HttpResponse::json(200, &json!({ "tips": dag.tips, "virtualSelectedParent": dag.virtual_selected_parent() })),
+        ["supply"] => HttpResponse::json(200, &json!({
+            "currentSupply": dag.current_supply,
+            "totalSupply": dag.params().total_supply,
+            "blockReward": dag.current_block_reward,
+            "blockCount": dag.known_block_count(),
+        })),
+        ["difficulty"] => page.respond(dag.difficulty_history()),
+        _ => error(404, &format!("No such endpoint: {}", request.path)),
+    }
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    HttpResponse::json(status, &json!({ "error": message }))
+}
+
+struct Page {
+    offset: usize,
+    limit: usize,
+}
+
+impl Page {
+    fn from_request(request: &HttpRequest) -> Result<Page, String> {
+        let parse = |name: &str, default: usize| match request.query_param(name) {
+            Some(value) => value.parse::<usize>().map_err(|_| format!("Invalid {}: {}", name, value)),
+            None => Ok(default),
+        };
+        Ok(Page { offset: parse("offset", 0)?, limit: parse("limit", DEFAULT_PAGE_SIZE)?.min(MAX_PAGE_SIZE) })
+    }
+
+    fn respond<T: Serialize>(&self, items: Vec<T>) -> HttpResponse {
+        let total = items.len();
+        let items: Vec<T> = items.into_iter().skip(self.offset).take(self.limit).collect();
+        let body: Value = json!({ "items": items, "offset": self.offset, "limit": self.limit, "total": total });
+        HttpResponse::json(200, &body)
+    }
+}
@@ -0,0 +1,276 @@
+// src/rpc.rs
+
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use log::{error, info};
+use tokio::net::TcpListener;
+use crate::block::Block;
+use crate::blockdag::BlockDAG;
+use crate::http::{self, HttpRequest, HttpResponse};
+use crate::logging;
+use crate::metrics::{handle_metrics, Metrics, METRICS_PATH};
+use crate::rest::{handle_rest, API_PREFIX};
+use crate::shutdown::Shutdown;
+use crate::transaction::Transaction;
+use crate::transport::Peers;
+use crate::ws::websocket_upgrade;
+
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server-defined errors: the request was well formed but the node refused it.
+pub(crate) const REJECTED: i64 = -32000;
+const NOT_FOUND: i64 = -32001;
+/// The node failed while handling an otherwise valid request.
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Node state the RPC methods operate on.
+#[derive(Clone)]
+pub struct RpcContext {
+    pub dag: Arc<Mutex<BlockDAG>>,
+    pub peers: Peers,
+    pub shutdown: Shutdown,
+    pub metrics: Arc<Metrics>,
+}
+
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError { code, message: message.into() }
+    }
+}
+
+/// Calls a node's JSON-RPC methods over HTTP.
+#[derive(Debug)]
+pub struct RpcClient {
+    url: String,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> RpcClient {
+        RpcClient { url: url.into(), next_id: AtomicU64::new(1) }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Calls `method` and returns its result; errors the node answers with become `Other` errors.
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, Error> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id });
+        let (status, mut response) = http::post_json(&self.url, &request).await?;
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("Unknown error");
+            return Err(Error::other(format!("{} failed: {}", method, message)));
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(Error::new(ErrorKind::InvalidData, format!("{} failed: HTTP {} without a result", method, status))),
+        }
+    }
+}
+
+/// Serves JSON-RPC 2.0 over HTTP POST on `listen`, over WebSocket with event subscriptions
+/// at `/ws`, the read-only explorer API under `/api/` and Prometheus metrics at `/metrics`.
+pub async fn start_rpc_server(listen: String, context: RpcContext) {
+    match TcpListener::bind(&listen).await {
+        Ok(listener) => serve_rpc(listener, context).await,
+        Err(e) => error!(target: "rpc", "Failed to start JSON-RPC server on {}: {}", listen, e),
+    }
+}
+
+/// Like [`start_rpc_server`], on a socket the caller has already bound.
+pub async fn serve_rpc(listener: TcpListener, context: RpcContext) {
+    let upgrade = websocket_upgrade(context.clone());
+    let shutdown = context.shutdown.clone();
+    let server = http::serve_listener(listener, "JSON-RPC", move |request| {
+        let context = context.clone();
+        async move {
+            if request.path.starts_with(API_PREFIX) {
+                handle_rest(&context, &request)
+            } else if request.path == METRICS_PATH {
+                handle_metrics(&context, &request)
+            } else {
+                handle_http(&context, request)
+            }
+        }
+    }, Some(upgrade));
+    // Stop accepting connections once the node shuts down
+    tokio::select! {
+        _ = server => {}
+        _ = shutdown.triggered() => {}
+    }
+}
+
+pub fn handle_http(context: &RpcContext, request: HttpRequest) -> HttpResponse {
+    if request.method != "POST" {
+        return HttpResponse::text(405, "JSON-RPC requests must be POSTed");
+    }
+    let body: Value = match serde_json::from_slice(&request.body) {
+        Ok(body) => body,
+        Err(e) => return HttpResponse::json(200, &error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+    };
+    match body {
+        Value::Array(calls) if calls.is_empty() => {
+            HttpResponse::json(200, &error_response(Value::Null, RpcError::new(INVALID_REQUEST, "Empty batch")))
+        }
+        Value::Array(calls) => {
+            let responses: Vec<Value> = calls.into_iter().filter_map(|call| handle_call(context, call)).collect();
+            if responses.is_empty() {
+                HttpResponse::no_content()
+            } else {
+                HttpResponse::json(200, &Value::Array(responses))
+            }
+        }
+        call => match handle_call(context, call) {
+            Some(response) => HttpResponse::json(200, &response),
+            None => HttpResponse::no_content(),
+        },
+    }
+}
+
+/// Runs one call; notifications (no `id`) get no response.
+pub fn handle_call(context: &RpcContext, call: Value) -> Option<Value> {
+    let id = call.get("id").cloned();
+    let method = match (call.get("jsonrpc").and_then(Value::as_str), call.get("method").and_then(Value::as_str)) {
+        (Some("2.0"), Some(method)) => method.to_string(),
+        _ => return Some(error_response(id.unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, "Not a JSON-RPC 2.0 request"))),
+    };
+    let params = call.get("params").cloned().unwrap_or(Value::Null);
+    let result = dispatch(context, &method, &params);
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+pub(crate) fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "error": { "code": error.code, "message": error.message }, "id": id })
+}
+
+pub fn dispatch(context: &RpcContext, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "getBlock" => get_block(context, params),
+        "getBlockDagInfo" => Ok(get_block_dag_info(context)),
+        "getTips" => Ok(json!(lock_dag(context).tips)),
+        "getBalance" => {
+            let address: String = required(params, 0, "address")?;
+            let dag = lock_dag(context);
+            let balance = dag.balance(&address);
+            Ok(json!({ "address": address, "balance": balance.total, "spendable": balance.spendable, "immature": balance.immature, "nextNonce": dag.next_nonce(&address) }))
+        }
+        "submitTransaction" => submit_transaction(context, params),
+        "getMempool" => Ok(json!(lock_dag(context).pending_transactions)),
+        "getPeerInfo" => {
+            let peers = context.peers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            Ok(json!(peers.values().collect::<Vec<_>>()))
+        }
+        "getBlockTemplate" => {
+            let mining_address: String = required(params, 0, "miningAddress")?;
+            lock_dag(context).block_template(&mining_address)
+                .map(|template| json!(template))
+                .map_err(|e| RpcError::new(REJECTED, e.to_string()))
+        }
+        "submitBlock" => submit_block(context, params),
+        "stop" => {
+            context.shutdown.trigger("stop requested over RPC");
+            Ok(json!("Node stopping"))
+        }
+        "getLogLevel" => Ok(json!({ "filter": logging::filter() })),
+        "setLogLevel" => {
+            let filter: String = required(params, 0, "filter")?;
+            logging::set_filter(&filter)
+                .map(|filter| json!({ "filter": filter }))
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    }
+}
+
+fn lock_dag(context: &RpcContext) -> MutexGuard<'_, BlockDAG> {
+    context.dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reads a parameter given either by position or by name.
+pub(crate) fn optional<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<Option<T>, RpcError> {
+    let value = match params {
+        Value::Array(values) => values.get(index),
+        Value::Object(fields) => fields.get(name),
+        Value::Null => None,
+        _ => return Err(RpcError::new(INVALID_PARAMS, "Params must be an array or an object")),
+    };
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid {}: {}", name, e))),
+    }
+}
+
+pub(crate) fn required<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<T, RpcError> {
+    optional(params, index, name)?.ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Missing parameter {}", name)))
+}
+
+fn get_block(context: &RpcContext, params: &Value) -> Result<Value, RpcError> {
+    let hash: String = required(params, 0, "hash")?;
+    let dag = lock_dag(context);
+    let ghostdag = dag.ghostdag.get(&hash).cloned();
+    if let Some(block) = dag.blocks.get(&hash) {
+        return Ok(json!({ "block": block, "ghostdag": ghostdag }));
+    }
+    if let Some(header) = dag.pruned_headers.get(&hash) {
+        return Ok(json!({ "header": header, "ghostdag": ghostdag, "pruned": true }));
+    }
+    Err(RpcError::new(NOT_FOUND, format!("Block {} not found", hash)))
+}
+
+fn get_block_dag_info(context: &RpcContext) -> Value {
+    let dag = lock_dag(context);
+    let virtual_selected_parent = dag.virtual_selected_parent();
+    json!({
+        "network": dag.network,
+        "blockCount": dag.known_block_count(),
+        "storedBlockCount": dag.blocks.len(),
+        "tipHashes": dag.tips,
+        "virtualSelectedParent": virtual_selected_parent,
+        "blueScore": virtual_selected_parent.as_deref().map(|hash| dag.blue_score(hash)).unwrap_or(0),
+        "difficulty": dag.difficulty,
+        "currentSupply": dag.current_supply,
+        "blockReward": dag.current_block_reward,
+        "pruningPoint": dag.pruning_point,
+        "finalityPoint": dag.finality_point(),
+        "mempoolSize": dag.pending_transactions.len(),
+    })
+}
+
+fn submit_transaction(context: &RpcContext, params: &Value) -> Result<Value, RpcError> {
+    let transaction: Transaction = required(params, 0, "transaction")?;
+    let txid = transaction.txid();
+    lock_dag(context).add_transaction(transaction).map_err(|e| RpcError::new(REJECTED, e.to_string()))?;
+    Ok(json!({ "txid": txid }))
+}
+
+fn submit_block(context: &RpcContext, params: &Value) -> Result<Value, RpcError> {
+    let block: Block = required(params, 0, "block")?;
+    let hash = block.hash.clone();
+    let mut dag = lock_dag(context);
+    dag.submit_block(block).map_err(|e| RpcError::new(REJECTED, e.to_string()))?;
+    if let Err(e) = dag.persist_block(&hash) {
+        error!(target: "storage", "Failed to persist block {}: {}", hash, e);
+        return Err(RpcError::new(INTERNAL_ERROR, format!("Block {} was accepted but could not be stored: {}", hash, e)));
+    }
+    info!(target: "rpc", "Block {} submitted over RPC", hash);
+    Ok(json!({ "hash": hash }))
+}
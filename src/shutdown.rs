@@ -0,0 +1,102 @@
+// src/shutdown.rs
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::{info, warn};
+use tokio::sync::{mpsc, watch};
+
+/// Coordinates a clean stop. Components watch for the trigger, and work that must finish before
+/// the node flushes its state (peer sessions, mined blocks) holds a [`TaskGuard`] meanwhile.
+#[derive(Clone)]
+pub struct Shutdown {
+    trigger: Arc<watch::Sender<bool>>,
+    triggered: watch::Receiver<bool>,
+    /// Cloned into every guard; taken when draining starts so the channel closes once they drop.
+    tasks: Arc<Mutex<Option<mpsc::Sender<()>>>>,
+    drained: Arc<tokio::sync::Mutex<mpsc::Receiver<()>>>,
+}
+
+/// Keeps shutdown waiting while held.
+pub struct TaskGuard {
+    _sender: mpsc::Sender<()>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (trigger, triggered) = watch::channel(false);
+        let (tasks, drained) = mpsc::channel(1);
+        Shutdown {
+            trigger: Arc::new(trigger),
+            triggered,
+            tasks: Arc::new(Mutex::new(Some(tasks))),
+            drained: Arc::new(tokio::sync::Mutex::new(drained)),
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    /// Asks every component to stop; later calls are no-ops.
+    pub fn trigger(&self, reason: &str) {
+        if !self.trigger.send_replace(true) {
+            info!(target: "node", "Shutting down: {}", reason);
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Resolves once shutdown has been triggered.
+    pub async fn triggered(&self) {
+        let mut triggered = self.triggered.clone();
+        let _ = triggered.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Registers in-flight work, or returns `None` if the node is already draining.
+    pub fn guard(&self) -> Option<TaskGuard> {
+        let tasks = self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        tasks.as_ref().map(|sender| TaskGuard { _sender: sender.clone() })
+    }
+
+    /// Waits up to `timeout` for every guard to be dropped, returning whether they all were.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        let mut drained = self.drained.lock().await;
+        let finished = tokio::time::timeout(timeout, drained.recv()).await.is_ok();
+        if !finished {
+            warn!(target: "node", "Gave up waiting for in-flight work after {:?}", timeout);
+        }
+        finished
+    }
+}
+
+/// Triggers `shutdown` on Ctrl-C, or on SIGTERM where there is one.
+pub async fn listen_for_signals(shutdown: Shutdown) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                warn!(target: "node", "Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                shutdown.trigger("interrupted");
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => shutdown.trigger("interrupted"),
+            _ = terminate.recv() => shutdown.trigger("terminated"),
+            _ = shutdown.triggered() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => shutdown.trigger("interrupted"),
+        _ = shutdown.triggered() => {}
+    }
+}
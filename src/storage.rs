@@ -0,0 +1,360 @@
+// src/storage.rs
+
+use sha2::{Sha256, Digest};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use log::warn;
+
+const RECORD_HEADER_SIZE: usize = 4 + 32;
+
+/// Separate key namespaces inside a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Keyspace {
+    Blocks,
+    Headers,
+    Index,
+    Tips,
+    State,
+    Ghostdag,
+}
+
+impl Keyspace {
+    pub const ALL: [Keyspace; 6] = [Keyspace::Blocks, Keyspace::Headers, Keyspace::Index, Keyspace::Tips, Keyspace::State, Keyspace::Ghostdag];
+
+    fn tag(self) -> u8 {
+        match self {
+            Keyspace::Blocks => 1,
+            Keyspace::Headers => 2,
+            Keyspace::Index => 3,
+            Keyspace::Tips => 4,
+            Keyspace::State => 5,
+            Keyspace::Ghostdag => 6,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Keyspace> {
+        Keyspace::ALL.iter().copied().find(|keyspace| keyspace.tag() == tag)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum BatchOp {
+    Put(Keyspace, String, Vec<u8>),
+    Delete(Keyspace, String),
+}
+
+/// A set of writes that a store applies all-or-nothing.
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch { ops: vec![] }
+    }
+
+    pub fn put(&mut self, keyspace: Keyspace, key: &str, value: Vec<u8>) {
+        self.ops.push(BatchOp::Put(keyspace, key.to_string(), value));
+    }
+
+    pub fn delete(&mut self, keyspace: Keyspace, key: &str) {
+        self.ops.push(BatchOp::Delete(keyspace, key.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for op in &self.ops {
+            match op {
+                BatchOp::Put(keyspace, key, value) => {
+                    out.push(1);
+                    out.push(keyspace.tag());
+                    write_bytes(&mut out, key.as_bytes());
+                    write_bytes(&mut out, value);
+                }
+                BatchOp::Delete(keyspace, key) => {
+                    out.push(2);
+                    out.push(keyspace.tag());
+                    write_bytes(&mut out, key.as_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    fn decode(mut bytes: &[u8]) -> Result<WriteBatch, Error> {
+        let mut batch = WriteBatch::new();
+        while !bytes.is_empty() {
+            let op = read_u8(&mut bytes)?;
+            let keyspace = Keyspace::from_tag(read_u8(&mut bytes)?).ok_or_else(|| corrupt("Unknown keyspace"))?;
+            let key = String::from_utf8(read_bytes(&mut bytes)?).map_err(|_| corrupt("Invalid key"))?;
+            match op {
+                1 => batch.ops.push(BatchOp::Put(keyspace, key, read_bytes(&mut bytes)?)),
+                2 => batch.ops.push(BatchOp::Delete(keyspace, key)),
+                _ => return Err(corrupt("Unknown batch operation")),
+            }
+        }
+        Ok(batch)
+    }
+}
+
+/// Persistent key-value storage for the DAG.
+pub trait Storage: Send {
+    fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>, Error>;
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), Error>;
+
+    fn is_empty(&self) -> Result<bool, Error> {
+        for keyspace in Keyspace::ALL {
+            if !self.scan(keyspace)?.is_empty() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+type Tables = HashMap<Keyspace, BTreeMap<String, Vec<u8>>>;
+
+fn apply(tables: &mut Tables, batch: WriteBatch) {
+    for op in batch.ops {
+        match op {
+            BatchOp::Put(keyspace, key, value) => {
+                tables.entry(keyspace).or_default().insert(key, value);
+            }
+            BatchOp::Delete(keyspace, key) => {
+                if let Some(table) = tables.get_mut(&keyspace) {
+                    table.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Volatile store, handy for tests and throwaway nodes.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Tables,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl Storage for MemoryStore {
+    fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.tables.get(&keyspace).and_then(|table| table.get(key).cloned()))
+    }
+
+    fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        Ok(self.tables.get(&keyspace).map(|table| table.iter().map(|(k, v)| (k.clone(), v.clone())).collect()).unwrap_or_default())
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), Error> {
+        apply(&mut self.tables, batch);
+        Ok(())
+    }
+}
+
+/// Embedded store backed by an append-only log of write batches.
+///
+/// Each batch is written and fsynced as one record (length, SHA-256 checksum over length and
+/// payload, payload), so a crash can only lose the batch being written; a torn trailing record
+/// is discarded when the log is reopened, while a damaged record anywhere else fails the open
+/// instead of silently dropping the records after it. A write that fails is cut off the log
+/// again, so later records never land behind a partial one. The whole keyspace is kept in memory
+/// and rebuilt by replaying the log.
+pub struct LogStore {
+    path: PathBuf,
+    file: Option<File>,
+    tables: Tables,
+    log_bytes: u64,
+    /// Set when a failed write couldn't be undone; the log may end in a partial record.
+    poisoned: bool,
+}
+
+impl LogStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<LogStore, Error> {
+        let mut store = LogStore::replay(path.as_ref())?;
+        let file = OpenOptions::new().create(true).append(true).open(&store.path)?;
+        file.set_len(store.log_bytes)?; // drop a torn trailing record, if any
+        store.file = Some(file);
+        if store.log_bytes > 2 * store.live_bytes() && store.log_bytes > 1024 * 1024 {
+            store.compact()?;
+        }
+        Ok(store)
+    }
+
+    /// Opens the log without taking ownership of it, e.g. for wallet tools next to a running node.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<LogStore, Error> {
+        LogStore::replay(path.as_ref())
+    }
+
+    fn replay(path: &Path) -> Result<LogStore, Error> {
+        let mut store = LogStore { path: path.to_path_buf(), file: None, tables: HashMap::new(), log_bytes: 0, poisoned: false };
+        let log = match fs::read(path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(store),
+            Err(e) => return Err(e),
+        };
+        let mut offset = 0;
+        while offset < log.len() {
+            if let Some(end) = record_end(&log, offset) {
+                apply(&mut store.tables, WriteBatch::decode(&log[offset + RECORD_HEADER_SIZE..end])?);
+                offset = end;
+                continue;
+            }
+            // A crash can only tear the record being appended, so a damaged record is tolerated
+            // at the very end of the log and nowhere else
+            let declared_end = log.get(offset..offset + 4)
+                .map(|len| offset + RECORD_HEADER_SIZE + u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize);
+            let reaches_end = declared_end.is_none_or(|end| end >= log.len());
+            if !reaches_end || (offset + 1..log.len()).any(|start| record_end(&log, start).is_some()) {
+                return Err(corrupt(&format!("Integrity check failed: damaged record in {} at offset {}", path.display(), offset)));
+            }
+            break;
+        }
+        if offset < log.len() {
+            warn!(target: "storage", "Discarding {} bytes of incomplete data at the end of {}", log.len() - offset, path.display());
+        }
+        store.log_bytes = offset as u64;
+        Ok(store)
+    }
+
+    // Approximate size of the live entries once re-encoded.
+    fn live_bytes(&self) -> u64 {
+        self.tables.values().flat_map(|table| table.iter()).map(|(k, v)| (k.len() + v.len() + 10) as u64).sum()
+    }
+
+    /// Rewrites the log so it only holds live entries.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        let mut batch = WriteBatch::new();
+        for (keyspace, table) in &self.tables {
+            for (key, value) in table {
+                batch.put(*keyspace, key, value.clone());
+            }
+        }
+        let record = encode_record(&batch.encode());
+        write_atomic(&self.path, &record)?;
+        self.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        self.log_bytes = record.len() as u64;
+        Ok(())
+    }
+}
+
+impl Storage for LogStore {
+    fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.tables.get(&keyspace).and_then(|table| table.get(key).cloned()))
+    }
+
+    fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        Ok(self.tables.get(&keyspace).map(|table| table.iter().map(|(k, v)| (k.clone(), v.clone())).collect()).unwrap_or_default())
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        if self.poisoned {
+            return Err(Error::other(format!("{} is unusable after a failed write; reopen it", self.path.display())));
+        }
+        let file = self.file.as_mut().ok_or_else(|| Error::new(ErrorKind::PermissionDenied, "Store was opened read-only"))?;
+        let record = encode_record(&batch.encode());
+        if let Err(e) = file.write_all(&record).and_then(|()| file.sync_data()) {
+            // Drop whatever part of the record made it to disk
+            if let Err(truncate_error) = file.set_len(self.log_bytes).and_then(|()| file.sync_data()) {
+                warn!(target: "storage", "Failed to roll back a partial write to {}: {}", self.path.display(), truncate_error);
+                self.poisoned = true;
+            }
+            return Err(e);
+        }
+        self.log_bytes += record.len() as u64;
+        apply(&mut self.tables, batch);
+        Ok(())
+    }
+}
+
+/// Replaces `path` with `contents` so that readers see either the old or the new file, never a
+/// partial one: the data goes to a temporary sibling that is fsynced before being renamed over.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut tmp_name = path.file_name().ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no file name"))?.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(contents)?;
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    // Persist the rename itself
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        dir.sync_all()?;
+    }
+    Ok(())
+}
+
+/// SHA-256 over a record's length field and payload.
+fn checksum(len: &[u8], payload: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(len);
+    hasher.update(payload);
+    hasher.finalize().into()
+}
+
+fn encode_record(payload: &[u8]) -> Vec<u8> {
+    let len = (payload.len() as u32).to_be_bytes();
+    let mut record = len.to_vec();
+    record.extend_from_slice(&checksum(&len, payload));
+    record.extend_from_slice(payload);
+    record
+}
+
+/// End of the intact record starting at `offset`, if one does. Logs written before the length
+/// was checksummed carry SHA-256 over the payload alone.
+fn record_end(log: &[u8], offset: usize) -> Option<usize> {
+    let header = log.get(offset..offset + RECORD_HEADER_SIZE)?;
+    let end = offset + RECORD_HEADER_SIZE + u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let payload = log.get(offset + RECORD_HEADER_SIZE..end)?;
+    let intact = checksum(&header[..4], payload)[..] == header[4..] || Sha256::digest(payload)[..] == header[4..];
+    if intact { Some(end) } else { None }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn read_u8(bytes: &mut &[u8]) -> Result<u8, Error> {
+    let (first, rest) = bytes.split_first().ok_or_else(|| corrupt("Truncated batch"))?;
+    *bytes = rest;
+    Ok(*first)
+}
+
+fn read_bytes(bytes: &mut &[u8]) -> Result<Vec<u8>, Error> {
+    if bytes.len() < 4 {
+        return Err(corrupt("Truncated batch"));
+    }
+    let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    if bytes.len() < 4 + len {
+        return Err(corrupt("Truncated batch"));
+    }
+    let value = bytes[4..4 + len].to_vec();
+    *bytes = &bytes[4 + len..];
+    Ok(value)
+}
+
+fn corrupt(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
@@ -1,26 +1,67 @@
 // src/transaction.rs
 
+use std::fmt;
 use serde::{Serialize, Deserialize};
 use sha2::Digest;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub sender: String,
     pub receiver: String,
     pub amount: u64,
     pub fee: u64,
+    /// Position of the transaction among its sender's, starting at 1, so a signed transaction
+    /// can't be applied twice. 0 on coinbases and on transactions from before nonces.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
     pub signature: String,
 }
 
+fn is_zero(nonce: &u64) -> bool {
+    *nonce == 0
+}
+
+// Block hashes cover the Debug form of their transactions, so one without a nonce must print
+// exactly as before nonces existed
+impl fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Transaction");
+        debug.field("sender", &self.sender).field("receiver", &self.receiver).field("amount", &self.amount).field("fee", &self.fee);
+        if self.nonce != 0 {
+            debug.field("nonce", &self.nonce);
+        }
+        debug.field("signature", &self.signature).finish()
+    }
+}
+
 impl Transaction {
     pub fn new(sender: String, receiver: String, amount: u64, fee: u64, signature: String) -> Self {
-        Transaction { sender, receiver, amount, fee, signature }
+        Transaction { sender, receiver, amount, fee, nonce: 0, signature }
     }
 
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Message the sender signs; it leaves out the signature itself.
     pub fn calculate_hash(&self) -> String {
-        let data = format!("{}{}{}{}", self.sender, self.receiver, self.amount, self.fee);
+        let mut data = format!("{}{}{}{}", self.sender, self.receiver, self.amount, self.fee);
+        // The separator keeps the nonce from running into the fee digits
+        if self.nonce != 0 {
+            data.push(':');
+            data.push_str(&self.nonce.to_string());
+        }
         let mut hasher = sha2::Sha256::new();
         hasher.update(data);
         hex::encode(hasher.finalize())
     }
+
+    /// Identifies the signed transaction: the signing message hashed together with the signature.
+    pub fn txid(&self) -> String {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(self.calculate_hash());
+        hasher.update(&self.signature);
+        hex::encode(hasher.finalize())
+    }
 }
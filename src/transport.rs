@@ -0,0 +1,357 @@
+// src/transport.rs
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{PublicKey, Signature, Signer, Verifier};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use x25519_dalek::{PublicKey as EphemeralPublic, StaticSecret};
+use crate::messages::Message;
+use crate::metrics::Metrics;
+use crate::storage::write_atomic;
+use crate::wallet::Wallet;
+
+/// Largest frame accepted in an established session; blocks and proofs can be big.
+const MAX_FRAME_SIZE: usize = 32 * 1024 * 1024;
+/// Largest frame accepted from a peer that hasn't authenticated yet; handshake frames are tiny.
+const MAX_HANDSHAKE_FRAME_SIZE: usize = 1024;
+const HANDSHAKE_PROLOGUE: &[u8] = b"blockdag-handshake-v1";
+
+/// Whether peer sessions run in the clear or behind the authenticated handshake.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportMode {
+    Plain,
+    Encrypted,
+}
+
+/// What we know about a connected peer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerInfo {
+    pub address: String,
+    pub node_id: Option<String>,
+    pub encrypted: bool,
+}
+
+pub type Peers = Arc<Mutex<HashMap<String, PeerInfo>>>;
+
+/// Long-lived identity of a node, backed by the same ed25519 keys as a wallet.
+pub struct NodeIdentity {
+    wallet: Wallet,
+}
+
+impl NodeIdentity {
+    pub fn generate() -> Self {
+        NodeIdentity { wallet: Wallet::new() }
+    }
+
+    pub fn from_wallet(wallet: Wallet) -> Self {
+        NodeIdentity { wallet }
+    }
+
+    /// Loads the identity kept at `path`, creating and saving a new one on first start, so the
+    /// node id peers pin stays the same across restarts.
+    pub fn load_or_generate(path: &Path) -> Result<Self, Error> {
+        match fs::read(path) {
+            Ok(key) => Ok(NodeIdentity { wallet: serde_json::from_slice(&key)? }),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let identity = NodeIdentity::generate();
+                write_atomic(path, &serde_json::to_vec_pretty(&identity.wallet)?)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+                }
+                Ok(identity)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn node_id(&self) -> String {
+        self.wallet.get_address()
+    }
+
+    fn public_bytes(&self) -> [u8; 32] {
+        self.wallet.keypair.public.to_bytes()
+    }
+
+    fn sign(&self, transcript: &[u8]) -> [u8; 64] {
+        self.wallet.keypair.sign(transcript).to_bytes()
+    }
+}
+
+struct SessionCipher {
+    send: ChaCha20Poly1305,
+    recv: ChaCha20Poly1305,
+    send_counter: u64,
+    recv_counter: u64,
+}
+
+impl SessionCipher {
+    fn nonce(counter: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = SessionCipher::nonce(self.send_counter);
+        self.send_counter += 1;
+        self.send.encrypt(&Nonce::from(nonce), plaintext)
+            .map_err(|_| Error::other("Failed to encrypt frame"))
+    }
+
+    fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = SessionCipher::nonce(self.recv_counter);
+        self.recv_counter += 1;
+        self.recv.decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Frame failed authentication"))
+    }
+}
+
+/// A peer session: network-magic tagged, length-prefixed message frames, optionally encrypted.
+pub struct Connection {
+    stream: TcpStream,
+    magic: [u8; 4],
+    cipher: Option<SessionCipher>,
+    /// Frame size limit for reads, raised from the handshake limit once the peer is authenticated.
+    max_frame: usize,
+    pub remote_id: Option<String>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Connection {
+    /// Dials `address`, either `host:port` or `id@host:port`; with an id, the peer must prove
+    /// it holds that node's key, which needs the encrypted transport.
+    pub async fn connect(address: &str, magic: [u8; 4], mode: TransportMode, identity: &NodeIdentity) -> Result<Connection, Error> {
+        let (expected_id, address) = match address.split_once('@') {
+            Some((id, address)) => (Some(id), address),
+            None => (None, address),
+        };
+        if expected_id.is_some() && mode == TransportMode::Plain {
+            return Err(Error::new(ErrorKind::InvalidInput, "Peer ids can only be checked over the encrypted transport"));
+        }
+        let stream = TcpStream::connect(address).await?;
+        match mode {
+            TransportMode::Plain => Ok(Connection::plain(stream, magic)),
+            TransportMode::Encrypted => Connection::initiate(stream, magic, identity, expected_id).await,
+        }
+    }
+
+    pub async fn accept(stream: TcpStream, magic: [u8; 4], mode: TransportMode, identity: &NodeIdentity) -> Result<Connection, Error> {
+        match mode {
+            TransportMode::Plain => Ok(Connection::plain(stream, magic)),
+            TransportMode::Encrypted => Connection::respond(stream, magic, identity).await,
+        }
+    }
+
+    pub fn plain(stream: TcpStream, magic: [u8; 4]) -> Connection {
+        Connection { stream, magic, cipher: None, max_frame: MAX_FRAME_SIZE, remote_id: None, metrics: None }
+    }
+
+    fn handshaking(stream: TcpStream, magic: [u8; 4]) -> Connection {
+        Connection { max_frame: MAX_HANDSHAKE_FRAME_SIZE, ..Connection::plain(stream, magic) }
+    }
+
+    /// Counts the session's messages into `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Initiator side: send our ephemeral key, authenticate the responder (as `expected_id`, if
+    /// given), then prove our identity.
+    pub async fn initiate(stream: TcpStream, magic: [u8; 4], identity: &NodeIdentity, expected_id: Option<&str>) -> Result<Connection, Error> {
+        let mut conn = Connection::handshaking(stream, magic);
+        let (secret, ephemeral) = new_ephemeral();
+        conn.write_frame(ephemeral.as_bytes()).await?;
+
+        let reply = conn.read_frame().await?.ok_or_else(handshake_eof)?;
+        if reply.len() != 32 + 32 + 64 {
+            return Err(handshake_error("Malformed responder handshake"));
+        }
+        let remote_ephemeral = to_array(&reply[..32]);
+        let transcript = transcript(ephemeral.as_bytes(), &remote_ephemeral);
+        let remote_id = verify_identity(&reply[32..64], &reply[64..], &transcript, b"responder")?;
+        if let Some(expected_id) = expected_id.filter(|expected_id| !expected_id.eq_ignore_ascii_case(&remote_id)) {
+            return Err(handshake_error(&format!("Peer is node {} instead of {}", remote_id, expected_id)));
+        }
+
+        let (send, recv) = derive_keys(&secret, &remote_ephemeral, &transcript);
+        conn.cipher = Some(SessionCipher { send, recv, send_counter: 0, recv_counter: 0 });
+        conn.remote_id = Some(remote_id);
+        conn.max_frame = MAX_FRAME_SIZE;
+
+        let mut proof = identity.public_bytes().to_vec();
+        proof.extend_from_slice(&identity.sign(&[&transcript[..], b"initiator"].concat()));
+        conn.send_bytes(&proof).await?;
+        Ok(conn)
+    }
+
+    /// Responder side: answer with our ephemeral key and signed identity, then authenticate the initiator.
+    pub async fn respond(stream: TcpStream, magic: [u8; 4], identity: &NodeIdentity) -> Result<Connection, Error> {
+        let mut conn = Connection::handshaking(stream, magic);
+        let hello = conn.read_frame().await?.ok_or_else(handshake_eof)?;
+        if hello.len() != 32 {
+            return Err(handshake_error("Malformed initiator handshake"));
+        }
+        let remote_ephemeral = to_array(&hello);
+        let (secret, ephemeral) = new_ephemeral();
+        let transcript = transcript(&remote_ephemeral, ephemeral.as_bytes());
+
+        let mut reply = ephemeral.as_bytes().to_vec();
+        reply.extend_from_slice(&identity.public_bytes());
+        reply.extend_from_slice(&identity.sign(&[&transcript[..], b"responder"].concat()));
+        conn.write_frame(&reply).await?;
+
+        let (recv, send) = derive_keys(&secret, &remote_ephemeral, &transcript);
+        conn.cipher = Some(SessionCipher { send, recv, send_counter: 0, recv_counter: 0 });
+
+        let proof = conn.recv_bytes().await?.ok_or_else(handshake_eof)?;
+        if proof.len() != 32 + 64 {
+            return Err(handshake_error("Malformed initiator identity"));
+        }
+        conn.remote_id = Some(verify_identity(&proof[..32], &proof[32..], &transcript, b"initiator")?);
+        conn.max_frame = MAX_FRAME_SIZE;
+        Ok(conn)
+    }
+
+    /// Finishes the session from our side; the peer sees the connection end.
+    pub async fn close(&mut self) -> Result<(), Error> {
+        self.stream.shutdown().await
+    }
+
+    pub async fn send(&mut self, msg: &Message) -> Result<(), Error> {
+        let json = msg.to_json();
+        if let Some(metrics) = &self.metrics {
+            metrics.message("out", msg.kind(), json.len());
+        }
+        self.send_bytes(json.as_bytes()).await
+    }
+
+    /// Reads the next message, or `None` once the peer has closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<Message>, Error> {
+        match self.recv_bytes().await? {
+            Some(bytes) => {
+                let msg = Message::from_json(&String::from_utf8_lossy(&bytes));
+                if let Some(metrics) = &self.metrics {
+                    metrics.message("in", msg.kind(), bytes.len());
+                }
+                Ok(Some(msg))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn send_bytes(&mut self, payload: &[u8]) -> Result<(), Error> {
+        match self.cipher.as_mut() {
+            Some(cipher) => {
+                let sealed = cipher.seal(payload)?;
+                self.write_frame(&sealed).await
+            }
+            None => self.write_frame(payload).await,
+        }
+    }
+
+    async fn recv_bytes(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let frame = match self.read_frame().await? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        match self.cipher.as_mut() {
+            Some(cipher) => cipher.open(&frame).map(Some),
+            None => Ok(Some(frame)),
+        }
+    }
+
+    async fn write_frame(&mut self, payload: &[u8]) -> Result<(), Error> {
+        if payload.len() > MAX_FRAME_SIZE {
+            return Err(Error::new(ErrorKind::InvalidInput, "Frame too large"));
+        }
+        self.stream.write_all(&self.magic).await?;
+        self.stream.write_all(&(payload.len() as u32).to_be_bytes()).await?;
+        self.stream.write_all(payload).await?;
+        self.stream.flush().await
+    }
+
+    async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut magic = [0u8; 4];
+        match self.stream.read_exact(&mut magic).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        if magic != self.magic {
+            return Err(Error::new(ErrorKind::InvalidData, "Peer is on a different network"));
+        }
+        let mut len_bytes = [0u8; 4];
+        self.stream.read_exact(&mut len_bytes).await?;
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        if len > self.max_frame {
+            return Err(Error::new(ErrorKind::InvalidData, "Frame too large"));
+        }
+        let mut payload = vec![0u8; len];
+        self.stream.read_exact(&mut payload).await?;
+        Ok(Some(payload))
+    }
+}
+
+fn new_ephemeral() -> (StaticSecret, EphemeralPublic) {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = StaticSecret::from(bytes);
+    let public = EphemeralPublic::from(&secret);
+    (secret, public)
+}
+
+fn transcript(initiator_ephemeral: &[u8], responder_ephemeral: &[u8]) -> Vec<u8> {
+    [HANDSHAKE_PROLOGUE, initiator_ephemeral, responder_ephemeral].concat()
+}
+
+/// Derives the (initiator -> responder, responder -> initiator) session keys.
+fn derive_keys(secret: &StaticSecret, remote_ephemeral: &[u8; 32], transcript: &[u8]) -> (ChaCha20Poly1305, ChaCha20Poly1305) {
+    let shared = secret.diffie_hellman(&EphemeralPublic::from(*remote_ephemeral));
+    let hkdf = Hkdf::<Sha256>::new(Some(transcript), shared.as_bytes());
+    let mut okm = [0u8; 64];
+    hkdf.expand(b"blockdag-session-keys", &mut okm).expect("64 bytes is a valid HKDF output length");
+    let initiator_to_responder = ChaCha20Poly1305::new(&Key::from(to_array(&okm[..32])));
+    let responder_to_initiator = ChaCha20Poly1305::new(&Key::from(to_array(&okm[32..])));
+    (initiator_to_responder, responder_to_initiator)
+}
+
+fn verify_identity(public: &[u8], signature: &[u8], transcript: &[u8], role: &[u8]) -> Result<String, Error> {
+    let public_key = PublicKey::from_bytes(public).map_err(|_| handshake_error("Invalid peer identity key"))?;
+    let signature = Signature::from_bytes(signature).map_err(|_| handshake_error("Invalid peer signature"))?;
+    public_key.verify(&[transcript, role].concat(), &signature)
+        .map_err(|_| handshake_error("Peer failed to prove its identity"))?;
+    Ok(hex::encode(public_key.to_bytes()))
+}
+
+fn to_array(bytes: &[u8]) -> [u8; 32] {
+    let mut array = [0u8; 32];
+    array.copy_from_slice(bytes);
+    array
+}
+
+fn handshake_error(reason: &str) -> Error {
+    Error::new(ErrorKind::PermissionDenied, reason)
+}
+
+fn handshake_eof() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "Peer closed connection during handshake")
+}
@@ -12,6 +12,12 @@ pub struct Wallet {
     pub keypair: Keypair,
 }
 
+impl Default for Wallet {
+    fn default() -> Self {
+        Wallet::new()
+    }
+}
+
 impl Wallet {
     pub fn new() -> Self {
         // Manually generate secret key and derive public key
@@ -44,8 +50,11 @@ impl Wallet {
     }
 
     pub fn verify(public_key: &PublicKey, message: &str, signature: &str) -> bool {
-        let sig_bytes = hex::decode(signature).expect("Invalid signature hex");
-        let signature = Signature::from_bytes(&sig_bytes).expect("Invalid signature bytes");
+        // Malformed signatures come from untrusted peers and RPC clients, so they just fail
+        let signature = match hex::decode(signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(signature) => signature,
+            None => return false,
+        };
         public_key.verify(message.as_bytes(), &signature).is_ok()
     }
 }
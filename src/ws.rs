@@ -0,0 +1,113 @@
+// src/ws.rs
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+use log::debug;
+use crate::events::NodeEvent;
+use crate::http::{write_response, HttpRequest, HttpResponse, UpgradeHandler};
+use crate::rpc::{handle_call, RpcContext};
+
+pub const WEBSOCKET_PATH: &str = "/ws";
+
+/// Upgrade handler serving WebSocket sessions at `/ws`.
+///
+/// Sessions speak JSON-RPC 2.0: every RPC method is available, plus `subscribe` and
+/// `unsubscribe` taking a list of event topics. Subscribed events arrive as `notification` calls.
+pub fn websocket_upgrade(context: RpcContext) -> UpgradeHandler {
+    Arc::new(move |request, stream| {
+        let context = context.clone();
+        Box::pin(async move { handle_websocket(context, request, stream).await })
+    })
+}
+
+async fn handle_websocket(context: RpcContext, request: HttpRequest, mut stream: TcpStream) {
+    let key = match request.header("sec-websocket-key") {
+        Some(key) if request.path == WEBSOCKET_PATH && request.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket")) => key,
+        _ => {
+            let _ = write_response(&mut stream, &HttpResponse::text(404, "WebSocket endpoint is /ws"), false).await;
+            return;
+        }
+    };
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes()),
+    );
+    if stream.write_all(handshake.as_bytes()).await.is_err() {
+        return;
+    }
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    let (mut sink, mut incoming) = socket.split();
+
+    let mut events = context.dag.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).events().subscribe();
+    let mut topics: HashSet<String> = HashSet::new();
+    loop {
+        let reply = tokio::select! {
+            message = incoming.next() => match message {
+                Some(Ok(WsMessage::Text(text))) => handle_message(&context, &mut topics, &text),
+                Some(Ok(WsMessage::Close(_))) | None => break,
+                Some(Ok(_)) => None, // pings are answered by the library
+                Some(Err(e)) => {
+                    debug!(target: "rpc", "WebSocket session with {} failed: {}", peer, e);
+                    break;
+                }
+            },
+            event = events.recv() => match event {
+                Ok(event) if topics.contains(event.topic()) => {
+                    Some(json!({ "jsonrpc": "2.0", "method": "notification", "params": event }))
+                }
+                Ok(_) => None,
+                Err(RecvError::Lagged(missed)) => {
+                    Some(json!({ "jsonrpc": "2.0", "method": "lagged", "params": { "missed": missed } }))
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if let Some(reply) = reply {
+            if sink.send(WsMessage::Text(reply.to_string())).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+fn handle_message(context: &RpcContext, topics: &mut HashSet<String>, text: &str) -> Option<Value> {
+    let call: Value = match serde_json::from_str(text) {
+        Ok(call) => call,
+        Err(e) => return Some(json!({ "jsonrpc": "2.0", "error": { "code": -32700, "message": e.to_string() }, "id": null })),
+    };
+    let method = call.get("method").and_then(Value::as_str).unwrap_or_default();
+    if method != "subscribe" && method != "unsubscribe" {
+        return handle_call(context, call);
+    }
+
+    let id = call.get("id").cloned()?;
+    let params = call.get("params").cloned().unwrap_or(Value::Null);
+    let requested = match params.get("topics").unwrap_or(&params) {
+        Value::Array(requested) => requested.iter().filter_map(Value::as_str).map(str::to_string).collect::<Vec<_>>(),
+        _ => vec![],
+    };
+    if let Some(unknown) = requested.iter().find(|topic| !NodeEvent::TOPICS.contains(&topic.as_str())) {
+        let message = format!("Unknown topic {} (expected one of {})", unknown, NodeEvent::TOPICS.join(", "));
+        return Some(json!({ "jsonrpc": "2.0", "error": { "code": -32602, "message": message }, "id": id }));
+    }
+    for topic in requested {
+        if method == "subscribe" {
+            topics.insert(topic);
+        } else {
+            topics.remove(&topic);
+        }
+    }
+    let mut subscribed: Vec<&String> = topics.iter().collect();
+    subscribed.sort();
+    Some(json!({ "jsonrpc": "2.0", "result": { "topics": subscribed }, "id": id }))
+}
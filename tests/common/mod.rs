@@ -0,0 +1,61 @@
+// tests/common/mod.rs
+
+// Every test binary compiles this module but uses only part of it
+#![allow(dead_code)]
+
+use blockdag::block::Block;
+use blockdag::blockdag::BlockDAG;
+use blockdag::config::NodeConfig;
+use blockdag::mining::MiningEngine;
+use blockdag::params::REGTEST;
+use blockdag::storage::MemoryStore;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+pub const MINER: &str = "7bf2b2f920a612a724a490b7b2dbea0199f8ae4fa3f595a930cf5f4c0d446308";
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// In-memory regtest DAG, where any nonce meets the difficulty.
+pub fn regtest_dag() -> BlockDAG {
+    BlockDAG::open(Box::new(MemoryStore::new()), &REGTEST, None).unwrap()
+}
+
+/// Regtest node settings with the P2P and RPC servers on ephemeral ports.
+pub fn regtest_config() -> NodeConfig {
+    NodeConfig {
+        network: "regtest".to_string(),
+        port: Some(0),
+        rpc_port: Some(0),
+        ..NodeConfig::default()
+    }
+}
+
+/// Mines and submits a block on the current tips paying `address`.
+pub fn mine(dag: &mut BlockDAG, address: &str) -> Block {
+    let template = dag.block_template(address).unwrap();
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    dag.submit_block(block.clone()).unwrap();
+    block
+}
+
+/// Empty directory under the system temp dir, unique to this test process.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("blockdag-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Polls `condition` until it holds or [`TIMEOUT`] passes.
+pub async fn wait_until(mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + TIMEOUT;
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
@@ -0,0 +1,79 @@
+// tests/config.rs
+
+mod common;
+
+use blockdag::config::NodeConfig;
+use blockdag::params::{REGTEST, TESTNET};
+use common::scratch_dir;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+// Configuration reads the process environment, which tests running in parallel share
+static ENV: Mutex<()> = Mutex::new(());
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn cli_overrides_env_which_overrides_file() {
+    let _env = ENV.lock().unwrap();
+    let dir = scratch_dir("config-layers");
+    let path = dir.join("node.toml");
+    fs::write(&path, "port = 1000\nnetwork = \"testnet\"\nlog_level = \"debug\"\npeers = [\"10.0.0.1:8080\"]\n").unwrap();
+
+    let from_file = NodeConfig::from_args(args(&["--config", path.to_str().unwrap()])).unwrap();
+    assert_eq!((from_file.port(), from_file.network.as_str()), (1000, "testnet"));
+
+    std::env::set_var("BLOCKDAG_PORT", "2000");
+    std::env::set_var("BLOCKDAG_NETWORK", "simnet");
+    let from_env = NodeConfig::from_args(args(&["--config", path.to_str().unwrap()]));
+    let from_cli = NodeConfig::from_args(args(&["--config", path.to_str().unwrap(), "--port=3000", "--peer", "10.0.0.2:8080"]));
+    std::env::remove_var("BLOCKDAG_PORT");
+    std::env::remove_var("BLOCKDAG_NETWORK");
+
+    let from_env = from_env.unwrap();
+    assert_eq!((from_env.port(), from_env.network.as_str()), (2000, "simnet"));
+    let from_cli = from_cli.unwrap();
+    assert_eq!((from_cli.port(), from_cli.network.as_str()), (3000, "simnet"));
+    assert_eq!(from_cli.peers, vec!["10.0.0.2:8080".to_string()]);
+    // Settings no later layer touches keep the file's value
+    assert_eq!(from_cli.log_level, "debug");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ports_default_to_the_network() {
+    let _env = ENV.lock().unwrap();
+    let config = NodeConfig::from_args(args(&["--network", "regtest"])).unwrap();
+    assert_eq!((config.port(), config.rpc_port()), (REGTEST.default_port, REGTEST.default_rpc_port));
+    let config = NodeConfig::from_args(args(&["--network", "testnet", "--rpc-port", "9000"])).unwrap();
+    assert_eq!((config.port(), config.rpc_port()), (TESTNET.default_port, 9000));
+}
+
+#[test]
+fn rejects_invalid_settings() {
+    let _env = ENV.lock().unwrap();
+    for invalid in [&["--network", "mainet"][..], &["--port", "http"], &["--frobnicate", "1"], &["--port"], &["--log-level", "loud"]] {
+        assert!(NodeConfig::from_args(args(invalid)).is_err(), "accepted {:?}", invalid);
+    }
+    let dir = scratch_dir("config-invalid");
+    let path = dir.join("node.toml");
+    fs::write(&path, "port = \"eighty\"\n").unwrap();
+    assert!(NodeConfig::from_args(args(&["--config", path.to_str().unwrap()])).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn addresses_parse_for_ipv4_and_ipv6() {
+    let ipv6 = NodeConfig { listen_address: "::".to_string(), port: Some(8080), rpc_address: "::".to_string(), rpc_port: Some(8081), ..NodeConfig::default() };
+    assert_eq!(ipv6.listen_socket().parse::<SocketAddr>().unwrap(), "[::]:8080".parse().unwrap());
+    assert_eq!(ipv6.node_address().parse::<SocketAddr>().unwrap(), "[::1]:8080".parse().unwrap());
+    assert_eq!(ipv6.rpc_socket().parse::<SocketAddr>().unwrap(), "[::]:8081".parse().unwrap());
+    assert_eq!(ipv6.rpc_url(), "http://[::1]:8081/");
+
+    let ipv4 = NodeConfig { listen_address: "0.0.0.0".to_string(), port: Some(8080), ..NodeConfig::default() };
+    assert_eq!(ipv4.listen_socket(), "0.0.0.0:8080");
+    assert_eq!(ipv4.node_address(), "127.0.0.1:8080");
+}
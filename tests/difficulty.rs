@@ -0,0 +1,46 @@
+// tests/difficulty.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::mining::MiningEngine;
+use blockdag::params::SIMNET;
+use common::{mine, MINER};
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn late_block_is_judged_by_the_difficulty_of_its_own_past() {
+    let mut dag = BlockDAG::new(&SIMNET);
+    // The first window reaches back to genesis and eases the difficulty; the next one, mined
+    // in a burst, raises it again at blue score 20
+    for _ in 1..19 {
+        mine(&mut dag, MINER);
+    }
+    assert_eq!(dag.difficulty, SIMNET.genesis.difficulty - 1);
+
+    // A block mined just before the retarget is held back while a competing one and the block
+    // retargeting on top of it get through
+    let template = dag.block_template(MINER).unwrap();
+    let late = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    // Another coinbase, or within the same millisecond it would be the same block
+    let competing = MiningEngine::new(1).solve(&template.with_extra_nonce(2), &AtomicBool::new(false)).unwrap();
+    dag.submit_block(competing).unwrap();
+    let retargeted = mine(&mut dag, MINER);
+    assert_eq!(dag.blue_score(&retargeted.hash), 20);
+    assert_eq!((retargeted.difficulty, dag.difficulty), (SIMNET.genesis.difficulty, SIMNET.genesis.difficulty));
+
+    // Claiming the new difficulty would be as wrong as falling short of it
+    let mut inflated = template.clone();
+    inflated.difficulty = dag.difficulty;
+    let inflated = MiningEngine::new(1).solve(&inflated.with_extra_nonce(1), &AtomicBool::new(false)).unwrap();
+    assert!(!dag.validate_block(&inflated));
+    dag.submit_block(late.clone()).unwrap();
+    assert_eq!(late.difficulty, SIMNET.genesis.difficulty - 1);
+
+    // Past the retarget the lower difficulty no longer passes
+    let mut stale = dag.block_template(MINER).unwrap();
+    stale.difficulty -= 1;
+    let stale = MiningEngine::new(1).solve(&stale, &AtomicBool::new(false)).unwrap();
+    assert!(dag.submit_block(stale).is_err());
+    mine(&mut dag, MINER);
+}
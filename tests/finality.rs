@@ -0,0 +1,78 @@
+// tests/finality.rs
+
+mod common;
+
+use blockdag::block::Block;
+use blockdag::blockdag::BlockDAG;
+use blockdag::events::NodeEvent;
+use blockdag::params::REGTEST;
+use common::{mine, regtest_dag, MINER};
+use tokio::sync::broadcast::Receiver;
+
+const ATTACKER: &str = "d4b3c1a0e9f8d7c6b5a4938271605f4e3d2c1b0a9f8e7d6c5b4a392817065f4e";
+
+fn mine_chain(dag: &mut BlockDAG, address: &str, count: usize) -> Vec<Block> {
+    (0..count).map(|_| mine(dag, address)).collect()
+}
+
+fn received(events: &mut Receiver<NodeEvent>) -> Vec<NodeEvent> {
+    std::iter::from_fn(|| events.try_recv().ok()).collect()
+}
+
+#[test]
+fn finality_point_trails_the_tip_by_finality_depth() {
+    let mut dag = regtest_dag();
+    mine_chain(&mut dag, MINER, REGTEST.finality_depth as usize - 1);
+    assert!(dag.finality_point().is_none());
+    mine_chain(&mut dag, MINER, 10);
+    let finality_point = dag.finality_point().unwrap();
+    assert_eq!(dag.blue_score(&finality_point), 9);
+    assert!(dag.selected_chain().contains(&finality_point));
+}
+
+#[test]
+fn refuses_to_reorganize_below_the_finality_point() {
+    let mut honest = regtest_dag();
+    mine_chain(&mut honest, MINER, 60);
+    let honest_tip = honest.virtual_selected_parent().unwrap();
+
+    // A chain mined in private from genesis, longer than the honest one
+    let mut attacker = regtest_dag();
+    let hidden = mine_chain(&mut attacker, ATTACKER, 70);
+
+    // While it trails the honest chain it is accepted as a side branch
+    for block in &hidden[..59] {
+        honest.submit_block(block.clone()).unwrap();
+    }
+    // The block tying the honest chain's blue score takes over only if its hash is smaller
+    let mut events = honest.events().subscribe();
+    let refused = hidden[59..].iter().position(|block| honest.submit_block(block.clone()).is_err()).unwrap();
+    assert!(refused <= 1);
+    let conflict = hidden[59 + refused].hash.clone();
+    assert!(received(&mut events).iter().any(|event| matches!(
+        event, NodeEvent::FinalityConflict { hash, .. } if *hash == conflict)));
+    assert!(!honest.has_block(&conflict));
+    assert_eq!(honest.virtual_selected_parent().unwrap(), honest_tip);
+}
+
+#[test]
+fn reorganizes_within_the_finality_window() {
+    let mut honest = regtest_dag();
+    let blocks = mine_chain(&mut honest, MINER, 60);
+
+    // A competing branch forking off well above the finality point
+    let mut rival = regtest_dag();
+    for block in &blocks[..55] {
+        rival.submit_block(block.clone()).unwrap();
+    }
+    let branch = mine_chain(&mut rival, ATTACKER, 10);
+
+    let mut events = honest.events().subscribe();
+    for block in &branch {
+        honest.submit_block(block.clone()).unwrap();
+    }
+    assert_eq!(honest.virtual_selected_parent().unwrap(), branch[9].hash);
+    let events = received(&mut events);
+    assert!(events.iter().any(|event| matches!(event, NodeEvent::Reorg { removed, .. } if removed.contains(&blocks[59].hash))));
+    assert!(!events.iter().any(|event| matches!(event, NodeEvent::FinalityConflict { .. })));
+}
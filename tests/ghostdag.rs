@@ -0,0 +1,115 @@
+// tests/ghostdag.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::params::REGTEST;
+use common::regtest_dag;
+
+/// Inserts a bodiless block named `hash` on top of `parents`; colouring only looks at the shape.
+fn add(dag: &mut BlockDAG, hash: &str, parents: &[&str]) -> String {
+    let mut block = REGTEST.genesis_block();
+    block.hash = hash.to_string();
+    block.previous_hashes = parents.iter().map(|parent| parent.to_string()).collect();
+    dag.insert_block(block);
+    hash.to_string()
+}
+
+fn chain(dag: &mut BlockDAG, name: &str, from: &str, length: usize) -> String {
+    let mut tip = from.to_string();
+    for i in 0..length {
+        tip = add(dag, &format!("{}{:03}", name, i), &[&tip]);
+    }
+    tip
+}
+
+#[test]
+fn chain_blocks_are_all_blue() {
+    let mut dag = regtest_dag();
+    let genesis = REGTEST.genesis.hash;
+    let tip = chain(&mut dag, "a", genesis, 10);
+    assert_eq!(dag.blue_score(&tip), 10);
+    let data = &dag.ghostdag[&tip];
+    assert_eq!(data.selected_parent.as_deref(), Some("a008"));
+    assert!(data.mergeset.is_empty() && data.mergeset_reds.is_empty());
+    assert_eq!(dag.selected_chain().len(), 11);
+}
+
+#[test]
+fn anticone_of_at_most_k_stays_blue() {
+    let mut dag = regtest_dag();
+    let genesis = REGTEST.genesis.hash;
+    let k = REGTEST.ghostdag_k as usize;
+    let parallel: Vec<String> = (0..=k).map(|i| add(&mut dag, &format!("p{:03}", i), &[genesis])).collect();
+    let parents: Vec<&str> = parallel.iter().map(String::as_str).collect();
+    let merge = add(&mut dag, "merge", &parents);
+
+    // k + 1 parallel blocks each have k blues in their anticone
+    let data = &dag.ghostdag[&merge];
+    assert_eq!((data.mergeset.len(), data.mergeset_reds.len()), (k, 0));
+    assert_eq!(data.blue_score, 1 + 1 + k as u64);
+    assert!(data.blues_anticone_sizes.values().all(|size| *size == k as u64));
+}
+
+#[test]
+fn wide_anticone_produces_red_blocks() {
+    let mut dag = regtest_dag();
+    let genesis = REGTEST.genesis.hash;
+    let k = REGTEST.ghostdag_k as usize;
+    let parallel: Vec<String> = (0..k + 7).map(|i| add(&mut dag, &format!("p{:03}", i), &[genesis])).collect();
+    let parents: Vec<&str> = parallel.iter().map(String::as_str).collect();
+    let merge = add(&mut dag, "merge", &parents);
+
+    // At most k + 1 mutually parallel blocks fit in a k-cluster; the rest are red
+    let data = dag.ghostdag[&merge].clone();
+    assert_eq!(data.mergeset.len(), k + 6);
+    assert_eq!(data.mergeset_reds.len(), 6);
+    assert_eq!(data.mergeset_blues().count(), k + 1);
+    assert_eq!(data.blue_score, 1 + 1 + k as u64);
+    for red in &data.mergeset_reds {
+        assert!(!data.blues_anticone_sizes.contains_key(red));
+    }
+
+    // Red blocks add nothing to the blue score of their descendants
+    let tip = add(&mut dag, "next", &[&merge]);
+    assert_eq!(dag.blue_score(&tip), data.blue_score + 1);
+}
+
+#[test]
+fn late_merged_side_chain_is_red() {
+    let mut dag = regtest_dag();
+    let genesis = REGTEST.genesis.hash;
+    let honest = chain(&mut dag, "h", genesis, 30);
+    let hidden = chain(&mut dag, "s", genesis, 25);
+    let merge = add(&mut dag, "merge", &[&honest, &hidden]);
+
+    // Every side-chain block has the whole honest chain, more than k blues, in its anticone
+    let data = &dag.ghostdag[&merge];
+    assert_eq!(data.selected_parent.as_deref(), Some(honest.as_str()));
+    assert_eq!((data.mergeset.len(), data.mergeset_reds.len()), (25, 25));
+    assert_eq!(data.blue_score, 31);
+
+    // A short side chain within the k-cluster is merged blue
+    let side = chain(&mut dag, "t", &honest, 3);
+    let main = chain(&mut dag, "m", &merge, 3);
+    let merge = add(&mut dag, "merge2", &[&main, &side]);
+    let data = &dag.ghostdag[&merge];
+    assert_eq!((data.mergeset.len(), data.mergeset_reds.len()), (3, 0));
+}
+
+#[test]
+fn recomputing_reproduces_the_colouring() {
+    let mut dag = regtest_dag();
+    let genesis = REGTEST.genesis.hash;
+    let k = REGTEST.ghostdag_k as usize;
+    let parallel: Vec<String> = (0..k + 4).map(|i| add(&mut dag, &format!("p{:03}", i), &[genesis])).collect();
+    let parents: Vec<&str> = parallel.iter().map(String::as_str).collect();
+    let merge = add(&mut dag, "merge", &parents[..k]);
+    let hidden = chain(&mut dag, "s", &parallel[k + 1], 5);
+    add(&mut dag, "tip", &[&merge, &hidden, &parallel[k + 3]]);
+
+    let computed = dag.ghostdag.clone();
+    dag.ghostdag.retain(|hash, _| hash == genesis);
+    assert_eq!(dag.fill_ghostdag().len(), computed.len() - 1);
+    assert_eq!(dag.ghostdag, computed);
+}
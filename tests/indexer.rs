@@ -0,0 +1,65 @@
+// tests/indexer.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::params::REGTEST;
+use blockdag::storage::LogStore;
+use common::{mine, regtest_dag, scratch_dir, MINER};
+use serde_json::Value;
+use std::fs;
+
+fn history(dag: &BlockDAG) -> Value {
+    serde_json::to_value(dag.address_history(MINER)).unwrap()
+}
+
+#[test]
+fn rebuilt_index_matches_after_pruning_and_reopening() {
+    let dir = scratch_dir("indexer-rebuild");
+    let path = dir.join("blockdag.db");
+    let mut dag = BlockDAG::open(Box::new(LogStore::open(&path).unwrap()), &REGTEST, None).unwrap();
+    dag.enable_tx_index();
+    let mut coinbase = None;
+    while dag.pruned_block_count < 20 {
+        let block = mine(&mut dag, MINER);
+        dag.persist_block(&block.hash).unwrap();
+        coinbase = Some(block.transactions[0].txid());
+    }
+
+    // Pruned bodies left the index as they were pruned; every coinbase pays the same, so
+    // they share a txid and the index lists one location per stored block
+    let locations = dag.tx_index().unwrap().transaction(&coinbase.unwrap()).to_vec();
+    assert!(locations.iter().all(|location| dag.blocks.contains_key(&location.block_hash)));
+    assert_eq!(locations.len(), dag.blocks.values().filter(|block| !block.transactions.is_empty()).count());
+    let indexed = history(&dag);
+    assert_eq!(indexed.as_array().unwrap().len(), locations.len());
+    dag.rebuild_tx_index();
+    assert_eq!(history(&dag), indexed);
+    drop(dag);
+
+    // After a restart the index is rebuilt from the stored blocks and agrees with a plain scan
+    let mut dag = BlockDAG::open(Box::new(LogStore::open(&path).unwrap()), &REGTEST, None).unwrap();
+    assert!(dag.tx_index().is_none());
+    let scanned = history(&dag);
+    dag.enable_tx_index();
+    assert_eq!(history(&dag), indexed);
+    assert_eq!(scanned, indexed);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn index_follows_a_snapshot_bootstrap() {
+    let mut source = regtest_dag();
+    for _ in 0..5 {
+        mine(&mut source, MINER);
+    }
+    let tip = source.tips[0].clone();
+    let snapshot = source.export_snapshot(&tip).unwrap();
+
+    let mut fresh = regtest_dag();
+    fresh.enable_tx_index();
+    fresh.import_snapshot(snapshot.clone(), &snapshot.commitment).unwrap();
+    // Only the snapshot's block body is stored, so only it is indexed
+    let entries = fresh.address_history(MINER);
+    assert_eq!(entries.iter().map(|entry| entry.block_hash.clone()).collect::<Vec<_>>(), vec![Some(tip)]);
+}
@@ -0,0 +1,104 @@
+// tests/ledger.rs
+
+mod common;
+
+use blockdag::mining::MiningEngine;
+use blockdag::params::REGTEST;
+use blockdag::transaction::Transaction;
+use blockdag::wallet::Wallet;
+use common::{mine, regtest_dag, MINER};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::AtomicBool;
+
+fn payment(wallet: &Wallet, receiver: &str, amount: u64, nonce: u64) -> Transaction {
+    let transaction = Transaction::new(wallet.get_address(), receiver.to_string(), amount, 0, String::new()).with_nonce(nonce);
+    Transaction { signature: wallet.sign(&transaction.calculate_hash()), ..transaction }
+}
+
+#[test]
+fn only_the_first_of_two_parallel_spends_is_applied() {
+    let mut dag = regtest_dag();
+    let wallet = Wallet::new();
+    mine(&mut dag, &wallet.get_address());
+    let reward = dag.get_balance(&wallet.get_address());
+    for _ in 0..REGTEST.coinbase_maturity {
+        mine(&mut dag, MINER);
+    }
+
+    // Two blocks on the same tips each spend the whole reward, to different receivers
+    let (first, second) = (Wallet::new().get_address(), Wallet::new().get_address());
+    let template = dag.block_template(MINER).unwrap();
+    for (extra_nonce, receiver) in [(1, &first), (2, &second)] {
+        let mut template = template.with_extra_nonce(extra_nonce);
+        template.transactions.push(payment(&wallet, receiver, reward, 1));
+        let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+        dag.submit_block(block).unwrap();
+    }
+    assert_eq!(dag.tips.len(), 2);
+
+    // Merging both keeps the money in one place
+    let merging = mine(&mut dag, MINER);
+    assert_eq!(merging.previous_hashes.len(), 2);
+    let ledger = dag.virtual_ledger();
+    assert_eq!(dag.get_balance(&wallet.get_address()), 0);
+    let mut received = [dag.get_balance(&first), dag.get_balance(&second)];
+    received.sort();
+    assert_eq!(received, [0, reward]);
+    assert_eq!(ledger.balances.values().sum::<u64>(), ledger.supply);
+    assert_eq!(dag.current_supply, ledger.supply);
+
+    // Blocks on top commit to the resolved state and still validate
+    let next = mine(&mut dag, MINER);
+    assert_eq!(next.state_commitment, ledger.commitment());
+    assert_eq!(dag.ledger_at(&next.hash).unwrap(), ledger);
+}
+
+#[test]
+fn spent_transactions_cannot_be_replayed() {
+    let mut dag = regtest_dag();
+    let wallet = Wallet::new();
+    mine(&mut dag, &wallet.get_address());
+    for _ in 0..REGTEST.coinbase_maturity {
+        mine(&mut dag, MINER);
+    }
+    let receiver = Wallet::new().get_address();
+    assert_eq!(dag.next_nonce(&wallet.get_address()), 1);
+    let first = payment(&wallet, &receiver, 10, 1);
+    dag.add_transaction(first.clone()).unwrap();
+    assert_eq!(dag.next_nonce(&wallet.get_address()), 2);
+    mine(&mut dag, MINER);
+    assert_eq!((dag.get_balance(&receiver), dag.virtual_ledger().nonce(&wallet.get_address())), (10, 1));
+
+    // The same signed transaction is refused by the mempool and by block validation
+    assert!(dag.add_transaction(first.clone()).is_err());
+    let mut template = dag.block_template(MINER).unwrap();
+    template.transactions.insert(0, first.clone());
+    let replay = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(!dag.validate_block(&replay));
+    // Transactions without a nonce could be replayed, so new ones must carry one
+    assert!(dag.add_transaction(payment(&wallet, &receiver, 10, 0)).is_err());
+    // The signature covers the nonce, so bumping it doesn't make a replay valid either
+    let mut template = dag.block_template(MINER).unwrap();
+    template.transactions.insert(0, Transaction { nonce: 2, ..first });
+    let forged = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(!dag.validate_block(&forged));
+
+    dag.add_transaction(payment(&wallet, &receiver, 10, 2)).unwrap();
+    mine(&mut dag, MINER);
+    assert_eq!(dag.get_balance(&receiver), 20);
+    assert_eq!(dag.virtual_ledger().nonces.get(&wallet.get_address()), Some(&2));
+}
+
+#[test]
+fn transactions_without_a_nonce_keep_their_hashes() {
+    // Block hashes cover this form, and signatures the signing message, so neither may change
+    let legacy = Transaction::new("a".to_string(), "b".to_string(), 5, 1, "sig".to_string());
+    assert_eq!(format!("{:?}", legacy), r#"Transaction { sender: "a", receiver: "b", amount: 5, fee: 1, signature: "sig" }"#);
+    assert_eq!(legacy.calculate_hash(), hex::encode(Sha256::digest(b"ab51")));
+    assert_eq!(serde_json::to_value(&legacy).unwrap().get("nonce"), None);
+
+    let current = legacy.clone().with_nonce(3);
+    assert_eq!(format!("{:?}", current), r#"Transaction { sender: "a", receiver: "b", amount: 5, fee: 1, nonce: 3, signature: "sig" }"#);
+    // Fee 1 with nonce 2 must not sign the same message as fee 12 without one
+    assert_ne!(legacy.with_nonce(2).calculate_hash(), Transaction::new("a".to_string(), "b".to_string(), 5, 12, String::new()).calculate_hash());
+}
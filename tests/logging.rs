@@ -0,0 +1,75 @@
+// tests/logging.rs
+
+mod common;
+
+use blockdag::config::NodeConfig;
+use blockdag::logging::{self, LogFilter};
+use common::scratch_dir;
+use log::Level;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn filters_parse_and_print_normalized() {
+    let filter = LogFilter::parse(" WARN , network = debug,mining=off,").unwrap();
+    assert_eq!(filter.to_string(), "warn,mining=off,network=debug");
+    assert_eq!(LogFilter::parse(&filter.to_string()).unwrap(), filter);
+    // The default level is info, and a later directive overrides an earlier one
+    assert_eq!(LogFilter::parse("").unwrap().to_string(), "info");
+    assert_eq!(LogFilter::parse("rpc=trace,debug,rpc=error").unwrap().to_string(), "debug,rpc=error");
+
+    for spec in ["loud", "network=", "network=verbose,info"] {
+        assert!(LogFilter::parse(spec).is_err(), "accepted {:?}", spec);
+    }
+}
+
+// The logger is process-wide, so everything that needs it is checked in this one test
+#[test]
+fn logger_filters_by_target_and_rotates_its_file() {
+    let dir = scratch_dir("logging");
+    let config = NodeConfig {
+        data_dir: dir.clone(),
+        log_level: "warn,network=debug".to_string(),
+        log_file: Some(PathBuf::from("node.log")),
+        log_max_size: 200,
+        log_max_files: 2,
+        ..NodeConfig::default()
+    };
+    logging::init(&config).unwrap();
+    assert!(logging::init(&config).is_err());
+
+    assert_eq!(logging::filter().unwrap(), "warn,network=debug");
+    assert!(log::log_enabled!(target: "network", Level::Debug));
+    assert!(log::log_enabled!(target: "network::peer", Level::Debug));
+    assert!(!log::log_enabled!(target: "networking", Level::Debug));
+    assert!(!log::log_enabled!(target: "consensus", Level::Info));
+    assert!(log::log_enabled!(target: "consensus", Level::Warn));
+
+    assert_eq!(logging::set_filter("error,consensus=info").unwrap(), "error,consensus=info");
+    assert!(log::log_enabled!(target: "consensus", Level::Info));
+    assert!(!log::log_enabled!(target: "network", Level::Warn));
+    assert!(logging::set_filter("consensus=loud").is_err());
+    assert_eq!(logging::filter().unwrap(), "error,consensus=info");
+
+    for line in 0..40 {
+        log::info!(target: "consensus", "line {:02}", line);
+        log::info!(target: "network", "filtered out");
+    }
+    log::logger().flush();
+
+    // Only the newest two rotated files are kept, each under the size limit, and together with
+    // the live file they hold the latest lines in order
+    let path = |name: &str| dir.join(name);
+    assert!(!path("node.log.3").exists());
+    let mut lines = vec![];
+    for name in ["node.log.2", "node.log.1", "node.log"] {
+        let text = fs::read_to_string(path(name)).unwrap();
+        assert!(!text.is_empty() && text.len() <= 200, "{} holds {} bytes", name, text.len());
+        assert!(!text.contains("filtered out"));
+        lines.extend(text.lines().map(|line| line.rsplit(": ").next().unwrap().to_string()));
+    }
+    let expected: Vec<String> = (40 - lines.len()..40).map(|line| format!("line {:02}", line)).collect();
+    assert_eq!(lines, expected);
+    assert!(lines.len() < 40);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,45 @@
+// tests/maturity.rs
+
+mod common;
+
+use blockdag::mining::MiningEngine;
+use blockdag::params::REGTEST;
+use blockdag::transaction::Transaction;
+use blockdag::wallet::Wallet;
+use common::{mine, regtest_dag, MINER};
+use std::sync::atomic::AtomicBool;
+
+fn payment(wallet: &Wallet, amount: u64, nonce: u64) -> Transaction {
+    let transaction = Transaction::new(wallet.get_address(), MINER.to_string(), amount, 0, String::new()).with_nonce(nonce);
+    Transaction { signature: wallet.sign(&transaction.calculate_hash()), ..transaction }
+}
+
+#[test]
+fn rewards_are_locked_until_mature() {
+    let mut dag = regtest_dag();
+    let wallet = Wallet::new();
+    mine(&mut dag, &wallet.get_address());
+    let reward = dag.get_balance(&wallet.get_address());
+
+    let balance = dag.balance(&wallet.get_address());
+    assert_eq!((balance.total, balance.spendable, balance.immature), (reward, 0, reward));
+    assert!(dag.add_transaction(payment(&wallet, 1, 1)).is_err());
+
+    // A block spending the immature reward is invalid too
+    let mut template = dag.block_template(MINER).unwrap();
+    template.transactions.insert(0, payment(&wallet, 1, 1));
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(!dag.validate_block(&block));
+
+    for _ in 0..REGTEST.coinbase_maturity {
+        mine(&mut dag, MINER);
+    }
+    let balance = dag.balance(&wallet.get_address());
+    assert_eq!((balance.spendable, balance.immature), (reward, 0));
+    dag.add_transaction(payment(&wallet, reward, 1)).unwrap();
+    assert!(dag.add_transaction(payment(&wallet, 1, 2)).is_err());
+
+    mine(&mut dag, MINER);
+    assert_eq!(dag.get_balance(&wallet.get_address()), 0);
+    assert!(dag.pending_transactions.is_empty());
+}
@@ -0,0 +1,99 @@
+// tests/metrics.rs
+
+mod common;
+
+use blockdag::http::HttpRequest;
+use blockdag::messages::Message;
+use blockdag::metrics::{handle_metrics, METRICS_PATH};
+use blockdag::mining::MiningEngine;
+use blockdag::node::Node;
+use blockdag::params::REGTEST;
+use blockdag::rpc::RpcContext;
+use blockdag::shutdown::Shutdown;
+use blockdag::transport::{Connection, NodeIdentity, TransportMode};
+use common::{mine, regtest_config, regtest_dag, wait_until, MINER};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+fn scrape(context: &RpcContext) -> String {
+    let request = HttpRequest { method: "GET".to_string(), path: METRICS_PATH.to_string(), query: String::new(), headers: Default::default(), body: vec![] };
+    let response = handle_metrics(context, &request);
+    assert_eq!((response.status, response.content_type), (200, "text/plain; version=0.0.4; charset=utf-8"));
+    String::from_utf8(response.body).unwrap()
+}
+
+fn has_line(text: &str, line: &str) -> bool {
+    text.lines().any(|candidate| candidate == line)
+}
+
+#[test]
+fn renders_counters_and_dag_gauges() {
+    let mut dag = regtest_dag();
+    let metrics = dag.metrics();
+    let engine = MiningEngine::new(1).metrics(metrics.clone());
+    let template = dag.block_template(MINER).unwrap();
+    let block = engine.solve(&template, &AtomicBool::new(false)).unwrap();
+    dag.submit_block(block.clone()).unwrap();
+    mine(&mut dag, MINER);
+    assert!(dag.submit_block(block).is_err());
+    let context = RpcContext { dag: Arc::new(Mutex::new(dag)), peers: Default::default(), shutdown: Shutdown::new(), metrics };
+
+    let text = scrape(&context);
+    for line in [
+        "# TYPE blockdag_blocks_accepted_total counter",
+        "blockdag_blocks_accepted_total 2",
+        "blockdag_blocks_rejected_total{reason=\"duplicate\"} 1",
+        "blockdag_block_count 3",
+        "blockdag_tips 1",
+        "blockdag_blocks_mined_total 1",
+        "blockdag_peers 0",
+        "blockdag_sync_progress 1",
+    ] {
+        assert!(has_line(&text, line), "missing {:?} in\n{}", line, text);
+    }
+
+    let request = HttpRequest { method: "POST".to_string(), path: METRICS_PATH.to_string(), query: String::new(), headers: Default::default(), body: vec![] };
+    assert_eq!(handle_metrics(&context, &request).status, 405);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nodes_keep_their_own_metrics() {
+    let first = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let second = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    for _ in 0..2 {
+        mine(&mut first.dag().lock().unwrap(), MINER);
+    }
+    assert!(has_line(&scrape(&first.rpc_context()), "blockdag_blocks_accepted_total 2"));
+    assert!(has_line(&scrape(&second.rpc_context()), "blockdag_blocks_accepted_total 0"));
+
+    // The session's messages are counted once on each side, in its own direction
+    second.connect(first.p2p_address().to_string());
+    assert!(wait_until(|| has_line(&scrape(&first.rpc_context()), "blockdag_messages_total{direction=\"in\",type=\"RequestTip\"} 1")).await);
+    let text = scrape(&second.rpc_context());
+    assert!(has_line(&text, "blockdag_messages_total{direction=\"out\",type=\"RequestTip\"} 1"), "{}", text);
+    assert!(!text.contains("direction=\"in\",type=\"RequestTip\""), "{}", text);
+
+    first.stop().await.unwrap();
+    second.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejected_peer_blocks_leave_sync_progress_alone() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let mut block = mine(&mut node.dag().lock().unwrap(), MINER);
+    let address = node.p2p_address().to_string();
+    let mut peer = Connection::connect(&address, REGTEST.magic, TransportMode::Plain, &NodeIdentity::generate()).await.unwrap();
+
+    // A block claiming an absurd index fails validation and must not count as seen
+    block.index = u64::MAX;
+    block.hash = format!("ff{}", &block.hash[2..]);
+    peer.send(&Message::NewBlock(block)).await.unwrap();
+    peer.send(&Message::RequestTip).await.unwrap();
+    assert!(matches!(peer.recv().await.unwrap(), Some(Message::Tip(_))));
+
+    let text = scrape(&node.rpc_context());
+    for line in ["blockdag_blocks_rejected_total{reason=\"invalid_pow\"} 1", "blockdag_peer_index 1", "blockdag_sync_progress 1"] {
+        assert!(has_line(&text, line), "missing {:?} in\n{}", line, text);
+    }
+    node.stop().await.unwrap();
+}
@@ -0,0 +1,182 @@
+// tests/migrations.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::mining::MiningEngine;
+use blockdag::params::{MAINNET, REGTEST};
+use blockdag::schema::{detect_version, CURRENT_SCHEMA_VERSION};
+use blockdag::storage::{Keyspace, LogStore, Storage};
+use blockdag::transaction::Transaction;
+use common::{mine, scratch_dir, MINER};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(FIXTURES).join(name)
+}
+
+/// Opens a copy of a fixture database in `dir`, upgrading it to the current schema.
+fn open_fixture(name: &str, dir: &Path) -> BlockDAG {
+    let db_path = dir.join("blockdag.db");
+    fs::copy(fixture(name), &db_path).unwrap();
+    BlockDAG::open(Box::new(LogStore::open(&db_path).unwrap()), &REGTEST, None).unwrap()
+}
+
+/// The one transfer in a fixture: 30 from a wallet that mined the first two blocks to `MINER`.
+fn transfer(dag: &BlockDAG) -> Transaction {
+    let mut transfers = dag.blocks.values().flat_map(|block| &block.transactions).filter(|tx| tx.sender != "0");
+    let transfer = transfers.next().unwrap().clone();
+    assert!(transfers.next().is_none());
+    assert_eq!((transfer.receiver.as_str(), transfer.amount, transfer.nonce), (MINER, 30, 0));
+    transfer
+}
+
+/// Checks a fixture of two blocks to the sender, two parallel blocks to `MINER`, one of them
+/// carrying the transfer, and a block to `MINER` merging both.
+fn check_merged_fork(dag: &BlockDAG) {
+    assert_eq!(dag.blocks.len(), 6);
+    let merge = &dag.tips[..];
+    assert_eq!(merge.len(), 1);
+    let data = &dag.ghostdag[&merge[0]];
+    assert_eq!(data.blue_score, 5);
+    assert!(data.mergeset_reds.is_empty());
+    assert_eq!(dag.ghostdag[data.selected_parent.as_ref().unwrap()].blue_score, 3);
+    assert_eq!(dag.get_balance(&transfer(dag).sender), 70);
+    assert_eq!(dag.get_balance(MINER), 180);
+}
+
+#[test]
+fn loads_v0_json_export() {
+    let dag = BlockDAG::load_from_file(fixture("v0_mainnet.json")).unwrap();
+    assert_eq!(dag.network, MAINNET.name);
+    assert_eq!(dag.blocks.len(), 3);
+    assert_eq!(dag.get_balance(MINER), 100);
+    dag.verify_integrity().unwrap();
+}
+
+#[test]
+fn loads_v1_json_export() {
+    let dag = BlockDAG::load_from_file(fixture("v1_regtest.json")).unwrap();
+    assert_eq!(dag.network, REGTEST.name);
+    assert_eq!(dag.blocks.len(), 3);
+    assert_eq!(dag.get_balance(MINER), 100);
+    dag.verify_integrity().unwrap();
+}
+
+#[test]
+fn upgrades_v1_database_in_place() {
+    let dir = scratch_dir("migrations-v1-db");
+    let db_path = dir.join("blockdag.db");
+    fs::copy(fixture("v1_regtest.db"), &db_path).unwrap();
+
+    let dag = BlockDAG::open(Box::new(LogStore::open(&db_path).unwrap()), &REGTEST, None).unwrap();
+    assert_eq!(dag.blocks.len(), 3);
+    assert_eq!(dag.get_balance(MINER), 100);
+    drop(dag);
+
+    let store = LogStore::open_read_only(&db_path).unwrap();
+    let version = store.get(Keyspace::State, "schema_version").unwrap().unwrap();
+    assert_eq!(String::from_utf8(version).unwrap(), CURRENT_SCHEMA_VERSION.to_string());
+    assert_eq!(BlockDAG::load_from_storage(&store).unwrap().blocks.len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn upgrades_v2_database_and_computes_ghostdag_data() {
+    let dir = scratch_dir("migrations-v2-db");
+    let mut dag = open_fixture("v2_regtest.db", &dir);
+    assert_eq!(dag.pruning_point, REGTEST.genesis.hash);
+    check_merged_fork(&dag);
+    let block = mine(&mut dag, MINER);
+    dag.persist_block(&block.hash).unwrap();
+    drop(dag);
+
+    // The upgrade was written back, GHOSTDAG data included
+    let store = LogStore::open_read_only(dir.join("blockdag.db")).unwrap();
+    let version = store.get(Keyspace::State, "schema_version").unwrap().unwrap();
+    assert_eq!(String::from_utf8(version).unwrap(), CURRENT_SCHEMA_VERSION.to_string());
+    assert_eq!(store.scan(Keyspace::Ghostdag).unwrap().len(), 7);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn upgrades_v3_database() {
+    let dir = scratch_dir("migrations-v3-db");
+    let mut dag = open_fixture("v3_regtest.db", &dir);
+    check_merged_fork(&dag);
+    mine(&mut dag, MINER);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn upgrades_v4_database_and_recolours_blocks_above_the_pruning_point() {
+    let dir = scratch_dir("migrations-v4-db");
+    let mut dag = open_fixture("v4_regtest.db", &dir);
+    // The pruning point keeps the data v4 stored for it, as its past is gone
+    assert_eq!((dag.pruned_block_count, dag.blocks.len()), (23, 101));
+    assert_eq!(dag.ghostdag[&dag.pruning_point].blue_score, 23);
+
+    // v4 coloured all 20 parallel blocks blue; only k of them besides the selected parent are
+    let merge = &dag.ghostdag[&dag.tips[0]];
+    let selected_parent = merge.selected_parent.clone().unwrap();
+    assert_eq!(dag.tips.len(), 1);
+    assert_eq!(merge.mergeset_reds.len(), 1);
+    assert_eq!(merge.blue_score, dag.ghostdag[&selected_parent].blue_score + 1 + REGTEST.ghostdag_k);
+
+    // The red block's reward never reached the ledger
+    assert_eq!(dag.get_balance(&transfer(&dag).sender), 70);
+    assert_eq!(dag.get_balance(MINER), 6030);
+    assert_eq!(dag.current_supply, 6100);
+    mine(&mut dag, MINER);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn upgrades_v5_database_and_starts_nonces_after_legacy_transfers() {
+    let dir = scratch_dir("migrations-v5-db");
+    let mut dag = open_fixture("v5_regtest.db", &dir);
+    assert_eq!(dag.blocks.len(), 17);
+    let transfer = transfer(&dag);
+    assert_eq!(dag.get_balance(&transfer.sender), 70);
+    assert_eq!(dag.get_balance(MINER), 730);
+    assert_eq!(dag.next_nonce(&transfer.sender), 1);
+
+    // A transfer without a nonce can't be carried again
+    let mut template = dag.block_template(MINER).unwrap();
+    template.transactions.insert(0, transfer);
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(dag.submit_block(block).is_err());
+    mine(&mut dag, MINER);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn imports_v0_json_into_empty_database() {
+    let dir = scratch_dir("migrations-v0-import");
+    let store = LogStore::open(dir.join("blockdag.db")).unwrap();
+    let dag = BlockDAG::open(Box::new(store), &MAINNET, Some(&fixture("v0_mainnet.json"))).unwrap();
+    assert_eq!(dag.blocks.len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn exports_current_version() {
+    let dir = scratch_dir("migrations-export");
+    let path = dir.join("blockdag.json");
+    BlockDAG::load_from_file(fixture("v0_mainnet.json")).unwrap().save_to_file(&path).unwrap();
+    let doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(detect_version(&doc), CURRENT_SCHEMA_VERSION);
+    assert_eq!(BlockDAG::load_from_file(&path).unwrap().blocks.len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rejects_newer_schema() {
+    let mut doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(fixture("v1_regtest.json")).unwrap()).unwrap();
+    doc["schema_version"] = serde_json::Value::from(CURRENT_SCHEMA_VERSION + 1);
+    assert!(blockdag::schema::migrate(&mut doc).is_err());
+}
@@ -0,0 +1,97 @@
+// tests/mining.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::mining::MiningEngine;
+use blockdag::params::{REGTEST, TESTNET_BLAKE3, TESTNET_SHA256D};
+use blockdag::storage::MemoryStore;
+use blockdag::transaction::Transaction;
+use blockdag::wallet::Wallet;
+use common::{mine, regtest_dag, MINER};
+use std::sync::atomic::AtomicBool;
+
+#[test]
+fn solves_template_across_threads() {
+    let mut dag = regtest_dag();
+    let mut template = dag.block_template(MINER).unwrap();
+    template.difficulty = 3;
+    let engine = MiningEngine::new(4);
+
+    let block = engine.solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(dag.has_valid_pow(&block));
+    assert!(engine.hash_rate() > 0.0);
+    template.difficulty = dag.difficulty;
+    let block = engine.solve(&template, &AtomicBool::new(false)).unwrap();
+    dag.submit_block(block).unwrap();
+    assert_eq!(dag.get_balance(MINER), template.reward);
+}
+
+#[test]
+fn rolls_extra_nonce_when_nonce_space_is_exhausted() {
+    let dag = regtest_dag();
+    let mut template = dag.block_template(MINER).unwrap();
+    template.difficulty = 2;
+    let engine = MiningEngine::new(2).max_nonce(0);
+
+    let block = engine.solve(&template, &AtomicBool::new(false)).unwrap();
+    assert_eq!(block.nonce, 0);
+    assert!(dag.has_valid_pow(&block));
+    let coinbase = block.transactions.last().unwrap();
+    assert_eq!(coinbase.sender, "0");
+    assert_eq!(coinbase.amount, template.reward);
+}
+
+#[test]
+fn stops_when_cancelled() {
+    let dag = regtest_dag();
+    let mut template = dag.block_template(MINER).unwrap();
+    template.difficulty = 64;
+    assert!(MiningEngine::new(2).solve(&template, &AtomicBool::new(true)).is_none());
+}
+
+#[test]
+fn mines_with_the_network_pow_function() {
+    for params in [&TESTNET_SHA256D, &TESTNET_BLAKE3] {
+        let dag = BlockDAG::open(Box::new(MemoryStore::new()), params, None).unwrap();
+        let mut template = dag.block_template(MINER).unwrap();
+        assert_eq!(template.pow, params.pow.name());
+        template.difficulty = 1;
+
+        let block = MiningEngine::new(2).solve(&template, &AtomicBool::new(false)).unwrap();
+        assert!(dag.has_valid_pow(&block));
+        assert!(!regtest_dag().has_valid_pow(&block));
+    }
+}
+
+#[test]
+fn refuses_unknown_pow_function() {
+    let dag = regtest_dag();
+    let mut template = dag.block_template(MINER).unwrap();
+    template.pow = "scrypt".to_string();
+    assert!(template.pow().is_err());
+    assert!(MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).is_none());
+}
+
+#[test]
+fn rejected_blocks_take_their_forged_transactions_out_of_the_mempool() {
+    let mut dag = regtest_dag();
+    let victim = Wallet::new();
+    mine(&mut dag, &victim.get_address());
+    for _ in 0..REGTEST.coinbase_maturity {
+        mine(&mut dag, MINER);
+    }
+
+    // Signed by someone else, e.g. admitted by a build that didn't check mempool signatures
+    let transaction = Transaction::new(victim.get_address(), MINER.to_string(), 10, 0, String::new()).with_nonce(1);
+    let forged = Transaction { signature: Wallet::new().sign(&transaction.calculate_hash()), ..transaction };
+    assert!(dag.add_transaction(forged.clone()).is_err());
+    dag.pending_transactions.push(forged);
+
+    let template = dag.block_template(MINER).unwrap();
+    assert_eq!(template.transactions.len(), 2);
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(dag.submit_block(block).is_err());
+    assert!(dag.pending_transactions.is_empty());
+    mine(&mut dag, MINER);
+}
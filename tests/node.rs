@@ -0,0 +1,212 @@
+// tests/node.rs
+
+mod common;
+
+use blockdag::config::NodeConfig;
+use blockdag::messages::Message;
+use blockdag::mining::{run_remote_miner, MiningEngine};
+use blockdag::node::Node;
+use blockdag::pool::{run_pool_worker, PoolServer};
+use blockdag::params::REGTEST;
+use blockdag::rpc::RpcClient;
+use blockdag::transaction::Transaction;
+use blockdag::transport::{Connection, NodeIdentity, TransportMode};
+use blockdag::wallet::Wallet;
+use common::{mine, regtest_config, scratch_dir, wait_until, MINER, TIMEOUT};
+use serde_json::json;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const POOL: &str = "3f7bd1c02bb1d4a5ff0ec7e4b6dc3d8d5a2ab1e0f6e4f54b0d0a7c5ff2a3c611";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mining_node_produces_blocks() {
+    let node = Node::builder(regtest_config())
+        .in_memory()
+        .miner(MINER)
+        .mining_interval(Duration::from_millis(10))
+        .start()
+        .await
+        .unwrap();
+    assert!(node.rpc_address().is_some());
+
+    let dag = node.dag();
+    assert!(wait_until(|| dag.lock().unwrap().known_block_count() >= 3).await);
+    assert!(dag.lock().unwrap().get_balance(MINER) > 0);
+    node.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nodes_connect_to_each_other() {
+    let config = NodeConfig { disable_rpc: true, ..regtest_config() };
+    let first = Node::builder(config.clone()).in_memory().start().await.unwrap();
+    let second = Node::builder(config).in_memory().start().await.unwrap();
+    assert!(first.rpc_address().is_none());
+
+    second.connect(first.p2p_address().to_string());
+    let (first_peers, second_peers) = (first.peers(), second.peers());
+    assert!(wait_until(|| !first_peers.lock().unwrap().is_empty() && !second_peers.lock().unwrap().is_empty()).await);
+
+    second.stop().await.unwrap();
+    first.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn node_keeps_its_identity_and_pinned_peers_must_match_it() {
+    let dir = scratch_dir("node-identity");
+    let config = NodeConfig { data_dir: dir.clone(), transport: TransportMode::Encrypted, disable_rpc: true, ..regtest_config() };
+    let node = Node::builder(config.clone()).start().await.unwrap();
+    let node_id = node.identity().node_id();
+    node.stop().await.unwrap();
+    let node = Node::builder(config.clone()).start().await.unwrap();
+    assert_eq!(node.identity().node_id(), node_id);
+
+    let peer_config = NodeConfig { transport: TransportMode::Encrypted, disable_rpc: true, ..regtest_config() };
+    let peer = Node::builder(peer_config).in_memory().start().await.unwrap();
+    let address = node.p2p_address();
+    peer.connect(format!("{}@{}", NodeIdentity::generate().node_id(), address));
+    peer.connect(format!("{}@{}", node_id, address));
+    let peers = peer.peers();
+    assert!(wait_until(|| !peers.lock().unwrap().is_empty()).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let connected: Vec<_> = peers.lock().unwrap().values().map(|info| info.node_id.clone()).collect();
+    assert_eq!(connected, vec![Some(node_id)]);
+
+    peer.stop().await.unwrap();
+    node.stop().await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn forged_transactions_from_peers_stay_out_of_the_mempool() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let sender = Wallet::new();
+    mine(&mut node.dag().lock().unwrap(), &sender.get_address());
+    for _ in 0..REGTEST.coinbase_maturity {
+        mine(&mut node.dag().lock().unwrap(), MINER);
+    }
+    let transaction = Transaction::new(sender.get_address(), MINER.to_string(), 10, 0, String::new()).with_nonce(1);
+    let forged = Transaction { signature: Wallet::new().sign(&transaction.calculate_hash()), ..transaction.clone() };
+    let signed = Transaction { signature: sender.sign(&transaction.calculate_hash()), ..transaction };
+
+    let address = node.p2p_address().to_string();
+    let mut peer = Connection::connect(&address, REGTEST.magic, TransportMode::Plain, &NodeIdentity::generate()).await.unwrap();
+    for transaction in [forged, signed.clone()] {
+        peer.send(&Message::NewTransaction(transaction)).await.unwrap();
+    }
+    // The node handles a session's messages in order, so the tip reply comes after both
+    peer.send(&Message::RequestTip).await.unwrap();
+    assert!(matches!(peer.recv().await.unwrap(), Some(Message::Tip(_))));
+
+    let pending = node.dag().lock().unwrap().pending_transactions.clone();
+    assert_eq!(pending.iter().map(|tx| tx.txid()).collect::<Vec<_>>(), vec![signed.txid()]);
+    node.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fresh_node_bootstraps_only_from_proofs_it_asked_for() {
+    let config = NodeConfig { disable_rpc: true, ..regtest_config() };
+    let pruned = Node::builder(config.clone()).in_memory().start().await.unwrap();
+    let dag = pruned.dag();
+    let proof = {
+        let mut dag = dag.lock().unwrap();
+        while dag.pruned_block_count == 0 {
+            mine(&mut dag, MINER);
+        }
+        dag.pruning_point_proof().unwrap()
+    };
+
+    // A proof pushed by a peer that wasn't asked is ignored
+    let fresh = Node::builder(config).in_memory().start().await.unwrap();
+    let mut peer = Connection::connect(&fresh.p2p_address().to_string(), REGTEST.magic, TransportMode::Plain, &NodeIdentity::generate()).await.unwrap();
+    peer.send(&Message::PruningPointProof(Box::new(proof.clone()))).await.unwrap();
+    peer.send(&Message::RequestTip).await.unwrap();
+    assert!(matches!(peer.recv().await.unwrap(), Some(Message::Tip(tip)) if tip == REGTEST.genesis.hash));
+    assert_eq!(fresh.dag().lock().unwrap().pruning_point, REGTEST.genesis.hash);
+
+    fresh.connect(pruned.p2p_address().to_string());
+    let dag = fresh.dag();
+    assert!(wait_until(|| dag.lock().unwrap().pruning_point == proof.pruning_point.hash).await);
+
+    fresh.stop().await.unwrap();
+    pruned.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peer_blocks_update_supply_and_block_count() {
+    let config = NodeConfig { disable_rpc: true, ..regtest_config() };
+    let first = Node::builder(config.clone()).in_memory().start().await.unwrap();
+    let block = mine(&mut first.dag().lock().unwrap(), MINER);
+    let second = Node::builder(config).in_memory().start().await.unwrap();
+    second.connect(first.p2p_address().to_string());
+
+    // A relayed block is accepted like a submitted one, reward bookkeeping included
+    let dag = second.dag();
+    assert!(wait_until(|| dag.lock().unwrap().has_block(&block.hash)).await);
+    let counters = dag.lock().map(|dag| (dag.block_count, dag.current_supply)).unwrap();
+    assert_eq!(counters, (2, block.reward));
+
+    second.stop().await.unwrap();
+    first.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_miner_submits_blocks_over_rpc() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let client = RpcClient::new(format!("http://{}/", node.rpc_address().unwrap()));
+    let shutdown = node.shutdown_handle();
+    let miner = tokio::spawn(run_remote_miner(client, MiningEngine::new(2), MINER.to_string(), Duration::from_millis(10), shutdown));
+
+    let dag = node.dag();
+    assert!(wait_until(|| dag.lock().unwrap().get_balance(MINER) > 0).await);
+    node.stop().await.unwrap();
+    miner.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rpc_stop_shuts_the_node_down() {
+    let node = Node::builder(regtest_config())
+        .in_memory()
+        .miner(MINER)
+        .mining_interval(Duration::from_millis(10))
+        .start()
+        .await
+        .unwrap();
+    let client = RpcClient::new(format!("http://{}/", node.rpc_address().unwrap()));
+    let dag = node.dag();
+    assert!(wait_until(|| dag.lock().unwrap().block_count >= 2).await);
+
+    assert_eq!(client.call("stop", json!([])).await.unwrap(), json!("Node stopping"));
+    tokio::time::timeout(TIMEOUT, node.wait_for_shutdown()).await.unwrap();
+    node.stop().await.unwrap();
+
+    // The miner and the RPC server are gone once the node has stopped
+    let block_count = dag.lock().unwrap().block_count;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(dag.lock().unwrap().block_count, block_count);
+    assert!(client.call("getTips", json!([])).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pool_credits_shares_and_forwards_blocks() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let client = RpcClient::new(format!("http://{}/", node.rpc_address().unwrap()));
+    let shutdown = node.shutdown_handle();
+    let pool = PoolServer::new(client, POOL, Some(0));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let pool_address = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(pool.clone().run(listener, shutdown.clone()));
+    let workers = ["alice", "bob"].map(|worker| {
+        tokio::spawn(run_pool_worker(pool_address.clone(), worker.to_string(), MiningEngine::new(1), shutdown.clone()))
+    });
+
+    let dag = node.dag();
+    assert!(wait_until(|| dag.lock().unwrap().get_balance(POOL) > 0).await);
+    assert!(wait_until(|| pool.stats().values().filter(|stats| stats.accepted_shares > 0).count() == 2).await);
+    assert!(pool.stats().values().map(|stats| stats.blocks_found).sum::<u64>() > 0);
+    node.stop().await.unwrap();
+    server.await.unwrap();
+    for worker in workers {
+        worker.await.unwrap();
+    }
+}
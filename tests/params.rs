@@ -0,0 +1,60 @@
+// tests/params.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::params::{NetworkParams, MAINNET, REGTEST, SIMNET, TESTNET, TESTNET_BLAKE3, TESTNET_SHA256D};
+use blockdag::storage::LogStore;
+use common::{regtest_dag, scratch_dir};
+use std::collections::HashSet;
+use std::fs;
+
+const NETWORKS: [&NetworkParams; 6] = [&MAINNET, &TESTNET, &TESTNET_SHA256D, &TESTNET_BLAKE3, &SIMNET, &REGTEST];
+
+#[test]
+fn profiles_have_fixed_distinct_genesis_blocks() {
+    let mut hashes = HashSet::new();
+    let mut magics = HashSet::new();
+    let mut ports = HashSet::new();
+    for params in NETWORKS {
+        let genesis = params.genesis_block();
+        assert_eq!(genesis.hash, params.genesis.hash);
+        assert_eq!(params.genesis_block().hash, genesis.hash, "{} genesis is not deterministic", params.name);
+        assert!(std::ptr::eq(NetworkParams::by_name(params.name).unwrap(), params));
+        assert!(params.pruning_depth > params.finality_depth);
+        assert!(hashes.insert(genesis.hash) && magics.insert(params.magic));
+        assert!(ports.insert(params.default_port) && ports.insert(params.default_rpc_port));
+    }
+    assert!(NetworkParams::by_name("mainet").is_none());
+}
+
+#[test]
+fn dag_starts_from_its_network_genesis() {
+    for params in NETWORKS {
+        let dag = BlockDAG::new(params);
+        assert_eq!(dag.tips, vec![params.genesis.hash.to_string()]);
+        assert_eq!((dag.difficulty, dag.params().name), (params.genesis.difficulty, params.name));
+    }
+}
+
+#[test]
+fn refuses_to_load_an_unknown_network() {
+    let dir = scratch_dir("params-unknown-network");
+    let path = dir.join("blockdag.json");
+    let mut dag = regtest_dag();
+    dag.network = "regtset".to_string();
+    dag.save_to_file(&path).unwrap();
+    let error = BlockDAG::load_from_file(&path).err().unwrap();
+    assert!(error.to_string().contains("Unknown network regtset"));
+
+    // Same for a database whose chain state names an unknown network
+    let db_path = dir.join("blockdag.db");
+    let mut dag = BlockDAG::open(Box::new(LogStore::open(&db_path).unwrap()), &REGTEST, None).unwrap();
+    dag.network = "regtset".to_string();
+    dag.flush().unwrap();
+    drop(dag);
+    let store = LogStore::open(&db_path).unwrap();
+    assert!(BlockDAG::load_from_storage(&store).err().unwrap().to_string().contains("Unknown network regtset"));
+    assert!(BlockDAG::open(Box::new(store), &REGTEST, None).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,191 @@
+// tests/pruning.rs
+
+mod common;
+
+use blockdag::block::{Block, BlockHeader};
+use blockdag::blockdag::BlockDAG;
+use blockdag::mining::MiningEngine;
+use blockdag::params::{REGTEST, SIMNET};
+use blockdag::pruning::PruningPointProof;
+use common::{mine, regtest_dag, MINER};
+use std::sync::atomic::AtomicBool;
+
+/// Mines `count` blocks on the same tips, differing only in their coinbase.
+fn mine_parallel(dag: &mut BlockDAG, count: u64) {
+    let template = dag.block_template(MINER).unwrap();
+    for extra_nonce in 0..count {
+        let block = MiningEngine::new(1).solve(&template.with_extra_nonce(extra_nonce), &AtomicBool::new(false)).unwrap();
+        dag.submit_block(block).unwrap();
+    }
+}
+
+/// Regtest DAG whose pruning point has red blocks in its past.
+fn pruned_dag() -> BlockDAG {
+    let mut dag = regtest_dag();
+    mine(&mut dag, MINER);
+    mine_parallel(&mut dag, REGTEST.ghostdag_k + 5);
+    while dag.pruned_block_count < REGTEST.ghostdag_k + 10 {
+        mine(&mut dag, MINER);
+    }
+    dag
+}
+
+/// `header` with a nonce found for its difficulty and the hash that goes with it.
+fn mined_header(mut header: BlockHeader) -> BlockHeader {
+    let hash = |header: &BlockHeader| Block::calculate_header_hash(SIMNET.pow, header.index, header.timestamp, &header.previous_hashes, header.nonce, header.difficulty, &header.transactions_root, &header.state_commitment);
+    while !Block::meets_difficulty(&hash(&header), header.difficulty) {
+        header.nonce += 1;
+    }
+    header.hash = hash(&header);
+    header
+}
+
+#[test]
+fn fresh_node_bootstraps_from_a_pruned_nodes_proof() {
+    let dag = pruned_dag();
+    let proof = dag.pruning_point_proof().unwrap();
+    assert_eq!(proof.headers[0].hash, REGTEST.genesis.hash);
+    assert_eq!(proof.headers.len() as u64, dag.pruned_block_count);
+    // The red blocks in its past count as blocks but not towards its blue score
+    assert!(proof.pruning_point_ghostdag.blue_score < proof.headers.len() as u64);
+
+    let mut fresh = regtest_dag();
+    fresh.bootstrap_from_proof(proof.clone()).unwrap();
+    assert_eq!(fresh.pruned_block_count, proof.headers.len() as u64);
+    assert_eq!(fresh.block_count, dag.block_count);
+    assert_eq!((fresh.pruning_point.clone(), fresh.tips.clone()), (dag.pruning_point.clone(), dag.tips.clone()));
+    assert_eq!(fresh.ghostdag[&dag.tips[0]], dag.ghostdag[&dag.tips[0]]);
+    assert_eq!((fresh.current_supply, fresh.get_balance(MINER)), (dag.current_supply, dag.get_balance(MINER)));
+
+    // The bootstrapped node keeps the headers and can serve the same proof
+    assert_eq!(fresh.pruning_point_proof().unwrap().headers.len(), proof.headers.len());
+    mine(&mut fresh, MINER);
+}
+
+#[test]
+fn refuses_proof_not_linked_to_genesis() {
+    let dag = pruned_dag();
+    let proof = dag.pruning_point_proof().unwrap();
+    let fresh = regtest_dag();
+
+    let mut without_genesis = proof.clone();
+    without_genesis.headers.remove(0);
+    let mut out_of_order = proof.clone();
+    out_of_order.headers.swap(1, 2);
+    let mut truncated = proof.clone();
+    truncated.headers.truncate(10);
+    let mut without_past = proof.clone();
+    without_past.headers.clear();
+    for forged in [without_genesis, out_of_order, truncated, without_past] {
+        assert!(fresh.verify_pruning_point_proof(&forged).is_err());
+    }
+    fresh.verify_pruning_point_proof(&proof).unwrap();
+}
+
+#[test]
+fn refuses_forged_blue_score() {
+    let dag = pruned_dag();
+    let mut proof = dag.pruning_point_proof().unwrap();
+    proof.pruning_point_ghostdag.blue_score = proof.headers.len() as u64;
+    let error = regtest_dag().verify_pruning_point_proof(&proof).err().unwrap();
+    assert!(error.to_string().contains("blue score"));
+}
+
+#[test]
+fn refuses_snapshot_not_matching_the_pruning_point() {
+    let dag = pruned_dag();
+    let mut proof = dag.pruning_point_proof().unwrap();
+    proof.snapshot.supply += 1;
+    let error = regtest_dag().verify_pruning_point_proof(&proof).err().unwrap();
+    assert!(error.to_string().contains("state commitment"));
+}
+
+#[test]
+fn checks_difficulty_against_network_params() {
+    let mut dag = BlockDAG::new(&SIMNET);
+    let block = mine(&mut dag, MINER);
+    let snapshot = dag.export_snapshot(&block.hash).unwrap();
+    let proof = PruningPointProof {
+        network: snapshot.network,
+        pruning_point: snapshot.block,
+        pruning_point_ghostdag: snapshot.ghostdag,
+        snapshot: snapshot.ledger,
+        chain: vec![],
+        headers: snapshot.headers,
+    };
+    let fresh = BlockDAG::new(&SIMNET);
+    fresh.verify_pruning_point_proof(&proof).unwrap();
+
+    // The difficulty is hashed, so a block can't claim less than it was mined at
+    let mut easy = proof.clone();
+    easy.pruning_point.difficulty = 0;
+    assert!(!fresh.has_valid_pow(&easy.pruning_point));
+    let error = fresh.verify_pruning_point_proof(&easy).err().unwrap();
+    assert!(error.to_string().contains("invalid proof of work"));
+
+    let mut forged_hash = proof.clone();
+    let mut header = proof.headers[0].clone();
+    header.hash = "f00d".to_string();
+    header.previous_hashes = vec![SIMNET.genesis.hash.to_string()];
+    header.transactions_root = Block::transactions_root(&[]);
+    forged_hash.headers.push(header.clone());
+    let error = fresh.verify_pruning_point_proof(&forged_hash).err().unwrap();
+    assert!(error.to_string().contains("does not match its contents"));
+
+    // Correctly hashed, but harder than its past requires
+    let mut wrong_difficulty = proof.clone();
+    header.index = 1;
+    header.difficulty = SIMNET.genesis.difficulty + 1;
+    let header = mined_header(header);
+    wrong_difficulty.headers.push(header.clone());
+    let error = fresh.verify_pruning_point_proof(&wrong_difficulty).err().unwrap();
+    assert!(error.to_string().contains(&format!("header {} has invalid proof of work", header.hash)));
+}
+
+#[test]
+fn blocks_above_the_pruning_point_are_fully_validated() {
+    let dag = pruned_dag();
+    let mut template = dag.block_template(MINER).unwrap();
+    template.state_commitment = "00".repeat(32);
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert!(dag.has_valid_pow(&block));
+
+    let mut proof = dag.pruning_point_proof().unwrap();
+    proof.chain.push(block.clone());
+    let error = regtest_dag().verify_pruning_point_proof(&proof).err().unwrap();
+    assert!(error.to_string().contains(&format!("block {} is invalid", block.hash)));
+    assert!(regtest_dag().bootstrap_from_proof(proof).is_err());
+}
+
+#[test]
+fn only_requested_proofs_are_taken() {
+    let proof = pruned_dag().pruning_point_proof().unwrap();
+    let mut fresh = regtest_dag();
+    let error = fresh.receive_pruning_point_proof("peer", proof.clone()).err().unwrap();
+    assert!(error.to_string().contains("not requested"));
+    assert_eq!(fresh.pruning_point, REGTEST.genesis.hash);
+
+    fresh.request_pruning_point_proof("peer");
+    assert_eq!(fresh.receive_pruning_point_proof("peer", proof.clone()).unwrap(), Some(proof.pruning_point.hash.clone()));
+    // An answer counts once
+    assert!(fresh.receive_pruning_point_proof("peer", proof).is_err());
+}
+
+#[test]
+fn fresh_node_starts_from_the_heaviest_proof() {
+    let light = pruned_dag();
+    let mut heavy = pruned_dag();
+    for _ in 0..5 {
+        mine(&mut heavy, MINER);
+    }
+    let mut fresh = regtest_dag();
+    for peer in ["light", "heavy", "silent"] {
+        fresh.request_pruning_point_proof(peer);
+    }
+    assert_eq!(fresh.receive_pruning_point_proof("heavy", heavy.pruning_point_proof().unwrap()).unwrap(), None);
+    assert_eq!(fresh.receive_pruning_point_proof("light", light.pruning_point_proof().unwrap()).unwrap(), None);
+    // A peer that never answers holds the choice up only until it goes away
+    assert_eq!(fresh.abandon_pruning_point_proof("silent").unwrap(), Some(heavy.pruning_point.clone()));
+    assert_eq!(fresh.tips, heavy.tips);
+    assert_eq!(fresh.get_balance(MINER), heavy.get_balance(MINER));
+}
@@ -0,0 +1,119 @@
+// tests/reachability.rs
+
+use blockdag::reachability::ReachabilityIndex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashSet, VecDeque};
+
+fn name(i: usize) -> String {
+    format!("b{:04}", i)
+}
+
+/// Chain `first..first + length` hanging off `parent`.
+fn add_chain(index: &mut ReachabilityIndex, parent: &str, first: usize, length: usize) {
+    let mut parent = parent.to_string();
+    for i in first..first + length {
+        index.add_block(&name(i), Some(&parent), &[]);
+        parent = name(i);
+    }
+}
+
+#[test]
+fn long_chain_exhausting_intervals_is_reindexed() {
+    let mut index = ReachabilityIndex::default();
+    index.add_block("genesis", None, &[]);
+    // Each child keeps 7/8 of its parent's interval, so a few hundred levels use up 64 bits
+    add_chain(&mut index, "genesis", 0, 300);
+    let before: Vec<_> = (0..300).map(|i| index.interval(&name(i)).unwrap()).collect();
+    add_chain(&mut index, &name(299), 300, 200);
+    assert!((0..300).any(|i| index.interval(&name(i)).unwrap() != before[i]));
+
+    for i in (0..500).step_by(7) {
+        for j in (0..500).step_by(11) {
+            assert_eq!(index.is_dag_ancestor_of(&name(i), &name(j)), i <= j, "{} vs {}", i, j);
+        }
+    }
+    // Side branches added after the reindex still get their own room
+    add_chain(&mut index, &name(450), 1000, 50);
+    assert!(index.is_dag_ancestor_of(&name(450), &name(1049)));
+    assert!(!index.is_dag_ancestor_of(&name(451), &name(1049)));
+    assert!(!index.is_dag_ancestor_of(&name(1049), &name(499)));
+}
+
+#[test]
+fn wide_fan_out_exhausting_intervals_is_reindexed() {
+    let mut index = ReachabilityIndex::default();
+    index.add_block("genesis", None, &[]);
+    // Each sibling leaves 1/8 of what's free to the next one
+    for i in 0..20 {
+        index.add_block(&name(i), Some("genesis"), &[]);
+    }
+    let first = index.interval(&name(0)).unwrap();
+    for i in 20..60 {
+        index.add_block(&name(i), Some("genesis"), &[]);
+    }
+    assert_ne!(index.interval(&name(0)).unwrap(), first);
+
+    for i in 0..60 {
+        assert!(index.is_dag_ancestor_of("genesis", &name(i)));
+        for j in 0..60 {
+            assert_eq!(index.is_dag_ancestor_of(&name(i), &name(j)), i == j);
+        }
+    }
+}
+
+/// Random DAG of `size` blocks, each with parents among the few blocks before it.
+fn random_dag(rng: &mut StdRng, size: usize) -> Vec<Vec<usize>> {
+    (0..size).map(|i| {
+        if i == 0 {
+            return vec![];
+        }
+        let window = i.min(6);
+        let mut parents: Vec<usize> = (0..rng.gen_range(1..=3)).map(|_| i - rng.gen_range(1..=window)).collect();
+        parents.sort();
+        parents.dedup();
+        parents
+    }).collect()
+}
+
+#[test]
+fn matches_breadth_first_search_on_random_dags() {
+    for seed in 0..4 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let parents = random_dag(&mut rng, 600);
+
+        // Reference: the past of every block, by breadth-first search over its parents
+        let mut past: Vec<HashSet<usize>> = vec![];
+        for block_parents in &parents {
+            let mut reached = HashSet::new();
+            let mut queue: VecDeque<usize> = block_parents.iter().copied().collect();
+            while let Some(block) = queue.pop_front() {
+                if reached.insert(block) {
+                    queue.extend(&parents[block]);
+                }
+            }
+            past.push(reached);
+        }
+
+        let mut index = ReachabilityIndex::default();
+        for (i, block_parents) in parents.iter().enumerate() {
+            // The parent with the largest past stands in for the GHOSTDAG selected parent
+            let selected_parent = block_parents.iter().copied().max_by_key(|parent| (past[*parent].len(), *parent));
+            let mergeset: Vec<String> = match selected_parent {
+                Some(selected_parent) => past[i].iter()
+                    .filter(|block| **block != selected_parent && !past[selected_parent].contains(block))
+                    .map(|block| name(*block))
+                    .collect(),
+                None => vec![],
+            };
+            index.add_block(&name(i), selected_parent.map(name).as_deref(), &mergeset);
+        }
+
+        for i in 0..parents.len() {
+            for (j, past_of_j) in past.iter().enumerate() {
+                let expected = i == j || past_of_j.contains(&i);
+                assert_eq!(index.is_dag_ancestor_of(&name(i), &name(j)), expected, "seed {}: {} vs {}", seed, i, j);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// tests/rest.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::http::HttpRequest;
+use blockdag::mining::MiningEngine;
+use blockdag::rest::handle_rest;
+use blockdag::rpc::RpcContext;
+use blockdag::shutdown::Shutdown;
+use common::{mine, regtest_dag, MINER};
+use serde_json::Value;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+fn context(dag: BlockDAG) -> RpcContext {
+    let metrics = dag.metrics();
+    RpcContext { dag: Arc::new(Mutex::new(dag)), peers: Default::default(), shutdown: Shutdown::new(), metrics }
+}
+
+fn get(context: &RpcContext, path: &str, query: &str) -> (u16, Value) {
+    let request = HttpRequest { method: "GET".to_string(), path: path.to_string(), query: query.to_string(), headers: Default::default(), body: vec![] };
+    let response = handle_rest(context, &request);
+    (response.status, serde_json::from_slice(&response.body).unwrap())
+}
+
+fn page(context: &RpcContext, path: &str, query: &str) -> (usize, usize, usize, usize) {
+    let (status, body) = get(context, path, query);
+    assert_eq!(status, 200, "{}", body);
+    let field = |name: &str| body[name].as_u64().unwrap() as usize;
+    (body["items"].as_array().unwrap().len(), field("offset"), field("limit"), field("total"))
+}
+
+#[test]
+fn lists_page_within_bounds() {
+    let mut dag = regtest_dag();
+    for _ in 0..60 {
+        mine(&mut dag, MINER);
+    }
+    let context = context(dag);
+
+    // (items, offset, limit, total)
+    assert_eq!(page(&context, "/api/difficulty", ""), (50, 0, 50, 61));
+    assert_eq!(page(&context, "/api/difficulty", "offset=50"), (11, 50, 50, 61));
+    assert_eq!(page(&context, "/api/difficulty", "offset=55&limit=3"), (3, 55, 3, 61));
+    assert_eq!(page(&context, "/api/difficulty", "offset=61"), (0, 61, 50, 61));
+    assert_eq!(page(&context, "/api/difficulty", &format!("offset={}", usize::MAX)), (0, usize::MAX, 50, 61));
+    assert_eq!(page(&context, "/api/difficulty", "limit=0"), (0, 0, 0, 61));
+    // Oversized pages are capped
+    assert_eq!(page(&context, "/api/difficulty", "limit=1000000"), (61, 0, 500, 61));
+
+    for query in ["offset=-1", "limit=ten", "offset=", "limit=99999999999999999999999"] {
+        assert_eq!(get(&context, "/api/difficulty", query).0, 400, "accepted {}", query);
+    }
+}
+
+#[test]
+fn block_relations_page_and_unknown_blocks_are_not_found() {
+    let mut dag = regtest_dag();
+    let genesis = dag.tips[0].clone();
+    let template = dag.block_template(MINER).unwrap();
+    for extra_nonce in 0..7 {
+        let block = MiningEngine::new(1).solve(&template.with_extra_nonce(extra_nonce), &AtomicBool::new(false)).unwrap();
+        dag.submit_block(block).unwrap();
+    }
+    let context = context(dag);
+
+    let children = format!("/api/blocks/{}/children", genesis);
+    assert_eq!(page(&context, &children, "offset=5&limit=10"), (2, 5, 10, 7));
+    assert_eq!(page(&context, &format!("/api/blocks/{}/parents", genesis), ""), (0, 0, 50, 0));
+    assert_eq!(get(&context, "/api/blocks/00ff/children", "").0, 404);
+    assert_eq!(get(&context, "/api/blocks/00ff", "").0, 404);
+    assert_eq!(get(&context, "/api/nothing", "").0, 404);
+
+    let request = HttpRequest { method: "POST".to_string(), path: "/api/tips".to_string(), query: String::new(), headers: Default::default(), body: vec![] };
+    assert_eq!(handle_rest(&context, &request).status, 405);
+}
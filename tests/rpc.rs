@@ -0,0 +1,108 @@
+// tests/rpc.rs
+
+mod common;
+
+use blockdag::block::BlockTemplate;
+use blockdag::blockdag::BlockDAG;
+use blockdag::http::HttpRequest;
+use blockdag::mining::MiningEngine;
+use blockdag::params::SIMNET;
+use blockdag::rpc::{handle_http, RpcContext};
+use blockdag::shutdown::Shutdown;
+use common::{regtest_dag, MINER};
+use serde_json::{json, Value};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+fn context(dag: BlockDAG) -> RpcContext {
+    let metrics = dag.metrics();
+    RpcContext { dag: Arc::new(Mutex::new(dag)), peers: Default::default(), shutdown: Shutdown::new(), metrics }
+}
+
+fn post(context: &RpcContext, body: &[u8]) -> (u16, Option<Value>) {
+    let request = HttpRequest { method: "POST".to_string(), path: "/".to_string(), query: String::new(), headers: Default::default(), body: body.to_vec() };
+    let response = handle_http(context, request);
+    (response.status, serde_json::from_slice(&response.body).ok())
+}
+
+fn call(context: &RpcContext, method: &str, params: Value) -> Value {
+    let (_, response) = post(context, json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 7 }).to_string().as_bytes());
+    let response = response.unwrap();
+    assert_eq!(response["id"], 7);
+    response
+}
+
+fn error_code(response: &Value) -> i64 {
+    response["error"]["code"].as_i64().unwrap_or_else(|| panic!("not an error: {}", response))
+}
+
+#[test]
+fn malformed_requests_get_standard_error_codes() {
+    let context = context(regtest_dag());
+    let (status, response) = post(&context, br#"{"jsonrpc": "#);
+    assert_eq!((status, error_code(&response.unwrap())), (200, -32700));
+    let (_, response) = post(&context, b"[]");
+    assert_eq!(error_code(&response.unwrap()), -32600);
+    let (_, response) = post(&context, br#"{"method": "getTips", "id": 1}"#);
+    let response = response.unwrap();
+    assert_eq!((error_code(&response), response["id"].clone()), (-32600, json!(1)));
+
+    let request = HttpRequest { method: "GET".to_string(), path: "/".to_string(), query: String::new(), headers: Default::default(), body: vec![] };
+    assert_eq!(handle_http(&context, request).status, 405);
+}
+
+#[test]
+fn method_errors_get_their_codes() {
+    let context = context(regtest_dag());
+    assert_eq!(error_code(&call(&context, "getTipz", json!([]))), -32601);
+    assert_eq!(error_code(&call(&context, "getBalance", json!([]))), -32602);
+    assert_eq!(error_code(&call(&context, "getBalance", json!([42]))), -32602);
+    assert_eq!(error_code(&call(&context, "getBlock", json!(["00ff"]))), -32001);
+    assert_eq!(error_code(&call(&context, "setLogLevel", json!(["consensus=loud"]))), -32602);
+
+    let unsigned = json!({ "sender": MINER, "receiver": MINER, "amount": 1, "fee": 0, "signature": "" });
+    assert_eq!(error_code(&call(&context, "submitTransaction", json!([unsigned]))), -32000);
+    let mut block = context.dag.lock().unwrap().blocks.values().next().unwrap().clone();
+    assert_eq!(error_code(&call(&context, "submitBlock", json!([block.clone()]))), -32000);
+    block.hash = "00ff".to_string();
+    assert_eq!(error_code(&call(&context, "submitBlock", json!([block]))), -32000);
+}
+
+#[test]
+fn batches_and_notifications() {
+    let context = context(regtest_dag());
+    let batch = json!([
+        { "jsonrpc": "2.0", "method": "getTips", "id": 1 },
+        { "jsonrpc": "2.0", "method": "nope", "id": 2 },
+        { "jsonrpc": "2.0", "method": "getTips" },
+    ]);
+    let (_, response) = post(&context, batch.to_string().as_bytes());
+    let responses = response.unwrap();
+    assert_eq!(responses.as_array().unwrap().len(), 2);
+    assert_eq!(responses[0]["result"], json!([context.dag.lock().unwrap().tips[0]]));
+    assert_eq!(error_code(&responses[1]), -32601);
+
+    let (status, response) = post(&context, br#"{"jsonrpc": "2.0", "method": "getTips"}"#);
+    assert_eq!((status, response), (204, None));
+}
+
+#[test]
+fn submitted_blocks_must_meet_the_expected_difficulty() {
+    let context = context(BlockDAG::new(&SIMNET));
+    let template: BlockTemplate = serde_json::from_value(call(&context, "getBlockTemplate", json!([MINER]))["result"].clone()).unwrap();
+    assert_eq!(template.difficulty, SIMNET.genesis.difficulty);
+    let block_count = context.dag.lock().unwrap().block_count;
+
+    // A block claiming a lower difficulty meets its own target but not the node's
+    let mut easy = template.clone();
+    easy.difficulty -= 1;
+    let block = MiningEngine::new(1).solve(&easy, &AtomicBool::new(false)).unwrap();
+    let response = call(&context, "submitBlock", json!([block]));
+    assert_eq!(error_code(&response), -32000);
+    assert_eq!(context.dag.lock().unwrap().block_count, block_count);
+
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    assert_eq!(call(&context, "submitBlock", json!([block.clone()]))["result"]["hash"], json!(block.hash));
+    let dag = context.dag.lock().unwrap();
+    assert_eq!((dag.block_count, dag.current_supply), (block_count + 1, block.reward));
+}
@@ -0,0 +1,90 @@
+// tests/snapshot.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::snapshot::LedgerSnapshot;
+use common::{mine, regtest_dag, scratch_dir, MINER};
+use std::fs;
+
+fn mined_dag(blocks: usize) -> BlockDAG {
+    let mut dag = regtest_dag();
+    for _ in 0..blocks {
+        mine(&mut dag, MINER);
+    }
+    dag
+}
+
+#[test]
+fn snapshot_round_trips_through_a_file_into_a_fresh_node() {
+    let dag = mined_dag(5);
+    let tip = dag.tips[0].clone();
+    let dir = scratch_dir("snapshot-round-trip");
+    let path = dir.join("snapshot.json");
+    let snapshot = dag.export_snapshot(&tip).unwrap();
+    snapshot.save_to_file(&path).unwrap();
+
+    let loaded = LedgerSnapshot::load_from_file(&path).unwrap();
+    assert_eq!(loaded.commitment, snapshot.commitment);
+    assert_eq!(loaded.headers.len(), 5);
+    let mut fresh = regtest_dag();
+    fresh.import_snapshot(loaded, &snapshot.commitment).unwrap();
+    assert_eq!((fresh.pruning_point.clone(), fresh.tips.clone()), (tip.clone(), vec![tip.clone()]));
+    assert_eq!((fresh.current_supply, fresh.get_balance(MINER)), (dag.current_supply, dag.get_balance(MINER)));
+    assert_eq!(fresh.blue_score(&tip), dag.blue_score(&tip));
+    assert_eq!(fresh.block_count, dag.block_count);
+
+    // The imported node carries on from the snapshot's block
+    let block = mine(&mut fresh, MINER);
+    assert_eq!(block.previous_hashes, vec![tip]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn refuses_snapshot_with_unexpected_commitment() {
+    let dag = mined_dag(3);
+    let snapshot = dag.export_snapshot(&dag.tips[0]).unwrap();
+    let other = dag.export_snapshot(&dag.selected_chain()[1]).unwrap();
+    let error = regtest_dag().import_snapshot(snapshot.clone(), &other.commitment).err().unwrap();
+    assert!(error.to_string().contains("does not match the expected"));
+
+    // Commitments compare regardless of case and surrounding whitespace
+    let expected = format!(" {}\n", snapshot.commitment.to_uppercase());
+    snapshot.verify(&expected).unwrap();
+}
+
+#[test]
+fn refuses_to_import_into_a_used_dag() {
+    let dag = mined_dag(3);
+    let snapshot = dag.export_snapshot(&dag.tips[0]).unwrap();
+    let mut used = mined_dag(1);
+    assert!(used.import_snapshot(snapshot.clone(), &snapshot.commitment).is_err());
+}
+
+#[test]
+fn refuses_tampered_snapshot() {
+    let dag = mined_dag(3);
+    let snapshot = dag.export_snapshot(&dag.tips[0]).unwrap();
+
+    let mut inflated = snapshot.clone();
+    *inflated.ledger.balances.get_mut(MINER).unwrap() += 1_000;
+    let error = inflated.verify(&snapshot.commitment).err().unwrap();
+    assert!(error.to_string().contains("does not match its commitment"));
+
+    // Recomputing the snapshot's own commitment doesn't help: the block's state root pins the ledger
+    inflated.commitment = LedgerSnapshot::compute_commitment(&inflated.network, &inflated.block.hash, &inflated.ledger);
+    let error = regtest_dag().import_snapshot(inflated.clone(), &inflated.commitment).err().unwrap();
+    assert!(error.to_string().contains("state commitment"));
+}
+
+#[test]
+fn refuses_snapshot_without_state_commitment() {
+    let dag = mined_dag(3);
+    let mut snapshot = dag.export_snapshot(&dag.tips[0]).unwrap();
+    // Without a state root, as on blocks predating commitments, any ledger would pass
+    snapshot.block.state_commitment.clear();
+    *snapshot.ledger.balances.get_mut(MINER).unwrap() += 1_000;
+    snapshot.commitment = LedgerSnapshot::compute_commitment(&snapshot.network, &snapshot.block.hash, &snapshot.ledger);
+    let error = regtest_dag().import_snapshot(snapshot.clone(), &snapshot.commitment).err().unwrap();
+    assert!(error.to_string().contains("no state commitment"));
+}
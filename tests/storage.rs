@@ -0,0 +1,251 @@
+// tests/storage.rs
+
+mod common;
+
+use blockdag::blockdag::BlockDAG;
+use blockdag::mining::MiningEngine;
+use blockdag::params::REGTEST;
+use blockdag::rpc::{dispatch, RpcContext};
+use blockdag::shutdown::Shutdown;
+use blockdag::storage::{write_atomic, Keyspace, LogStore, Storage, WriteBatch};
+use common::{mine, scratch_dir, MINER};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+fn put(store: &mut LogStore, key: &str, value: &str) {
+    let mut batch = WriteBatch::new();
+    batch.put(Keyspace::State, key, value.as_bytes().to_vec());
+    store.write_batch(batch).unwrap();
+}
+
+fn get(store: &LogStore, key: &str) -> Option<String> {
+    store.get(Keyspace::State, key).unwrap().map(|value| String::from_utf8(value).unwrap())
+}
+
+#[test]
+fn log_store_replays_batches_on_reopen() {
+    let dir = scratch_dir("storage-replay");
+    let path = dir.join("blockdag.db");
+    let mut store = LogStore::open(&path).unwrap();
+    assert!(store.is_empty().unwrap());
+    let mut batch = WriteBatch::new();
+    batch.put(Keyspace::Blocks, "b", b"block".to_vec());
+    batch.put(Keyspace::Tips, "b", vec![]);
+    batch.put(Keyspace::State, "a", b"1".to_vec());
+    store.write_batch(batch).unwrap();
+    let mut batch = WriteBatch::new();
+    batch.delete(Keyspace::Tips, "b");
+    batch.put(Keyspace::State, "a", b"2".to_vec());
+    store.write_batch(batch).unwrap();
+    drop(store);
+
+    let store = LogStore::open(&path).unwrap();
+    assert_eq!(get(&store, "a").as_deref(), Some("2"));
+    assert_eq!(store.get(Keyspace::Blocks, "b").unwrap(), Some(b"block".to_vec()));
+    assert!(store.scan(Keyspace::Tips).unwrap().is_empty());
+    // Keyspaces are separate namespaces
+    assert!(store.get(Keyspace::Headers, "b").unwrap().is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compaction_keeps_live_entries_only() {
+    let dir = scratch_dir("storage-compact");
+    let path = dir.join("blockdag.db");
+    let mut store = LogStore::open(&path).unwrap();
+    for i in 0..100 {
+        put(&mut store, "counter", &i.to_string());
+    }
+    let before = fs::metadata(&path).unwrap().len();
+    store.compact().unwrap();
+    put(&mut store, "other", "x");
+    assert!(fs::metadata(&path).unwrap().len() < before);
+    drop(store);
+
+    let store = LogStore::open(&path).unwrap();
+    assert_eq!(get(&store, "counter").as_deref(), Some("99"));
+    assert_eq!(get(&store, "other").as_deref(), Some("x"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn read_only_store_refuses_writes() {
+    let dir = scratch_dir("storage-read-only");
+    let path = dir.join("blockdag.db");
+    put(&mut LogStore::open(&path).unwrap(), "a", "1");
+    let mut store = LogStore::open_read_only(&path).unwrap();
+    assert_eq!(get(&store, "a").as_deref(), Some("1"));
+    let mut batch = WriteBatch::new();
+    batch.put(Keyspace::State, "a", b"2".to_vec());
+    assert!(store.write_batch(batch).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dag_survives_reopening_its_store() {
+    let dir = scratch_dir("storage-dag");
+    let path = dir.join("blockdag.db");
+    let mut dag = BlockDAG::open(Box::new(LogStore::open(&path).unwrap()), &REGTEST, None).unwrap();
+    for _ in 0..3 {
+        let block = mine(&mut dag, MINER);
+        dag.persist_block(&block.hash).unwrap();
+    }
+    let (tips, supply) = (dag.tips.clone(), dag.current_supply);
+    drop(dag);
+
+    let dag = BlockDAG::open(Box::new(LogStore::open(&path).unwrap()), &REGTEST, None).unwrap();
+    assert_eq!(dag.blocks.len(), 4);
+    assert_eq!((dag.tips.clone(), dag.current_supply), (tips, supply));
+    assert_eq!(dag.get_balance(MINER), supply);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Writes three single-key batches and returns the log's size after each.
+fn three_records(path: &std::path::Path) -> Vec<u64> {
+    let mut store = LogStore::open(path).unwrap();
+    ["1", "2", "3"].iter().map(|value| {
+        put(&mut store, value, value);
+        fs::metadata(path).unwrap().len()
+    }).collect()
+}
+
+#[test]
+fn discards_torn_final_record() {
+    let dir = scratch_dir("storage-torn");
+    let path = dir.join("blockdag.db");
+    let sizes = three_records(&path);
+    for torn_len in [sizes[2] - 1, sizes[1] + 10] {
+        let log = fs::read(&path).unwrap();
+        fs::write(&path, &log[..torn_len as usize]).unwrap();
+
+        let mut store = LogStore::open(&path).unwrap();
+        assert_eq!((get(&store, "1").as_deref(), get(&store, "2").as_deref(), get(&store, "3")), (Some("1"), Some("2"), None));
+        assert_eq!(fs::metadata(&path).unwrap().len(), sizes[1]);
+        put(&mut store, "3", "3");
+    }
+    assert_eq!(get(&LogStore::open(&path).unwrap(), "3").as_deref(), Some("3"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reads_logs_checksummed_before_lengths_were() {
+    let dir = scratch_dir("storage-legacy");
+    let path = dir.join("blockdag.db");
+    let sizes = three_records(&path);
+    // Checksums over the payload alone, as older releases wrote them
+    let mut log = fs::read(&path).unwrap();
+    let mut start = 0;
+    for end in sizes.iter().map(|size| *size as usize) {
+        let checksum = Sha256::digest(&log[start + 36..end]);
+        log[start + 4..start + 36].copy_from_slice(&checksum);
+        start = end;
+    }
+
+    // A log of a single record, as compaction leaves it, must not pass for a torn write
+    for len in [sizes[0], sizes[2]] {
+        fs::write(&path, &log[..len as usize]).unwrap();
+        let store = LogStore::open(&path).unwrap();
+        assert_eq!(get(&store, "1").as_deref(), Some("1"));
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+    }
+    assert_eq!(get(&LogStore::open(&path).unwrap(), "3").as_deref(), Some("3"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn refuses_log_damaged_before_its_end() {
+    let dir = scratch_dir("storage-corrupt");
+    let path = dir.join("blockdag.db");
+    let sizes = three_records(&path);
+    let log = fs::read(&path).unwrap();
+
+    // A length field pointing past the end of the log, as if the rest were a torn write
+    let mut damaged = log.clone();
+    damaged[sizes[0] as usize..sizes[0] as usize + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    // A length field off by one, which the checksum covers
+    let mut shortened = log.clone();
+    shortened[sizes[0] as usize + 3] ^= 0x01;
+    // A flipped payload byte in the first record
+    let mut flipped = log.clone();
+    flipped[sizes[0] as usize - 1] ^= 0x01;
+
+    for corrupted in [damaged, shortened, flipped] {
+        fs::write(&path, &corrupted).unwrap();
+        let error = LogStore::open(&path).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(LogStore::open_read_only(&path).is_err());
+        // Nothing is truncated away
+        assert_eq!(fs::read(&path).unwrap(), corrupted);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn atomic_write_replaces_whole_file() {
+    let dir = scratch_dir("storage-atomic");
+    let path = dir.join("blockdag.json");
+    write_atomic(&path, b"first").unwrap();
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"second");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A log store whose writes fail while `failing` is set, like a full disk.
+struct FlakyStore {
+    inner: LogStore,
+    failing: Arc<AtomicBool>,
+}
+
+impl Storage for FlakyStore {
+    fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>, std::io::Error> {
+        self.inner.get(keyspace, key)
+    }
+
+    fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>, std::io::Error> {
+        self.inner.scan(keyspace)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), std::io::Error> {
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("No space left on device"));
+        }
+        self.inner.write_batch(batch)
+    }
+}
+
+#[test]
+fn blocks_that_failed_to_store_are_reported_and_written_later() {
+    let dir = scratch_dir("storage-flaky");
+    let path = dir.join("blockdag.db");
+    let failing = Arc::new(AtomicBool::new(false));
+    let store = FlakyStore { inner: LogStore::open(&path).unwrap(), failing: failing.clone() };
+    let dag = BlockDAG::open(Box::new(store), &REGTEST, None).unwrap();
+    let context = RpcContext { metrics: dag.metrics(), dag: Arc::new(Mutex::new(dag)), peers: Default::default(), shutdown: Shutdown::new() };
+
+    failing.store(true, Ordering::Relaxed);
+    let template = context.dag.lock().unwrap().block_template(MINER).unwrap();
+    let block = MiningEngine::new(1).solve(&template, &AtomicBool::new(false)).unwrap();
+    let error = dispatch(&context, "submitBlock", &json!([block])).unwrap_err();
+    assert_eq!(error.code, -32603);
+    assert!(error.message.contains("could not be stored"), "{}", error.message);
+    assert!(context.dag.lock().unwrap().flush().is_err());
+
+    // Once the disk works again the next block carries the earlier one along
+    failing.store(false, Ordering::Relaxed);
+    let mut dag = context.dag.lock().unwrap();
+    let next = mine(&mut dag, MINER);
+    dag.persist_block(&next.hash).unwrap();
+    let (tips, supply) = (dag.tips.clone(), dag.current_supply);
+    drop(dag);
+    drop(context);
+
+    let dag = BlockDAG::open(Box::new(LogStore::open(&path).unwrap()), &REGTEST, None).unwrap();
+    assert_eq!(dag.blocks.len(), 3);
+    assert!(dag.blocks.contains_key(&block.hash));
+    assert_eq!((dag.tips.clone(), dag.current_supply), (tips, supply));
+    fs::remove_dir_all(dir).unwrap();
+}
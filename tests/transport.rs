@@ -0,0 +1,166 @@
+// tests/transport.rs
+
+use blockdag::block::Block;
+use blockdag::messages::Message;
+use blockdag::params::{MAINNET, TESTNET};
+use blockdag::transport::{Connection, NodeIdentity, TransportMode};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Opens an encrypted session, returning the initiator and responder ends.
+async fn encrypted_pair(initiator: &NodeIdentity, responder: &NodeIdentity) -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (client, server) = tokio::join!(
+        Connection::connect(&address, MAINNET.magic, TransportMode::Encrypted, initiator),
+        async {
+            let (socket, _) = listener.accept().await.unwrap();
+            Connection::accept(socket, MAINNET.magic, TransportMode::Encrypted, responder).await
+        },
+    );
+    (client.unwrap(), server.unwrap())
+}
+
+/// A block whose JSON is far larger than a handshake frame.
+fn large_block() -> Block {
+    let mut block = MAINNET.genesis_block();
+    block.message = Some("x".repeat(64 * 1024));
+    block
+}
+
+#[tokio::test]
+async fn encrypted_session_authenticates_both_sides() {
+    let (initiator, responder) = (NodeIdentity::generate(), NodeIdentity::generate());
+    let (mut client, mut server) = encrypted_pair(&initiator, &responder).await;
+    assert!(client.is_encrypted() && server.is_encrypted());
+    assert_eq!(client.remote_id, Some(responder.node_id()));
+    assert_eq!(server.remote_id, Some(initiator.node_id()));
+
+    // Frames beyond the handshake limit flow once both peers are authenticated
+    client.send(&Message::NewBlock(large_block())).await.unwrap();
+    match server.recv().await.unwrap() {
+        Some(Message::NewBlock(block)) => assert_eq!(block.message, large_block().message),
+        other => panic!("unexpected message {:?}", other),
+    }
+    server.send(&Message::Tip("abc".to_string())).await.unwrap();
+    assert!(matches!(client.recv().await.unwrap(), Some(Message::Tip(hash)) if hash == "abc"));
+}
+
+#[tokio::test]
+async fn rejects_peer_on_another_network() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let identity = NodeIdentity::generate();
+    let client = tokio::spawn(async move {
+        let identity = NodeIdentity::generate();
+        Connection::connect(&address, TESTNET.magic, TransportMode::Encrypted, &identity).await.map(|_| ())
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let error = Connection::accept(socket, MAINNET.magic, TransportMode::Encrypted, &identity).await.err().unwrap();
+    assert!(error.to_string().contains("different network"));
+    assert!(client.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn rejects_tampered_frame() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_address = listener.local_addr().unwrap();
+    let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_address = proxy.local_addr().unwrap().to_string();
+
+    // Relays the initiator's bytes, flipping one inside the first frame after the handshake:
+    // the 40-byte hello and the 120-byte sealed identity proof come first
+    tokio::spawn(async move {
+        let (client, _) = proxy.accept().await.unwrap();
+        let server = TcpStream::connect(server_address).await.unwrap();
+        let (mut client_read, mut client_write) = client.into_split();
+        let (mut server_read, mut server_write) = server.into_split();
+        tokio::spawn(async move { tokio::io::copy(&mut server_read, &mut client_write).await });
+        let mut relayed = 0;
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = client_read.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            for (i, byte) in buffer[..read].iter_mut().enumerate() {
+                if relayed + i == 40 + 120 + 8 {
+                    *byte ^= 0x01;
+                }
+            }
+            relayed += read;
+            if server_write.write_all(&buffer[..read]).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = tokio::spawn(async move {
+        let identity = NodeIdentity::generate();
+        let mut conn = Connection::connect(&proxy_address, MAINNET.magic, TransportMode::Encrypted, &identity).await.unwrap();
+        conn.send(&Message::RequestTip).await.unwrap();
+        conn
+    });
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut server = Connection::accept(socket, MAINNET.magic, TransportMode::Encrypted, &NodeIdentity::generate()).await.unwrap();
+    let error = server.recv().await.err().unwrap();
+    assert!(error.to_string().contains("authentication"));
+    drop(client.await.unwrap());
+}
+
+#[tokio::test]
+async fn limits_frame_size_before_authentication() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+
+    // Announce a 1 MiB hello; the responder must refuse it without waiting for the payload
+    stream.write_all(&MAINNET.magic).await.unwrap();
+    stream.write_all(&(1024u32 * 1024).to_be_bytes()).await.unwrap();
+    let error = Connection::accept(socket, MAINNET.magic, TransportMode::Encrypted, &NodeIdentity::generate()).await.err().unwrap();
+    assert!(error.to_string().contains("too large"));
+}
+
+#[tokio::test]
+async fn plain_session_carries_large_frames() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let identity = NodeIdentity::generate();
+    let mut client = Connection::connect(&address, MAINNET.magic, TransportMode::Plain, &identity).await.unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut server = Connection::accept(socket, MAINNET.magic, TransportMode::Plain, &identity).await.unwrap();
+    assert!(!server.is_encrypted());
+
+    client.send(&Message::NewBlock(large_block())).await.unwrap();
+    assert!(matches!(server.recv().await.unwrap(), Some(Message::NewBlock(_))));
+    drop(client);
+    assert!(server.recv().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn pinned_peer_id_must_match_the_responder() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let responder = NodeIdentity::generate();
+    let responder_id = responder.node_id();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let _ = Connection::accept(socket, MAINNET.magic, TransportMode::Encrypted, &responder).await;
+        }
+    });
+    let initiator = NodeIdentity::generate();
+
+    // Another node's id fails the handshake before we prove our own identity
+    let impostor = format!("{}@{}", NodeIdentity::generate().node_id(), address);
+    let error = Connection::connect(&impostor, MAINNET.magic, TransportMode::Encrypted, &initiator).await.err().unwrap();
+    assert!(error.to_string().contains(&format!("Peer is node {} instead of", responder_id)), "{}", error);
+
+    let pinned = format!("{}@{}", responder_id, address);
+    let client = Connection::connect(&pinned, MAINNET.magic, TransportMode::Encrypted, &initiator).await.unwrap();
+    assert_eq!(client.remote_id, Some(responder_id));
+    // A plain session cannot tell who answers
+    assert!(Connection::connect(&pinned, MAINNET.magic, TransportMode::Plain, &initiator).await.is_err());
+}
@@ -0,0 +1,84 @@
+// tests/websocket.rs
+
+mod common;
+
+use blockdag::node::Node;
+use common::{mine, regtest_config, regtest_dag, MINER, TIMEOUT};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+const RIVAL: &str = "d4b3c1a0e9f8d7c6b5a4938271605f4e3d2c1b0a9f8e7d6c5b4a392817065f4e";
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect(node: &Node) -> Socket {
+    connect_async(format!("ws://{}/ws", node.rpc_address().unwrap())).await.unwrap().0
+}
+
+async fn call(socket: &mut Socket, method: &str, params: Value) -> Value {
+    socket.send(Message::Text(json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 }).to_string())).await.unwrap();
+    next_json(socket).await
+}
+
+async fn next_json(socket: &mut Socket) -> Value {
+    loop {
+        match tokio::time::timeout(TIMEOUT, socket.next()).await.unwrap().unwrap().unwrap() {
+            Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscriber_receives_reorg_event() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let dag = node.dag();
+    let honest: Vec<String> = (0..2).map(|_| mine(&mut dag.lock().unwrap(), MINER).hash).collect();
+
+    let mut socket = connect(&node).await;
+    let response = call(&mut socket, "subscribe", json!(["reorg"])).await;
+    assert_eq!(response["result"]["topics"], json!(["reorg"]));
+
+    // A longer branch from genesis takes over the selected chain
+    let mut rival = regtest_dag();
+    let branch: Vec<_> = (0..3).map(|_| mine(&mut rival, RIVAL)).collect();
+    for block in &branch {
+        dag.lock().unwrap().submit_block(block.clone()).unwrap();
+    }
+
+    let notification = next_json(&mut socket).await;
+    assert_eq!(notification["method"], "notification");
+    let event = &notification["params"];
+    assert_eq!(event["event"], "reorg");
+    let removed: Vec<String> = serde_json::from_value(event["removed"].clone()).unwrap();
+    assert_eq!(removed, honest.into_iter().rev().collect::<Vec<_>>());
+    // The branch takes over once it outscores the honest chain, or on a tie with a smaller hash
+    let added: Vec<String> = serde_json::from_value(event["added"].clone()).unwrap();
+    let branch: Vec<String> = branch.iter().rev().map(|block| block.hash.clone()).collect();
+    assert!(branch.ends_with(&added) && added.len() >= 2);
+
+    node.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sessions_serve_rpc_and_reject_unknown_topics() {
+    let node = Node::builder(regtest_config()).in_memory().start().await.unwrap();
+    let mut socket = connect(&node).await;
+
+    let tips = node.dag().lock().unwrap().tips.clone();
+    assert_eq!(call(&mut socket, "getTips", json!([])).await["result"], json!(tips));
+    assert_eq!(call(&mut socket, "subscribe", json!(["blocks"])).await["error"]["code"], -32602);
+
+    // Unsubscribed topics stay quiet: only the subscribed blockAdded arrives
+    call(&mut socket, "subscribe", json!({ "topics": ["blockAdded"] })).await;
+    let block = mine(&mut node.dag().lock().unwrap(), MINER);
+    let notification = next_json(&mut socket).await;
+    assert_eq!((notification["params"]["event"].clone(), notification["params"]["hash"].clone()), (json!("blockAdded"), json!(block.hash)));
+    let response = call(&mut socket, "unsubscribe", json!(["blockAdded"])).await;
+    assert_eq!(response["result"]["topics"], json!([]));
+
+    node.stop().await.unwrap();
+}